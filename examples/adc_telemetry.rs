@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+
+extern crate panic_halt;
+
+use m48_robo_rust::telemetry::{Encoder, Message};
+use m48_robo_rust::{adc, prelude::*};
+
+/// All six ADC pins, decode with `cargo telemetry -m 1=u16,u16,u16,u16,u16,u16`
+const ADC_VALUES: u8 = 1;
+
+#[m48_robo_rust::entry]
+fn main() -> ! {
+    let dp = m48_robo_rust::Peripherals::take().unwrap();
+
+    let mut pinsd = dp.PORTD.split();
+
+    let mut serial = m48_robo_rust::Serial::new(
+        dp.USART0,
+        pinsd.pd0,
+        pinsd.pd1.into_output(&mut pinsd.ddr),
+        2400,
+    );
+
+    let mut adc = adc::Adc::new(dp.ADC, Default::default());
+
+    let portc = dp.PORTC.split();
+    let mut a0 = portc.pc5.into_analog_input(&mut adc);
+    let mut a1 = portc.pc4.into_analog_input(&mut adc);
+    let mut a2 = portc.pc3.into_analog_input(&mut adc);
+    let mut a3 = portc.pc2.into_analog_input(&mut adc);
+    let mut a4 = portc.pc1.into_analog_input(&mut adc);
+    let mut a5 = portc.pc0.into_analog_input(&mut adc);
+
+    let mut encoder = Encoder::new();
+
+    loop {
+        // 18 bytes per frame instead of about 50 for the text in `adc.rs`
+        let message = Message::new(ADC_VALUES)
+            .u16(nb::block!(adc.read(&mut a0)).void_unwrap())
+            .u16(nb::block!(adc.read(&mut a1)).void_unwrap())
+            .u16(nb::block!(adc.read(&mut a2)).void_unwrap())
+            .u16(nb::block!(adc.read(&mut a3)).void_unwrap())
+            .u16(nb::block!(adc.read(&mut a4)).void_unwrap())
+            .u16(nb::block!(adc.read(&mut a5)).void_unwrap());
+        encoder.send(&mut serial, &message).void_unwrap();
+
+        m48_robo_rust::delay_ms(100);
+    }
+}
@@ -0,0 +1,61 @@
+#![no_std]
+#![no_main]
+
+extern crate panic_halt;
+
+use m48_robo_rust::{
+    prelude::*,
+    serial::{BufferedSerial, Error},
+};
+
+#[m48_robo_rust::entry]
+fn main() -> ! {
+    let dp = m48_robo_rust::Peripherals::take().unwrap();
+
+    let mut pinsd = dp.PORTD.split();
+    let mut portb = dp.PORTB.split();
+
+    let serial = m48_robo_rust::Serial::new(
+        dp.USART0,
+        pinsd.pd0,
+        pinsd.pd1.into_output(&mut pinsd.ddr),
+        2400,
+    );
+    let mut serial = BufferedSerial::new(serial);
+
+    let mut led = portb.pb0.into_output(&mut portb.ddr);
+
+    unsafe {
+        // Enable interrupts
+        avr_device::interrupt::enable();
+    }
+
+    ufmt::uwriteln!(&mut serial, "Buffered echo from ATmega48P!\r").ok();
+
+    let mut loops: u16 = 0;
+    loop {
+        // Echo whatever arrived without waiting for more
+        loop {
+            match serial.read() {
+                Ok(byte) => {
+                    serial.write(byte).ok();
+                }
+                Err(nb::Error::Other(Error::Overrun)) => {
+                    ufmt::uwriteln!(&mut serial, "\r\n[overrun]\r").ok();
+                }
+                Err(nb::Error::Other(_)) => {
+                    ufmt::uwriteln!(&mut serial, "\r\n[bad byte]\r").ok();
+                }
+                Err(nb::Error::WouldBlock) => break,
+            }
+        }
+
+        // The "control loop" keeps its pace while the telemetry is sent
+        led.toggle().void_unwrap();
+        loops = loops.wrapping_add(1);
+        if loops % 100 == 0 {
+            ufmt::uwriteln!(&mut serial, "loops: {}\r", loops).ok();
+        }
+        m48_robo_rust::delay_ms(10);
+    }
+}
@@ -0,0 +1,92 @@
+#![no_std]
+#![no_main]
+
+extern crate panic_halt;
+
+use m48_robo_rust::config::{Config, Origin, Store};
+use m48_robo_rust::eeprom::{Eeprom, Persist};
+use m48_robo_rust::fixed::Q8_8;
+use m48_robo_rust::prelude::*;
+
+/// Calibration which has to survive a reset
+struct Calibration {
+    kp: Q8_8,
+    ki: Q8_8,
+    adc_offset: i16,
+}
+
+impl Default for Calibration {
+    fn default() -> Calibration {
+        Calibration {
+            kp: Q8_8::from_int(2),
+            ki: Q8_8::from_ratio(1, 4),
+            adc_offset: 0,
+        }
+    }
+}
+
+impl Persist for Calibration {
+    const SIZE: usize = 6;
+
+    fn save(&self, bytes: &mut [u8]) {
+        self.kp.save(&mut bytes[0..2]);
+        self.ki.save(&mut bytes[2..4]);
+        self.adc_offset.save(&mut bytes[4..6]);
+    }
+
+    fn load(bytes: &[u8]) -> Calibration {
+        Calibration {
+            kp: Q8_8::load(&bytes[0..2]),
+            ki: Q8_8::load(&bytes[2..4]),
+            adc_offset: i16::load(&bytes[4..6]),
+        }
+    }
+}
+
+impl Config for Calibration {
+    const VERSION: u8 = 1;
+}
+
+/// 16 bytes of room for the calibration, after the boot counter of the
+/// `eeprom` example
+static CALIBRATION: Store<Calibration> = Store::new(0x40, 16);
+
+#[m48_robo_rust::entry]
+fn main() -> ! {
+    let dp = m48_robo_rust::Peripherals::take().unwrap();
+
+    let mut pinsd = dp.PORTD.split();
+
+    let mut eeprom = Eeprom::new(dp.EEPROM);
+
+    let mut serial = m48_robo_rust::Serial::new(
+        dp.USART0,
+        pinsd.pd0,
+        pinsd.pd1.into_output(&mut pinsd.ddr),
+        2400,
+    );
+
+    let (mut calibration, origin) = CALIBRATION.load(&mut eeprom).unwrap();
+    match origin {
+        Origin::Stored => ufmt::uwriteln!(&mut serial, "Calibration loaded\r"),
+        Origin::Migrated(version) => {
+            ufmt::uwriteln!(&mut serial, "Calibration migrated from v{}\r", version)
+        }
+        Origin::Defaults => ufmt::uwriteln!(&mut serial, "Calibration defaults\r"),
+    }
+    .void_unwrap();
+    ufmt::uwriteln!(
+        &mut serial,
+        "kp: {} ki: {} offset: {}\r",
+        calibration.kp,
+        calibration.ki,
+        calibration.adc_offset
+    )
+    .void_unwrap();
+
+    // Pretend the robot calibrated itself
+    calibration.adc_offset += 1;
+    CALIBRATION.save(&mut eeprom, &calibration).unwrap();
+
+    loop {}
+}
@@ -2,15 +2,19 @@
 #![no_main]
 
 extern crate panic_halt;
+use m48_robo_rust::eeprom::{Eeprom, Ring};
 use m48_robo_rust::prelude::*;
 
+/// Boot counter spread over 16 slots, 48 bytes from address 0
+static BOOTS: Ring<u16> = Ring::new(0, 16);
+
 #[m48_robo_rust::entry]
 fn main() -> ! {
     let dp = m48_robo_rust::Peripherals::take().unwrap();
 
     let mut pinsd = dp.PORTD.split();
 
-    let eeprom = dp.EEPROM;
+    let mut eeprom = Eeprom::new(dp.EEPROM);
 
     let mut serial = m48_robo_rust::Serial::new(
         dp.USART0,
@@ -21,35 +25,16 @@ fn main() -> ! {
 
     ufmt::uwriteln!(&mut serial, "MCU EEPROM write/read ATmega48P!\r").void_unwrap();
 
-    let mut counter = ee_read(&eeprom, 0);
+    // Nothing was written yet on the first boot
+    let mut counter = BOOTS.read(&mut eeprom).unwrap().unwrap_or(0);
 
     ufmt::uwriteln!(&mut serial, "Counter readed from EEPROM: {}\r", counter).void_unwrap();
 
-    counter = counter.overflowing_add(1).0;
+    counter = counter.wrapping_add(1);
 
     ufmt::uwriteln!(&mut serial, "Counter writed to EEPROM: {}\r", counter).void_unwrap();
 
-    ee_write(&eeprom, 0, counter);
+    BOOTS.write(&mut eeprom, &counter).unwrap();
 
     loop {}
 }
-
-fn ee_write(eeprom: &m48_robo_rust::atmega48p::EEPROM, address: u8, value: u8) {
-    while eeprom.eecr.read().eepe().bit_is_set() {}
-
-    eeprom.eearl.write(|w| unsafe { w.bits(address) });
-    eeprom.eedr.write(|w| unsafe { w.bits(value) });
-
-    eeprom.eecr.modify(|_, w| w.eempe().set_bit());
-    eeprom.eecr.modify(|_, w| w.eepe().set_bit());
-}
-
-fn ee_read(eeprom: &m48_robo_rust::atmega48p::EEPROM, address: u8) -> u8 {
-    while eeprom.eecr.read().eepe().bit_is_set() {}
-
-    eeprom.eearl.write(|w| unsafe { w.bits(address) });
-
-    eeprom.eecr.modify(|_, w| w.eere().set_bit());
-
-    eeprom.eedr.read().bits()
-}
@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+
+extern crate panic_halt;
+
+use m48_robo_rust::{fixed::Q16_16, prelude::*};
+
+#[m48_robo_rust::entry]
+fn main() -> ! {
+    let dp = m48_robo_rust::Peripherals::take().unwrap();
+
+    let mut pinsd = dp.PORTD.split();
+
+    let mut serial = m48_robo_rust::Serial::new(
+        dp.USART0,
+        pinsd.pd0,
+        pinsd.pd1.into_output(&mut pinsd.ddr),
+        2400,
+    );
+
+    ufmt::uwriteln!(&mut serial, "Echo from ATmega48P!\r").void_unwrap();
+
+    let x = Q16_16::from_ratio(9, 2);
+
+    let y = Q16_16::from_ratio(29, 10);
+
+    let z = x + y * Q16_16::from_ratio(3, 2);
+
+    ufmt::uwriteln!(&mut serial, "for x = 4.5 and y = 2.9 x + y * 1.5 = {}\r", z).void_unwrap();
+
+    ufmt::uwriteln!(
+        &mut serial,
+        "sqrt(z) = {}, sin(x) = {}, atan2(y, x) = {}\r",
+        z.sqrt(),
+        x.sin(),
+        y.atan2(x)
+    )
+    .void_unwrap();
+
+    loop {}
+}
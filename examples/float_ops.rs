@@ -3,8 +3,7 @@
 
 extern crate panic_halt;
 
-use m48_robo_rust::prelude::*;
-use micromath::F32Ext;
+use m48_robo_rust::{fmt::Float, prelude::*};
 
 #[m48_robo_rust::entry]
 fn main() -> ! {
@@ -30,31 +29,9 @@ fn main() -> ! {
     ufmt::uwriteln!(
         &mut serial,
         "for x = 4.5 and y = 2.9 x + y * 1.5 = {}\r",
-        UFloat(z)
+        Float::new(z, 2)
     )
     .void_unwrap();
 
     loop {}
 }
-
-struct UFloat(f32);
-
-use ufmt::{uDisplay, uWrite, uwrite, Formatter};
-
-impl uDisplay for UFloat {
-    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
-    where
-        W: uWrite + ?Sized,
-    {
-        match self.0 {
-            x if x.is_infinite() && x.is_sign_negative() => uwrite!(f, "-inf"),
-            x if x.is_infinite() => uwrite!(f, "inf"),
-            x if x.is_nan() => uwrite!(f, "nan"),
-            x => uwrite!(f, "{}.{}", x.floor() as i32, get_frac(x)),
-        }
-    }
-}
-
-fn get_frac(f: f32) -> u32 {
-    (f.abs().fract() * 10000.0) as u32
-}
@@ -7,14 +7,14 @@ use m48_robo_rust::dev::gpio;
 
 #[m48_robo_rust::entry]
 fn main() -> ! {
-    gpio::GPIOB.ddrb.modify(gpio::DDRB::DDB0::SET);
+    gpio::GPIOB.ddrb.sbi(gpio::DDRB::DDB0);
 
     loop {
-        gpio::GPIOB.portb.modify(gpio::PORTB::PORTB0::SET);
+        gpio::GPIOB.portb.sbi(gpio::PORTB::PORTB0);
 
         m48_robo_rust::delay_ms(200);
 
-        gpio::GPIOB.portb.modify(gpio::PORTB::PORTB0::CLEAR);
+        gpio::GPIOB.portb.cbi(gpio::PORTB::PORTB0);
 
         m48_robo_rust::delay_ms(200);
     }
@@ -0,0 +1,24 @@
+#![no_std]
+#![no_main]
+
+extern crate panic_halt;
+
+use m48_robo_rust::gpio::Pins;
+
+#[m48_robo_rust::entry]
+fn main() -> ! {
+    let pins = Pins::take().unwrap();
+
+    let mut led = pins.portb.pb0.into_output();
+    let button = pins.portd.pd2.into_pull_up_input();
+
+    loop {
+        if button.is_low() {
+            led.toggle();
+        } else {
+            led.set_low();
+        }
+
+        m48_robo_rust::delay_ms(200);
+    }
+}
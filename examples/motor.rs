@@ -0,0 +1,51 @@
+#![no_std]
+#![no_main]
+
+extern crate panic_halt;
+
+use m48_robo_rust::{
+    motor::{DualInput, Idle, Motor},
+    prelude::*,
+    pwm,
+};
+
+#[m48_robo_rust::entry]
+fn main() -> ! {
+    let dp = m48_robo_rust::Peripherals::take().unwrap();
+
+    let mut portb = dp.PORTB.split();
+    let mut portd = dp.PORTD.split();
+
+    // Differential drive on a TB6612, PWMA/PWMB on OC1A/OC1B
+    let mut timer = pwm::Timer1Pwm::new(dp.TC1, pwm::Prescaler::Prescale8);
+    let mut left = Motor::new(DualInput::new(
+        portb.pb1.into_output(&mut portb.ddr).into_pwm(&mut timer),
+        portd.pd4.into_output(&mut portd.ddr),
+        portd.pd5.into_output(&mut portd.ddr),
+    ));
+    let mut right = Motor::new(DualInput::new(
+        portb.pb2.into_output(&mut portb.ddr).into_pwm(&mut timer),
+        portd.pd6.into_output(&mut portd.ddr),
+        portd.pd7.into_output(&mut portd.ddr),
+    ));
+    left.set_acceleration(400);
+    right.set_acceleration(400);
+    left.set_dead_time(100);
+    right.set_dead_time(100);
+    left.set_idle(Idle::Brake);
+    right.set_idle(Idle::Brake);
+
+    // Forward, spin in place, backward
+    let moves = [(200, 200), (150, -150), (-200, -200)];
+    loop {
+        for &(l, r) in &moves {
+            left.set_speed(l);
+            right.set_speed(r);
+            for _ in 0..200 {
+                left.update(10);
+                right.update(10);
+                m48_robo_rust::delay_ms(10);
+            }
+        }
+    }
+}
@@ -0,0 +1,54 @@
+#![no_std]
+#![no_main]
+
+extern crate panic_halt;
+
+use m48_robo_rust::{
+    gpio::{mode::Output, Pin, Pins},
+    servo::{
+        multi::{Slot, Timer1},
+        Calibration,
+    },
+};
+
+#[m48_robo_rust::entry]
+fn main() -> ! {
+    let dp = m48_robo_rust::Peripherals::take().unwrap();
+    let pins = Pins::take().unwrap();
+
+    let timer = Timer1::new(dp.TC1);
+    let mut slots: [Slot<Pin<Output>>; 8] = Default::default();
+    let mut servos = timer.bank(&mut slots);
+    let mut attach = |pin| servos.attach(pin, Calibration::DEFAULT).ok().unwrap();
+    let legs = [
+        attach(pins.portc.pc0.into_output().downgrade()),
+        attach(pins.portc.pc1.into_output().downgrade()),
+        attach(pins.portc.pc2.into_output().downgrade()),
+        attach(pins.portc.pc3.into_output().downgrade()),
+        attach(pins.portd.pd4.into_output().downgrade()),
+        attach(pins.portd.pd5.into_output().downgrade()),
+        attach(pins.portd.pd6.into_output().downgrade()),
+        attach(pins.portd.pd7.into_output().downgrade()),
+    ];
+
+    unsafe {
+        // Enable interrupts
+        avr_device::interrupt::enable();
+    }
+
+    let mut step = 0;
+    loop {
+        // Alternate the two sets of legs
+        if !servos.is_moving() {
+            for (i, &leg) in legs.iter().enumerate() {
+                servos[leg].set_speed(120);
+                servos[leg].set_angle(if (i + step) % 2 == 0 { 30 } else { -30 });
+            }
+            step += 1;
+        }
+        servos.update(20);
+        // Waits while the pulses of the current frame are running
+        nb::block!(timer.flush(&servos)).ok();
+        m48_robo_rust::delay_ms(20);
+    }
+}
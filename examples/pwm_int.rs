@@ -4,10 +4,12 @@
 
 extern crate panic_halt;
 
-use m48_robo_rust::{hal::port, prelude::*, pwm};
+use core::sync::atomic::{AtomicBool, Ordering};
 
-static mut PIN: Option<port::portb::PB1<port::mode::Pwm<pwm::Timer1Pwm>>> = None;
-static mut DIRECTION: bool = false;
+use m48_robo_rust::{hal::port, interrupt::Shared, prelude::*, pwm};
+
+static PIN: Shared<port::portb::PB1<port::mode::Pwm<pwm::Timer1Pwm>>> = Shared::new();
+static DIRECTION: AtomicBool = AtomicBool::new(false);
 
 #[avr_device::entry]
 fn main() -> ! {
@@ -26,8 +28,9 @@ fn main() -> ! {
     pin.set_duty(128);
     pin.enable();
 
+    PIN.init(pin).ok();
+
     unsafe {
-        PIN = Some(pin);
         // Enable interrupts
         avr_device::interrupt::enable();
     }
@@ -36,20 +39,22 @@ fn main() -> ! {
 }
 
 #[avr_device::interrupt(atmega48p)]
-unsafe fn TIMER1_OVF() {
-    let mut duty = PIN.as_mut().unwrap().get_duty();
-    if DIRECTION {
-        if duty < 255 {
-            duty += 1;
+fn TIMER1_OVF() {
+    PIN.lock(|_, pin| {
+        let mut duty = pin.get_duty();
+        if DIRECTION.load(Ordering::Relaxed) {
+            if duty < 255 {
+                duty += 1;
+            } else {
+                DIRECTION.store(false, Ordering::Relaxed);
+            }
         } else {
-            DIRECTION = false;
+            if duty > 0 {
+                duty -= 1;
+            } else {
+                DIRECTION.store(true, Ordering::Relaxed);
+            }
         }
-    } else {
-        if duty > 0 {
-            duty -= 1;
-        } else {
-            DIRECTION = true;
-        }
-    }
-    PIN.as_mut().unwrap().set_duty(duty);
+        pin.set_duty(duty);
+    });
 }
@@ -4,9 +4,9 @@
 
 extern crate panic_halt;
 
-use m48_robo_rust::{hal::port::mode, prelude::*};
+use m48_robo_rust::{hal::port::mode, interrupt::Shared, prelude::*};
 
-static mut SERIAL: Option<m48_robo_rust::Serial<mode::Floating>> = None;
+static SERIAL: Shared<m48_robo_rust::Serial<mode::Floating>> = Shared::new();
 
 #[m48_robo_rust::entry]
 fn main() -> ! {
@@ -21,30 +21,26 @@ fn main() -> ! {
         2400,
     );
 
+    ufmt::uwriteln!(&mut serial, "Echo interrupt from ATmega48P!\r").void_unwrap();
+
     serial.interrupt_rxc(true);
+    SERIAL.init(serial).ok();
 
     unsafe {
-        SERIAL = Some(serial);
         // Enable interrupts
         avr_device::interrupt::enable();
     }
 
-    unsafe {
-        ufmt::uwriteln!(
-            &mut SERIAL.as_mut().unwrap(),
-            "Echo interrupt from ATmega48P!\r"
-        )
-        .void_unwrap();
-    }
-
     loop {}
 }
 
 #[avr_device::interrupt(atmega48p)]
-unsafe fn USART_RX() {
-    // Read a byte from the serial connection
-    let b = nb::block!(SERIAL.as_mut().unwrap().read()).void_unwrap();
-
-    // Answer
-    nb::block!(SERIAL.as_mut().unwrap().write(b)).void_unwrap();
+fn USART_RX() {
+    SERIAL.lock(|_, serial| {
+        // Read a byte from the serial connection
+        let b = nb::block!(serial.read()).void_unwrap();
+
+        // Answer
+        nb::block!(serial.write(b)).void_unwrap();
+    });
 }
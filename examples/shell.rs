@@ -0,0 +1,43 @@
+#![no_std]
+#![no_main]
+
+extern crate panic_halt;
+
+use m48_robo_rust::prelude::*;
+use m48_robo_rust::pwm;
+use m48_robo_rust::shell::{builtins, Shell};
+
+#[m48_robo_rust::entry]
+fn main() -> ! {
+    let dp = m48_robo_rust::Peripherals::take().unwrap();
+
+    let mut pinsd = dp.PORTD.split();
+    let mut portb = dp.PORTB.split();
+
+    let mut serial = m48_robo_rust::Serial::new(
+        dp.USART0,
+        pinsd.pd0,
+        pinsd.pd1.into_output(&mut pinsd.ddr),
+        2400,
+    );
+
+    let mut timer = pwm::Timer1Pwm::new(dp.TC1, pwm::Prescaler::Prescale8);
+    let pin = portb.pb1.into_output(&mut portb.ddr).into_pwm(&mut timer);
+    let mut mcu = builtins::Mcu::new(pin);
+
+    let commands = [
+        builtins::peek(),
+        builtins::poke(),
+        builtins::adc(),
+        builtins::pwm(),
+        builtins::eeprom(),
+    ];
+    let mut shell = Shell::new(&commands);
+
+    ufmt::uwriteln!(&mut serial, "ATmega48P shell, try help\r").void_unwrap();
+    shell.prompt(&mut serial);
+
+    loop {
+        shell.poll(&mut serial, &mut mcu);
+    }
+}
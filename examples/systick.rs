@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+
+extern crate panic_halt;
+
+use m48_robo_rust::{
+    gpio::Pins,
+    time::{self, Duration, Instant},
+};
+
+#[m48_robo_rust::entry]
+fn main() -> ! {
+    let pins = Pins::take().unwrap();
+
+    let mut blink_led = pins.portb.pb0.into_output();
+    let mut button_led = pins.portb.pb1.into_output();
+    let button = pins.portd.pd2.into_pull_up_input();
+
+    time::init();
+    unsafe {
+        // Enable interrupts
+        avr_device::interrupt::enable();
+    }
+
+    let period = Duration::from_millis(500);
+    let debounce = Duration::from_millis(20);
+
+    let mut next_blink = Instant::now() + period;
+    let mut pressed_since: Option<Instant> = None;
+
+    loop {
+        let now = Instant::now();
+
+        if now >= next_blink {
+            next_blink += period;
+            blink_led.toggle();
+        }
+
+        if button.is_low() {
+            let since = *pressed_since.get_or_insert(now);
+            if now - since >= debounce {
+                button_led.set_high();
+            }
+        } else {
+            pressed_since = None;
+            button_led.set_low();
+        }
+    }
+}
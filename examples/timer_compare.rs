@@ -4,31 +4,20 @@
 
 extern crate panic_halt;
 
-use m48_robo_rust::{delay_ms, hal::port::*, prelude::*};
+use core::sync::atomic::{AtomicU8, Ordering};
 
-static mut LEDS: Option<[Pin<mode::Output>; 7]> = None;
-static mut NUM: u8 = 0;
+use m48_robo_rust::{
+    delay_ms,
+    display::segment::{Multiplexed, Polarity},
+    hal::port::*,
+    interrupt::Shared,
+    prelude::*,
+};
 
-const SEG_A: u8 = 1u8 << 0;
-const SEG_B: u8 = 1u8 << 1;
-const SEG_C: u8 = 1u8 << 2;
-const SEG_D: u8 = 1u8 << 3;
-const SEG_E: u8 = 1u8 << 4;
-const SEG_F: u8 = 1u8 << 5;
-const SEG_G: u8 = 1u8 << 6;
+type Display = Multiplexed<[Pin<mode::Output>; 8], [Pin<mode::Output>; 1]>;
 
-const NUMS: [u8; 10] = [
-    SEG_A | SEG_B | SEG_C | SEG_D | SEG_E | SEG_F, // 0
-    SEG_B | SEG_C,                                 // 1
-    SEG_A | SEG_B | SEG_G | SEG_E | SEG_D,         // 2
-    SEG_A | SEG_B | SEG_G | SEG_C | SEG_D,         // 3
-    SEG_F | SEG_G | SEG_B | SEG_C,                 // 4
-    SEG_A | SEG_F | SEG_G | SEG_C | SEG_D,         // 5
-    SEG_F | SEG_G | SEG_C | SEG_D | SEG_E,         // 6
-    SEG_A | SEG_B | SEG_C,                         // 7
-    SEG_A | SEG_B | SEG_C | SEG_D | SEG_E | SEG_F | SEG_G, // 8
-    SEG_F | SEG_A | SEG_B | SEG_G | SEG_C,         // 9
-];
+static DISPLAY: Shared<Display> = Shared::new();
+static NUM: AtomicU8 = AtomicU8::new(0);
 
 #[m48_robo_rust::entry]
 fn main() -> ! {
@@ -37,40 +26,24 @@ fn main() -> ! {
     let mut portd = dp.PORTD.split();
     let mut portc = dp.PORTC.split();
 
-    let pc0 = portc.pc0.into_output(&mut portc.ddr);
-    let pc1 = portc.pc1.into_output(&mut portc.ddr);
-    let pc2 = portc.pc2.into_output(&mut portc.ddr);
-    let pc3 = portc.pc3.into_output(&mut portc.ddr);
-    let pc4 = portc.pc4.into_output(&mut portc.ddr);
-    let pd3 = portd.pd3.into_output(&mut portd.ddr);
-    let pd4 = portd.pd4.into_output(&mut portd.ddr);
-
-    let mut dp_seg = portd.pd2.into_output(&mut portd.ddr);
-
-    unsafe {
-        LEDS = Some([
-            pc0.downgrade(),
-            pc1.downgrade(),
-            pc2.downgrade(),
-            pc3.downgrade(),
-            pc4.downgrade(),
-            pd3.downgrade(),
-            pd4.downgrade(),
-        ]);
-
-        let leds = LEDS.as_mut().unwrap();
-
-        for i in 0..leds.len() {
-            leds[i].set_high().void_unwrap();
-
-            leds[if i < 1 { leds.len() - 1 } else { i - 1 }]
-                .set_low()
-                .void_unwrap();
-
-            delay_ms(250);
-        }
-        leds[leds.len() - 1].set_low().void_unwrap();
-    }
+    let display = Multiplexed::new(
+        [
+            portc.pc0.into_output(&mut portc.ddr).downgrade(),
+            portc.pc1.into_output(&mut portc.ddr).downgrade(),
+            portc.pc2.into_output(&mut portc.ddr).downgrade(),
+            portc.pc3.into_output(&mut portc.ddr).downgrade(),
+            portc.pc4.into_output(&mut portc.ddr).downgrade(),
+            portd.pd3.into_output(&mut portd.ddr).downgrade(),
+            portd.pd4.into_output(&mut portd.ddr).downgrade(),
+            portd.pd2.into_output(&mut portd.ddr).downgrade(),
+        ],
+        // Only the leftmost digit of the board is used
+        [portd.pd5.into_output(&mut portd.ddr).downgrade()],
+        Polarity::CommonCathode,
+    )
+    // Digits are switched by NPN transistors
+    .invert_digits();
+    DISPLAY.init(display).ok();
 
     let tc1 = dp.TC1;
 
@@ -84,27 +57,25 @@ fn main() -> ! {
         avr_device::interrupt::enable();
     }
 
+    let mut dp_on = false;
+
     loop {
-        dp_seg.toggle().void_unwrap();
+        dp_on = !dp_on;
+        DISPLAY.lock(|_, display| {
+            display.set_dp(0, dp_on);
+            display.refresh();
+        });
         delay_ms(250);
     }
 }
 
 #[avr_device::interrupt(atmega48p)]
-unsafe fn TIMER1_COMPA() {
-    let num_repr = NUMS[NUM as usize];
-    NUM = if NUM < 9 { NUM + 1 } else { 0 };
-    apply_segments(num_repr);
-}
-
-#[inline(always)]
-fn apply_segments(segments: u8) {
-    let leds = unsafe { LEDS.as_mut().unwrap() };
-    for i in 0..leds.len() {
-        if segments & (1u8 << i) != 0 {
-            leds[i].set_high().void_unwrap();
-        } else {
-            leds[i].set_low().void_unwrap();
-        }
-    }
+fn TIMER1_COMPA() {
+    let num = NUM.load(Ordering::Relaxed);
+    NUM.store(if num < 9 { num + 1 } else { 0 }, Ordering::Relaxed);
+
+    DISPLAY.lock(|_, display| {
+        display.set_char(0, (b'0' + num) as char);
+        display.refresh();
+    });
 }
@@ -0,0 +1,145 @@
+//! CPU clock configuration.
+//!
+//! The clock the MCU starts with is selected with exactly one cargo feature:
+//!
+//! | Feature | Source | Reset prescaler | CPU clock |
+//! | --- | --- | --- | --- |
+//! | `clock-1mhz` (default) | internal 8 MHz RC | 8 (`CKDIV8` fuse) | 1 MHz |
+//! | `clock-8mhz` | internal 8 MHz RC | 1 | 8 MHz |
+//! | `clock-16mhz` | external 16 MHz crystal | 1 | 16 MHz |
+//!
+//! The selected frequency drives [`Delay`], [`Serial`] and [`I2c`] through the
+//! [`Clock`] type. The system clock prescaler can also be changed at runtime
+//! with [`set_prescaler`], [`delay_ms`]/[`delay_us`] follow the change while
+//! peripherals configured before it have to be set up again, e.g. with
+//! [`ubrr`] for the USART baud rate.
+//!
+//! [`Delay`]: ../type.Delay.html
+//! [`Serial`]: ../type.Serial.html
+//! [`I2c`]: ../type.I2c.html
+//! [`Clock`]: type.Clock.html
+//! [`set_prescaler`]: fn.set_prescaler.html
+//! [`delay_ms`]: ../fn.delay_ms.html
+//! [`delay_us`]: ../fn.delay_us.html
+//! [`ubrr`]: fn.ubrr.html
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+#[cfg(not(any(
+    feature = "clock-1mhz",
+    feature = "clock-8mhz",
+    feature = "clock-16mhz"
+)))]
+compile_error!("select the CPU clock with one of the `clock-*` features");
+
+#[cfg(any(
+    all(feature = "clock-1mhz", feature = "clock-8mhz"),
+    all(feature = "clock-1mhz", feature = "clock-16mhz"),
+    all(feature = "clock-8mhz", feature = "clock-16mhz")
+))]
+compile_error!("only one of the `clock-*` features can be enabled");
+
+/// Frequency of the clock source in Hz
+#[cfg(any(feature = "clock-1mhz", feature = "clock-8mhz"))]
+pub const SOURCE_HZ: u32 = 8_000_000;
+#[cfg(feature = "clock-16mhz")]
+pub const SOURCE_HZ: u32 = 16_000_000;
+
+/// Prescaler shift (`CLKPS` value) the MCU starts with
+#[cfg(feature = "clock-1mhz")]
+pub const RESET_SHIFT: u8 = 3;
+#[cfg(any(feature = "clock-8mhz", feature = "clock-16mhz"))]
+pub const RESET_SHIFT: u8 = 0;
+
+/// CPU frequency in Hz after reset, this is what `Clock` describes
+pub const FREQ_HZ: u32 = SOURCE_HZ >> RESET_SHIFT;
+
+/// Compile-time clock of the HAL, see `FREQ_HZ`
+#[cfg(all(target_arch = "avr", feature = "clock-1mhz"))]
+pub type Clock = crate::hal::clock::MHz1;
+#[cfg(all(target_arch = "avr", feature = "clock-8mhz"))]
+pub type Clock = crate::hal::clock::MHz8;
+#[cfg(all(target_arch = "avr", feature = "clock-16mhz"))]
+pub type Clock = crate::hal::clock::MHz16;
+
+/// System clock prescaler, the `CLKPS` field of `CLKPR`
+#[cfg(target_arch = "avr")]
+pub use crate::dev::cpu::CLKPR::CLKPS::Value as Prescaler;
+
+static SHIFT: AtomicU8 = AtomicU8::new(RESET_SHIFT);
+
+/// Current prescaler shift, the CPU runs at `SOURCE_HZ >> shift`
+#[inline]
+pub fn prescaler_shift() -> u8 {
+    SHIFT.load(Ordering::Relaxed)
+}
+
+/// Current CPU frequency in Hz
+#[inline]
+pub fn frequency() -> u32 {
+    SOURCE_HZ >> prescaler_shift()
+}
+
+/// Change the system clock prescaler.
+///
+/// `CLKPR` only accepts the new value within four cycles after the change
+/// enable bit was written, so both writes are done with interrupts masked.
+#[cfg(target_arch = "avr")]
+pub fn set_prescaler(prescaler: Prescaler) {
+    use crate::dev::cpu::{CLKPR, CLOCK};
+
+    crate::interrupt::free(|_| {
+        CLOCK.clkpr.set(CLKPR::CLKPCE::SET.value);
+        CLOCK.clkpr.set(prescaler as u8);
+        SHIFT.store(prescaler as u8, Ordering::Relaxed);
+    });
+}
+
+/// Scale a duration given for the reset frequency (`FREQ_HZ`) to the current
+/// frequency, saturating at `u32::MAX`
+#[inline]
+pub fn scale(value: u32) -> u32 {
+    let shift = prescaler_shift();
+    if shift >= RESET_SHIFT {
+        value >> (shift - RESET_SHIFT)
+    } else {
+        let up = RESET_SHIFT - shift;
+        if value > u32::MAX >> up {
+            u32::MAX
+        } else {
+            value << up
+        }
+    }
+}
+
+/// USART baud rate register value for `baud` at the current frequency
+/// (normal speed mode), rounded to the nearest value
+pub fn ubrr(baud: u32) -> u16 {
+    let div = 16 * baud;
+    let ubrr = (frequency() + div / 2) / div;
+    if ubrr == 0 {
+        0
+    } else {
+        (ubrr - 1) as u16
+    }
+}
+
+#[cfg(all(test, feature = "clock-1mhz"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reset_frequency() {
+        assert_eq!(FREQ_HZ, 1_000_000);
+        assert_eq!(frequency(), FREQ_HZ);
+        assert_eq!(scale(1000), 1000);
+    }
+
+    #[test]
+    fn ubrr_matches_datasheet() {
+        // ATmega48P datasheet, table "UBRRn settings", fosc = 1 MHz, U2Xn = 0
+        assert_eq!(ubrr(2400), 25);
+        assert_eq!(ubrr(4800), 12);
+        assert_eq!(ubrr(9600), 6);
+    }
+}
@@ -0,0 +1,427 @@
+//! Persistent configuration in the EEPROM.
+//!
+//! A [`Store`] keeps a [`Config`] value, such as calibration data, in two
+//! slots of the EEPROM. Each save goes to the slot not holding the newest
+//! copy, so a reset or power loss while saving leaves the previous copy
+//! intact. A slot holds
+//!
+//! | `version` | `generation` | `len` | value | CRC-16, little endian |
+//! | --- | --- | --- | --- | --- |
+//!
+//! where `generation` counts the saves and picks the newer slot, and the CRC
+//! (see `telemetry::crc16`) covers everything before it. Loading falls back
+//! to the other slot and then to `Default::default()` when slots are
+//! corrupted, and hands blocks of an older `VERSION` to [`Config::migrate`].
+//!
+//! ```
+//! use m48_robo_rust::config::{Config, Origin, Store};
+//! use m48_robo_rust::eeprom::{Eeprom, Persist, Ram};
+//! use m48_robo_rust::fixed::Q8_8;
+//!
+//! #[derive(Debug, PartialEq)]
+//! struct Gains {
+//!     kp: Q8_8,
+//!     ki: Q8_8,
+//! }
+//!
+//! impl Default for Gains {
+//!     fn default() -> Gains {
+//!         Gains { kp: Q8_8::ONE, ki: Q8_8::ZERO }
+//!     }
+//! }
+//!
+//! impl Persist for Gains {
+//!     const SIZE: usize = 4;
+//!
+//!     fn save(&self, bytes: &mut [u8]) {
+//!         self.kp.save(&mut bytes[0..2]);
+//!         self.ki.save(&mut bytes[2..4]);
+//!     }
+//!
+//!     fn load(bytes: &[u8]) -> Gains {
+//!         Gains { kp: Q8_8::load(&bytes[0..2]), ki: Q8_8::load(&bytes[2..4]) }
+//!     }
+//! }
+//!
+//! impl Config for Gains {
+//!     const VERSION: u8 = 1;
+//! }
+//!
+//! // Room for up to 8 bytes of configuration from address 0x40
+//! static GAINS: Store<Gains> = Store::new(0x40, 8);
+//!
+//! let mut eeprom = Eeprom::with_memory(Ram::new([0xFF; 256]));
+//! assert_eq!(GAINS.load(&mut eeprom), Ok((Gains::default(), Origin::Defaults)));
+//!
+//! let tuned = Gains { kp: Q8_8::from_ratio(5, 2), ki: Q8_8::from_ratio(1, 10) };
+//! GAINS.save(&mut eeprom, &tuned).unwrap();
+//! assert_eq!(GAINS.load(&mut eeprom), Ok((tuned, Origin::Stored)));
+//! ```
+//!
+//! [`Store`]: struct.Store.html
+//! [`Config`]: trait.Config.html
+//! [`Config::migrate`]: trait.Config.html#method.migrate
+
+use core::marker::PhantomData;
+
+use crate::eeprom::{Eeprom, Error, Memory, Persist, MAX_SIZE};
+use crate::telemetry::crc16;
+
+/// Version, generation and length
+const HEADER: usize = 3;
+
+/// Header and CRC
+const OVERHEAD: usize = HEADER + 2;
+
+/// Value kept in a `Store`
+pub trait Config: Persist + Default {
+    /// Layout version, change it whenever the layout changes
+    const VERSION: u8;
+
+    /// Convert the value stored by layout `version` to the current one,
+    /// `None` to use the defaults instead
+    fn migrate(version: u8, bytes: &[u8]) -> Option<Self> {
+        let _ = (version, bytes);
+        None
+    }
+}
+
+/// Where a loaded value came from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Origin {
+    /// The newest intact slot
+    Stored,
+    /// `Config::migrate` from the given version, save the value to keep it
+    Migrated(u8),
+    /// `Default::default()`, there was no intact slot or migration failed
+    Defaults,
+}
+
+/// Intact slot
+#[derive(Clone, Copy, Debug)]
+struct Slot {
+    index: usize,
+    version: u8,
+    generation: u8,
+    len: usize,
+}
+
+/// Two EEPROM slots holding a `T`
+///
+/// The store takes `2 * (capacity + 5)` bytes from `start`. `capacity` is
+/// the largest value size the slots can hold, up to `eeprom::MAX_SIZE`;
+/// leave room for the layout to grow, as the slots can't be moved without
+/// losing the stored values.
+pub struct Store<T> {
+    start: usize,
+    capacity: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Config> Store<T> {
+    pub const fn new(start: usize, capacity: usize) -> Store<T> {
+        Store {
+            start,
+            capacity,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Bytes taken in the EEPROM
+    pub fn size(&self) -> usize {
+        2 * (self.capacity + OVERHEAD)
+    }
+
+    /// Load the newest intact value, migrating or falling back to the
+    /// defaults if needed
+    pub fn load<M: Memory>(&self, eeprom: &mut Eeprom<M>) -> Result<(T, Origin), Error> {
+        self.check(eeprom)?;
+        let slot = match self.newest(eeprom)? {
+            Some(slot) => slot,
+            None => return Ok((T::default(), Origin::Defaults)),
+        };
+        let mut buffer = [0; MAX_SIZE];
+        let bytes = &mut buffer[..slot.len];
+        eeprom.read(self.address(slot.index) + HEADER, bytes)?;
+        if slot.version == T::VERSION && slot.len == T::SIZE {
+            return Ok((T::load(bytes), Origin::Stored));
+        }
+        Ok(match T::migrate(slot.version, bytes) {
+            Some(value) => (value, Origin::Migrated(slot.version)),
+            None => (T::default(), Origin::Defaults),
+        })
+    }
+
+    /// Save `value` over the older slot
+    pub fn save<M: Memory>(&self, eeprom: &mut Eeprom<M>, value: &T) -> Result<(), Error> {
+        self.check(eeprom)?;
+        if T::SIZE > self.capacity {
+            return Err(Error::TooLarge);
+        }
+        let (index, generation) = match self.newest(eeprom)? {
+            Some(slot) => (1 - slot.index, slot.generation.wrapping_add(1)),
+            None => (0, 0),
+        };
+
+        let mut buffer = [0; MAX_SIZE + OVERHEAD];
+        let end = HEADER + T::SIZE;
+        buffer[0] = T::VERSION;
+        buffer[1] = generation;
+        buffer[2] = T::SIZE as u8;
+        value.save(&mut buffer[HEADER..end]);
+        let crc = crc16(&buffer[..end]);
+        buffer[end..end + 2].copy_from_slice(&crc.to_le_bytes());
+        eeprom.write(self.address(index), &buffer[..end + 2])
+    }
+
+    /// Invalidate both slots, `load` returns the defaults afterwards
+    pub fn erase<M: Memory>(&self, eeprom: &mut Eeprom<M>) -> Result<(), Error> {
+        self.check(eeprom)?;
+        for index in 0..2 {
+            // An erased length is larger than any capacity
+            eeprom.write_byte(self.address(index) + 2, 0xFF)?;
+        }
+        Ok(())
+    }
+
+    fn check<M: Memory>(&self, eeprom: &Eeprom<M>) -> Result<(), Error> {
+        if self.capacity > MAX_SIZE {
+            return Err(Error::TooLarge);
+        }
+        match self.start.checked_add(self.size()) {
+            Some(end) if end <= eeprom.len() => Ok(()),
+            _ => Err(Error::OutOfRange),
+        }
+    }
+
+    fn address(&self, index: usize) -> usize {
+        self.start + index * (self.capacity + OVERHEAD)
+    }
+
+    /// Intact slot with the newer generation
+    fn newest<M: Memory>(&self, eeprom: &mut Eeprom<M>) -> Result<Option<Slot>, Error> {
+        let a = self.slot(eeprom, 0)?;
+        let b = self.slot(eeprom, 1)?;
+        Ok(match (a, b) {
+            // Generations wrap, the newer one is at most 127 ahead
+            (Some(a), Some(b)) if (b.generation.wrapping_sub(a.generation) as i8) > 0 => Some(b),
+            (Some(a), _) => Some(a),
+            (None, b) => b,
+        })
+    }
+
+    /// Header of slot `index` if its CRC matches
+    fn slot<M: Memory>(&self, eeprom: &mut Eeprom<M>, index: usize) -> Result<Option<Slot>, Error> {
+        let address = self.address(index);
+        let mut header = [0; HEADER];
+        eeprom.read(address, &mut header)?;
+        let len = header[2] as usize;
+        if len > self.capacity {
+            return Ok(None);
+        }
+        let mut buffer = [0; MAX_SIZE + OVERHEAD];
+        let block = &mut buffer[..len + OVERHEAD];
+        eeprom.read(address, block)?;
+        let (data, stored) = block.split_at(HEADER + len);
+        if crc16(data).to_le_bytes() != stored {
+            return Ok(None);
+        }
+        Ok(Some(Slot {
+            index,
+            version: header[0],
+            generation: header[1],
+            len,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eeprom::Ram;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Trim {
+        offset: i16,
+        gain: u16,
+    }
+
+    impl Default for Trim {
+        fn default() -> Trim {
+            Trim {
+                offset: 0,
+                gain: 1000,
+            }
+        }
+    }
+
+    impl Persist for Trim {
+        const SIZE: usize = 4;
+
+        fn save(&self, bytes: &mut [u8]) {
+            self.offset.save(&mut bytes[0..2]);
+            self.gain.save(&mut bytes[2..4]);
+        }
+
+        fn load(bytes: &[u8]) -> Trim {
+            Trim {
+                offset: i16::load(&bytes[0..2]),
+                gain: u16::load(&bytes[2..4]),
+            }
+        }
+    }
+
+    impl Config for Trim {
+        const VERSION: u8 = 2;
+
+        /// Version 1 had only the offset, as an `i8`
+        fn migrate(version: u8, bytes: &[u8]) -> Option<Trim> {
+            match (version, bytes) {
+                (1, &[offset]) => Some(Trim {
+                    offset: offset as i8 as i16,
+                    ..Trim::default()
+                }),
+                _ => None,
+            }
+        }
+    }
+
+    #[derive(Debug, Default, PartialEq)]
+    struct OldTrim(i8);
+
+    impl Persist for OldTrim {
+        const SIZE: usize = 1;
+
+        fn save(&self, bytes: &mut [u8]) {
+            self.0.save(bytes);
+        }
+
+        fn load(bytes: &[u8]) -> OldTrim {
+            OldTrim(i8::load(bytes))
+        }
+    }
+
+    impl Config for OldTrim {
+        const VERSION: u8 = 1;
+    }
+
+    const TRIM: Store<Trim> = Store::new(16, 8);
+    const OLD_TRIM: Store<OldTrim> = Store::new(16, 8);
+
+    fn eeprom() -> Eeprom<Ram<[u8; 64]>> {
+        Eeprom::with_memory(Ram::new([0xFF; 64]))
+    }
+
+    fn trim(offset: i16) -> Trim {
+        Trim { offset, gain: 900 }
+    }
+
+    #[test]
+    fn defaults_then_stored() {
+        let mut eeprom = eeprom();
+        assert_eq!(TRIM.size(), 26);
+        assert_eq!(
+            TRIM.load(&mut eeprom),
+            Ok((Trim::default(), Origin::Defaults))
+        );
+
+        for offset in 0..300 {
+            TRIM.save(&mut eeprom, &trim(offset)).unwrap();
+            assert_eq!(TRIM.load(&mut eeprom), Ok((trim(offset), Origin::Stored)));
+        }
+        // Nothing outside the store
+        let cells = eeprom.into_memory();
+        assert!(cells.cells()[..16].iter().all(|&byte| byte == 0xFF));
+        assert!(cells.cells()[42..].iter().all(|&byte| byte == 0xFF));
+    }
+
+    #[test]
+    fn saves_alternate_slots() {
+        let mut eeprom = eeprom();
+        TRIM.save(&mut eeprom, &trim(1)).unwrap();
+        TRIM.save(&mut eeprom, &trim(2)).unwrap();
+        TRIM.save(&mut eeprom, &trim(3)).unwrap();
+        // Generation 2 in slot A, 1 in slot B
+        assert_eq!(eeprom.read_byte(16 + 1), Ok(2));
+        assert_eq!(eeprom.read_byte(29 + 1), Ok(1));
+        assert_eq!(eeprom.get::<i16>(16 + HEADER), Ok(3));
+        assert_eq!(eeprom.get::<i16>(29 + HEADER), Ok(2));
+    }
+
+    #[test]
+    fn corruption_falls_back() {
+        let mut eeprom = eeprom();
+        TRIM.save(&mut eeprom, &trim(1)).unwrap();
+        TRIM.save(&mut eeprom, &trim(2)).unwrap();
+
+        // A save cut short leaves the newest slot with a bad CRC
+        eeprom.write_byte(29 + HEADER, 0x55).unwrap();
+        assert_eq!(TRIM.load(&mut eeprom), Ok((trim(1), Origin::Stored)));
+        // The next save replaces the broken slot and keeps the good one
+        TRIM.save(&mut eeprom, &trim(3)).unwrap();
+        assert_eq!(eeprom.read_byte(29 + 1), Ok(1));
+        assert_eq!(TRIM.load(&mut eeprom), Ok((trim(3), Origin::Stored)));
+
+        // A bit flip in the other slot
+        let mut cells = eeprom.into_memory();
+        cells.cells_mut()[16 + 4] ^= 0x01;
+        cells.cells_mut()[29 + 2] = 200;
+        let mut eeprom = Eeprom::with_memory(cells);
+        assert_eq!(
+            TRIM.load(&mut eeprom),
+            Ok((Trim::default(), Origin::Defaults))
+        );
+    }
+
+    #[test]
+    fn generations_wrap() {
+        let mut eeprom = eeprom();
+        for offset in 0..600 {
+            TRIM.save(&mut eeprom, &trim(offset)).unwrap();
+        }
+        assert_eq!(TRIM.load(&mut eeprom), Ok((trim(599), Origin::Stored)));
+    }
+
+    #[test]
+    fn migration() {
+        let mut eeprom = eeprom();
+        OLD_TRIM.save(&mut eeprom, &OldTrim(-5)).unwrap();
+        let migrated = Trim {
+            offset: -5,
+            gain: 1000,
+        };
+        assert_eq!(TRIM.load(&mut eeprom), Ok((migrated, Origin::Migrated(1))));
+
+        // Saving makes it permanent, in the other slot
+        TRIM.save(&mut eeprom, &migrated).unwrap();
+        assert_eq!(TRIM.load(&mut eeprom), Ok((migrated, Origin::Stored)));
+
+        // Unknown versions use the defaults
+        let mut eeprom = self::eeprom();
+        eeprom.write(16, &[7, 0, 0]).unwrap();
+        eeprom.put(16 + HEADER, &crc16(&[7, 0, 0])).unwrap();
+        assert_eq!(
+            TRIM.load(&mut eeprom),
+            Ok((Trim::default(), Origin::Defaults))
+        );
+    }
+
+    #[test]
+    fn erase_and_errors() {
+        let mut eeprom = eeprom();
+        TRIM.save(&mut eeprom, &trim(1)).unwrap();
+        TRIM.save(&mut eeprom, &trim(2)).unwrap();
+        TRIM.erase(&mut eeprom).unwrap();
+        assert_eq!(
+            TRIM.load(&mut eeprom),
+            Ok((Trim::default(), Origin::Defaults))
+        );
+
+        let small: Store<Trim> = Store::new(0, 3);
+        assert_eq!(small.save(&mut eeprom, &trim(1)), Err(Error::TooLarge));
+        let huge: Store<Trim> = Store::new(0, MAX_SIZE + 1);
+        assert_eq!(huge.load(&mut eeprom), Err(Error::TooLarge));
+        let outside: Store<Trim> = Store::new(40, 8);
+        assert_eq!(outside.load(&mut eeprom), Err(Error::OutOfRange));
+    }
+}
@@ -0,0 +1,123 @@
+//! Closed-loop control.
+//!
+//! Controllers are generic over [`Scalar`], which is implemented for `f32`
+//! and the [`fixed`](../fixed/index.html) point types. `Q16_16` is the usual
+//! choice on the MCU, `f32` costs a couple of kilobytes of soft-float.
+//!
+//! [`Scalar`]: trait.Scalar.html
+
+use core::ops::{Add, Div, Mul, Neg, Sub};
+
+use crate::fixed::{Q16_16, Q8_8};
+
+pub mod pid;
+
+/// Number type controllers compute in
+///
+/// Fixed-point implementations saturate instead of overflowing.
+pub trait Scalar:
+    Copy
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+{
+    const ZERO: Self;
+    const ONE: Self;
+    const MIN: Self;
+    const MAX: Self;
+
+    fn from_int(value: i16) -> Self;
+
+    /// `num / den`, for gains and other constants
+    fn from_ratio(num: i16, den: i16) -> Self;
+
+    /// `counts / 2^bits` of `full_scale`, for ADC readings
+    fn from_adc(counts: u16, bits: u8, full_scale: Self) -> Self;
+
+    /// Nearest integer, halves away from zero for `f32` and up for
+    /// fixed-point, saturating
+    fn round(self) -> i32;
+}
+
+impl Scalar for f32 {
+    const ZERO: f32 = 0.0;
+    const ONE: f32 = 1.0;
+    const MIN: f32 = f32::MIN;
+    const MAX: f32 = f32::MAX;
+
+    fn from_int(value: i16) -> f32 {
+        value as f32
+    }
+
+    fn from_ratio(num: i16, den: i16) -> f32 {
+        num as f32 / den as f32
+    }
+
+    fn from_adc(counts: u16, bits: u8, full_scale: f32) -> f32 {
+        counts as f32 * full_scale / (1u32 << bits) as f32
+    }
+
+    fn round(self) -> i32 {
+        // Float to int casts saturate
+        if self < 0.0 {
+            (self - 0.5) as i32
+        } else {
+            (self + 0.5) as i32
+        }
+    }
+}
+
+macro_rules! fixed_scalar {
+    ($($name:ident),*) => {
+        $(
+            impl Scalar for $name {
+                const ZERO: $name = $name::ZERO;
+                const ONE: $name = $name::ONE;
+                const MIN: $name = $name::MIN;
+                const MAX: $name = $name::MAX;
+
+                fn from_int(value: i16) -> $name {
+                    $name::from_int(value.into())
+                }
+
+                fn from_ratio(num: i16, den: i16) -> $name {
+                    $name::from_ratio(num.into(), den.into())
+                }
+
+                fn from_adc(counts: u16, bits: u8, full_scale: $name) -> $name {
+                    $name::from_adc(counts, bits, full_scale)
+                }
+
+                fn round(self) -> i32 {
+                    $name::round(self).into()
+                }
+            }
+        )*
+    };
+}
+
+fixed_scalar!(Q8_8, Q16_16);
+
+/// Restrict `value` to `min..=max`
+pub fn clamp<T: Scalar>(value: T, min: T, max: T) -> T {
+    if value < min {
+        min
+    } else if value > max {
+        max
+    } else {
+        value
+    }
+}
+
+/// PWM duty cycle for `pwm::Timer1Pwm` and the other 8-bit PWM pins, from a
+/// controller output in `0..=255`
+pub fn duty<T: Scalar>(output: T) -> u8 {
+    match output.round() {
+        value if value < 0 => 0,
+        value if value > 255 => 255,
+        value => value as u8,
+    }
+}
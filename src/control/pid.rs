@@ -0,0 +1,417 @@
+//! PID controller.
+//!
+//! A discrete parallel PID with the usual practical extensions:
+//!
+//! * output clamping with integrator anti-windup, see [`AntiWindup`]
+//! * derivative on measurement, so setpoint steps don't kick the output, with
+//!   a first order low-pass filter
+//! * setpoint weighting of the proportional term
+//! * bumpless transfer from manual to automatic mode
+//!
+//! Gains are per sample: for a loop running every `Ts` seconds pass
+//! `ki = Ki * Ts` and `kd = Kd / Ts`.
+//!
+//! ```
+//! use m48_robo_rust::control::{self, pid::{AntiWindup, Pid}, Scalar};
+//! use m48_robo_rust::fixed::Q16_16;
+//!
+//! let mut pid = Pid::new(
+//!     Q16_16::from_int(2),
+//!     Q16_16::from_ratio(1, 10),
+//!     Q16_16::ZERO,
+//! )
+//! .limits(Q16_16::ZERO, Q16_16::from_int(255))
+//! .anti_windup(AntiWindup::Clamp);
+//!
+//! // Setpoint and measurement in volts, from 10-bit ADC readings
+//! let vref = Q16_16::from_int(5);
+//! let setpoint = Q16_16::from_adc(600, 10, vref);
+//! let measurement = Q16_16::from_adc(400, 10, vref);
+//!
+//! let duty = control::duty(pid.update(setpoint, measurement));
+//! assert_eq!(duty, 2);
+//! ```
+//!
+//! [`AntiWindup`]: enum.AntiWindup.html
+
+use super::{clamp, Scalar};
+
+/// Integrator handling while the output is clamped
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AntiWindup<T> {
+    /// Keep integrating, the integrator can grow without bounds
+    None,
+    /// Stop integrating while the output is clamped in the direction of the
+    /// error, and keep the integrator within the output limits
+    Clamp,
+    /// Feed the clamped part of the output back into the integrator with the
+    /// given per sample tracking gain, usually between `ki` and `1`
+    BackCalculation(T),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Output is set with `set_manual`, the controller only tracks the
+    /// measurement
+    Manual,
+    Automatic,
+}
+
+#[derive(Clone, Debug)]
+pub struct Pid<T> {
+    kp: T,
+    ki: T,
+    kd: T,
+    setpoint_weight: T,
+    filter: T,
+    min: T,
+    max: T,
+    anti_windup: AntiWindup<T>,
+    mode: Mode,
+    // Set when switching to automatic until the next update
+    transfer: bool,
+    integral: T,
+    derivative: T,
+    last_measurement: Option<T>,
+    output: T,
+}
+
+impl<T: Scalar> Pid<T> {
+    /// Controller in automatic mode with unlimited output
+    pub fn new(kp: T, ki: T, kd: T) -> Self {
+        Pid {
+            kp,
+            ki,
+            kd,
+            setpoint_weight: T::ONE,
+            filter: T::ONE,
+            min: T::MIN,
+            max: T::MAX,
+            anti_windup: AntiWindup::None,
+            mode: Mode::Automatic,
+            transfer: false,
+            integral: T::ZERO,
+            derivative: T::ZERO,
+            last_measurement: None,
+            output: T::ZERO,
+        }
+    }
+
+    /// Clamp the output to `min..=max`
+    pub fn limits(mut self, min: T, max: T) -> Self {
+        self.min = min;
+        self.max = max;
+        self
+    }
+
+    pub fn anti_windup(mut self, anti_windup: AntiWindup<T>) -> Self {
+        self.anti_windup = anti_windup;
+        self
+    }
+
+    /// Smooth the derivative with `d += alpha * (d_raw - d)`, `alpha` in
+    /// `0..=1` where `1` (the default) disables the filter
+    pub fn derivative_filter(mut self, alpha: T) -> Self {
+        self.filter = alpha;
+        self
+    }
+
+    /// Proportional term acts on `weight * setpoint - measurement`. Values
+    /// below the default of `1` reduce overshoot on setpoint steps without
+    /// changing the disturbance response.
+    pub fn setpoint_weight(mut self, weight: T) -> Self {
+        self.setpoint_weight = weight;
+        self
+    }
+
+    /// Change the gains, the integrator is kept
+    pub fn set_gains(&mut self, kp: T, ki: T, kd: T) {
+        self.kp = kp;
+        self.ki = ki;
+        self.kd = kd;
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Switch to manual mode with a fixed (clamped) output
+    pub fn set_manual(&mut self, output: T) {
+        self.mode = Mode::Manual;
+        self.output = clamp(output, self.min, self.max);
+    }
+
+    /// Switch to automatic mode. The integrator is initialized on the next
+    /// `update` so that the output continues from the manual value.
+    pub fn set_automatic(&mut self) {
+        if self.mode == Mode::Manual {
+            self.mode = Mode::Automatic;
+            self.transfer = true;
+        }
+    }
+
+    /// Last output
+    pub fn output(&self) -> T {
+        self.output
+    }
+
+    pub fn integral(&self) -> T {
+        self.integral
+    }
+
+    /// Clear the integrator, derivative and measurement history
+    pub fn reset(&mut self) {
+        self.integral = T::ZERO;
+        self.derivative = T::ZERO;
+        self.last_measurement = None;
+        self.transfer = false;
+        self.output = T::ZERO;
+    }
+
+    /// Run one sample and return the new output
+    pub fn update(&mut self, setpoint: T, measurement: T) -> T {
+        let change = match self.last_measurement {
+            Some(last) => measurement - last,
+            None => T::ZERO,
+        };
+        self.last_measurement = Some(measurement);
+
+        if self.mode == Mode::Manual {
+            self.derivative = T::ZERO;
+            return self.output;
+        }
+
+        let error = setpoint - measurement;
+        let proportional = self.kp * (self.setpoint_weight * setpoint - measurement);
+        self.derivative = self.derivative + self.filter * (-(self.kd * change) - self.derivative);
+
+        let step = self.ki * error;
+        let integral = if self.transfer {
+            self.transfer = false;
+            self.output - proportional - self.derivative
+        } else {
+            self.integral + step
+        };
+
+        let unclamped = proportional + integral + self.derivative;
+        let output = clamp(unclamped, self.min, self.max);
+
+        self.integral = match self.anti_windup {
+            AntiWindup::None => integral,
+            AntiWindup::Clamp => {
+                let winding = (unclamped > self.max && step > T::ZERO)
+                    || (unclamped < self.min && step < T::ZERO);
+                if winding {
+                    clamp(self.integral, self.min, self.max)
+                } else {
+                    clamp(integral, self.min, self.max)
+                }
+            }
+            AntiWindup::BackCalculation(gain) => integral + gain * (output - unclamped),
+        };
+        self.output = output;
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixed::Q16_16;
+
+    /// First order plant `y += (gain * u - y) / tau` with an optional load
+    struct Plant<T> {
+        y: T,
+        gain: T,
+        tau: T,
+        load: T,
+    }
+
+    impl<T: Scalar> Plant<T> {
+        fn new() -> Self {
+            Plant {
+                y: T::ZERO,
+                gain: T::from_ratio(1, 2),
+                tau: T::from_int(10),
+                load: T::ZERO,
+            }
+        }
+
+        fn step(&mut self, u: T) -> T {
+            self.y = self.y + (self.gain * u - self.load - self.y) / self.tau;
+            self.y
+        }
+    }
+
+    /// Runs the loop for `samples` and returns the measurements
+    fn simulate<T: Scalar>(
+        pid: &mut Pid<T>,
+        plant: &mut Plant<T>,
+        setpoint: T,
+        samples: usize,
+    ) -> Vec<T> {
+        (0..samples)
+            .map(|_| {
+                let u = pid.update(setpoint, plant.y);
+                plant.step(u)
+            })
+            .collect()
+    }
+
+    fn f(value: Q16_16) -> f32 {
+        value.to_f32()
+    }
+
+    fn max(values: &[f32]) -> f32 {
+        values.iter().cloned().fold(f32::MIN, f32::max)
+    }
+
+    fn pi<T: Scalar>() -> Pid<T> {
+        Pid::new(T::from_int(4), T::from_ratio(1, 5), T::ZERO)
+    }
+
+    #[test]
+    fn converges_without_steady_state_error() {
+        let mut pid = pi::<f32>();
+        let mut plant = Plant::new();
+        plant.load = 3.0;
+        let y = simulate(&mut pid, &mut plant, 10.0, 300);
+        assert!((y[299] - 10.0).abs() < 1e-3, "{}", y[299]);
+
+        let mut pid = pi::<Q16_16>();
+        let mut plant = Plant::new();
+        plant.load = Q16_16::from_int(3);
+        let y = simulate(&mut pid, &mut plant, Q16_16::from_int(10), 300);
+        assert!((f(y[299]) - 10.0).abs() < 1e-3, "{}", f(y[299]));
+    }
+
+    #[test]
+    fn fixed_point_tracks_float() {
+        let mut pid = pi::<f32>().derivative_filter(0.5);
+        pid.set_gains(4.0, 0.2, 2.0);
+        let mut plant = Plant::new();
+        let expected = simulate(&mut pid, &mut plant, 10.0, 100);
+
+        let mut pid = pi::<Q16_16>().derivative_filter(Q16_16::from_ratio(1, 2));
+        pid.set_gains(
+            Q16_16::from_int(4),
+            Q16_16::from_ratio(1, 5),
+            Q16_16::from_int(2),
+        );
+        let mut plant = Plant::new();
+        let actual = simulate(&mut pid, &mut plant, Q16_16::from_int(10), 100);
+
+        for (a, e) in actual.iter().zip(&expected) {
+            assert!((f(*a) - e).abs() < 0.01, "{} != {}", f(*a), e);
+        }
+    }
+
+    #[test]
+    fn output_is_clamped() {
+        let mut pid = pi::<f32>().limits(-5.0, 5.0);
+        assert_eq!(pid.update(100.0, 0.0), 5.0);
+        assert_eq!(pid.update(-100.0, 0.0), -5.0);
+        assert_eq!(pid.output(), -5.0);
+    }
+
+    /// Saturates the output with an unreachable setpoint, then drops the
+    /// setpoint and returns how long the plant takes to settle
+    fn recovery(anti_windup: AntiWindup<f32>) -> usize {
+        let mut pid = pi::<f32>().limits(0.0, 30.0).anti_windup(anti_windup);
+        let mut plant = Plant::new();
+        simulate(&mut pid, &mut plant, 20.0, 200);
+        let y = simulate(&mut pid, &mut plant, 10.0, 400);
+        assert!((y[399] - 10.0).abs() < 0.01);
+        y.iter().rposition(|y| (y - 10.0).abs() > 0.1).unwrap()
+    }
+
+    #[test]
+    fn anti_windup_speeds_up_recovery() {
+        let windup = recovery(AntiWindup::None);
+        assert!(windup > 250, "{}", windup);
+        let clamp = recovery(AntiWindup::Clamp);
+        assert!(clamp < 100, "{}", clamp);
+        let back = recovery(AntiWindup::BackCalculation(0.5));
+        assert!(back < 100, "{}", back);
+    }
+
+    #[test]
+    fn clamp_holds_integrator_within_limits() {
+        let mut pid = pi::<Q16_16>()
+            .limits(Q16_16::ZERO, Q16_16::from_int(30))
+            .anti_windup(AntiWindup::Clamp);
+        for _ in 0..1000 {
+            pid.update(Q16_16::from_int(100), Q16_16::ZERO);
+        }
+        assert!(pid.integral() <= Q16_16::from_int(30));
+        // Unwinds as soon as the error changes sign
+        assert!(pid.update(Q16_16::ZERO, Q16_16::from_int(1)) < Q16_16::from_int(30));
+    }
+
+    #[test]
+    fn no_derivative_kick_on_setpoint_step() {
+        let mut pid = Pid::new(1.0, 0.0, 10.0);
+        assert_eq!(pid.update(0.0, 0.0), 0.0);
+        // Only proportional on the setpoint step
+        assert_eq!(pid.update(5.0, 0.0), 5.0);
+        // Measurement rising by 1 is opposed by kd
+        assert_eq!(pid.update(5.0, 1.0), 4.0 - 10.0);
+    }
+
+    #[test]
+    fn derivative_filter() {
+        let mut pid = Pid::new(0.0, 0.0, 8.0).derivative_filter(0.25);
+        pid.update(0.0, 0.0);
+        assert_eq!(pid.update(0.0, 1.0), -2.0);
+        assert_eq!(pid.update(0.0, 1.0), -1.5);
+        assert_eq!(pid.update(0.0, 1.0), -1.125);
+    }
+
+    #[test]
+    fn setpoint_weighting() {
+        let mut pid = Pid::new(2.0, 0.0, 0.0).setpoint_weight(0.0);
+        assert_eq!(pid.update(10.0, 0.0), 0.0);
+        assert_eq!(pid.update(10.0, 3.0), -6.0);
+
+        // Steady state is unaffected thanks to the integrator
+        let mut pid = pi::<f32>().setpoint_weight(0.5);
+        let y = simulate(&mut pid, &mut Plant::new(), 10.0, 400);
+        assert!((y[399] - 10.0).abs() < 1e-3);
+        let mut pid = pi::<f32>();
+        let overshoot = max(&simulate(&mut pid, &mut Plant::new(), 10.0, 400));
+        assert!(max(&y) < overshoot);
+    }
+
+    #[test]
+    fn bumpless_transfer() {
+        let mut pid = pi::<Q16_16>().limits(Q16_16::ZERO, Q16_16::from_int(100));
+        let mut plant = Plant::new();
+        let setpoint = Q16_16::from_int(20);
+
+        pid.set_manual(Q16_16::from_int(30));
+        assert_eq!(pid.mode(), Mode::Manual);
+        for _ in 0..50 {
+            let u = pid.update(setpoint, plant.y);
+            assert_eq!(u, Q16_16::from_int(30));
+            plant.step(u);
+        }
+
+        pid.set_automatic();
+        assert_eq!(pid.mode(), Mode::Automatic);
+        let u = pid.update(setpoint, plant.y);
+        assert_eq!(u, Q16_16::from_int(30));
+
+        // And it goes on to control
+        let y = simulate(&mut pid, &mut plant, setpoint, 400);
+        assert!((f(y[399]) - 20.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn manual_output_is_clamped() {
+        let mut pid = pi::<f32>().limits(0.0, 10.0);
+        pid.set_manual(50.0);
+        assert_eq!(pid.update(0.0, 0.0), 10.0);
+        pid.reset();
+        assert_eq!(pid.output(), 0.0);
+    }
+}
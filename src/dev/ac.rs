@@ -0,0 +1,32 @@
+//! Analog Comparator.
+//!
+//! The comparator multiplexer enable bit `ACME` is part of `ADCSRB`, see
+//! `dev::adc`.
+
+use crate::mmio::{registers::ReadWrite, static_ref::StaticRef};
+
+register_bitfields![u8,
+    ACSR [
+        ACD OFFSET(7) NUMBITS(1) [],
+        ACBG OFFSET(6) NUMBITS(1) [],
+        ACO OFFSET(5) NUMBITS(1) [],
+        ACI OFFSET(4) NUMBITS(1) [],
+        ACIE OFFSET(3) NUMBITS(1) [],
+        ACIC OFFSET(2) NUMBITS(1) [],
+        ACIS OFFSET(0) NUMBITS(2) [
+            Toggle = 0,
+            Falling = 2,
+            Rising = 3
+        ]
+    ],
+    DIDR1 [
+        AIN1D OFFSET(1) NUMBITS(1) [],
+        AIN0D OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+pub const ACSR: StaticRef<ReadWrite<u8, ACSR::Register>> =
+    unsafe { StaticRef::new(0x50 as *const ReadWrite<u8, ACSR::Register>) };
+
+pub const DIDR1: StaticRef<ReadWrite<u8, DIDR1::Register>> =
+    unsafe { StaticRef::new(0x7F as *const ReadWrite<u8, DIDR1::Register>) };
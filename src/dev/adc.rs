@@ -0,0 +1,82 @@
+//! Analog to Digital Converter.
+
+use crate::mmio::{
+    registers::{ReadOnly16, ReadWrite},
+    static_ref::StaticRef,
+};
+
+register_structs! {
+    pub AdcRegisters {
+        (0x00 => pub adc: ReadOnly16),
+        (0x02 => pub adcsra: ReadWrite<u8, ADCSRA::Register>),
+        (0x03 => pub adcsrb: ReadWrite<u8, ADCSRB::Register>),
+        (0x04 => pub admux: ReadWrite<u8, ADMUX::Register>),
+        (0x05 => _reserved0),
+        (0x06 => pub didr0: ReadWrite<u8, DIDR0::Register>),
+        (0x07 => @END),
+    }
+}
+
+register_bitfields![u8,
+    ADCSRA [
+        ADEN OFFSET(7) NUMBITS(1) [],
+        ADSC OFFSET(6) NUMBITS(1) [],
+        ADATE OFFSET(5) NUMBITS(1) [],
+        ADIF OFFSET(4) NUMBITS(1) [],
+        ADIE OFFSET(3) NUMBITS(1) [],
+        ADPS OFFSET(0) NUMBITS(3) [
+            Prescale2 = 1,
+            Prescale4 = 2,
+            Prescale8 = 3,
+            Prescale16 = 4,
+            Prescale32 = 5,
+            Prescale64 = 6,
+            Prescale128 = 7
+        ]
+    ],
+    ADCSRB [
+        /// Analog comparator multiplexer enable, see `dev::ac`
+        ACME OFFSET(6) NUMBITS(1) [],
+        ADTS OFFSET(0) NUMBITS(3) [
+            FreeRunning = 0,
+            AnalogComparator = 1,
+            ExtInt0 = 2,
+            Tc0CompareA = 3,
+            Tc0Overflow = 4,
+            Tc1CompareB = 5,
+            Tc1Overflow = 6,
+            Tc1Capture = 7
+        ]
+    ],
+    ADMUX [
+        REFS OFFSET(6) NUMBITS(2) [
+            Aref = 0,
+            Avcc = 1,
+            Internal = 3
+        ],
+        ADLAR OFFSET(5) NUMBITS(1) [],
+        MUX OFFSET(0) NUMBITS(4) [
+            Adc0 = 0,
+            Adc1 = 1,
+            Adc2 = 2,
+            Adc3 = 3,
+            Adc4 = 4,
+            Adc5 = 5,
+            Adc6 = 6,
+            Adc7 = 7,
+            Temperature = 8,
+            Vbg = 14,
+            Gnd = 15
+        ]
+    ],
+    DIDR0 [
+        ADC5D OFFSET(5) NUMBITS(1) [],
+        ADC4D OFFSET(4) NUMBITS(1) [],
+        ADC3D OFFSET(3) NUMBITS(1) [],
+        ADC2D OFFSET(2) NUMBITS(1) [],
+        ADC1D OFFSET(1) NUMBITS(1) [],
+        ADC0D OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+pub const ADC: StaticRef<AdcRegisters> = unsafe { StaticRef::new(0x78 as *const AdcRegisters) };
@@ -0,0 +1,101 @@
+//! CPU core, sleep, reset and system clock registers.
+
+use crate::mmio::{
+    registers::{ReadWrite, ReadWrite16},
+    static_ref::StaticRef,
+};
+
+register_structs! {
+    pub CpuRegisters {
+        (0x00 => pub smcr: ReadWrite<u8, SMCR::Register>),
+        (0x01 => pub mcusr: ReadWrite<u8, MCUSR::Register>),
+        (0x02 => pub mcucr: ReadWrite<u8, MCUCR::Register>),
+        (0x03 => _reserved0),
+        (0x04 => pub spmcsr: ReadWrite<u8, SPMCSR::Register>),
+        (0x05 => _reserved1),
+        (0x0A => pub sp: ReadWrite16),
+        (0x0C => pub sreg: ReadWrite<u8, SREG::Register>),
+        (0x0D => @END),
+    },
+    pub ClockRegisters {
+        (0x00 => pub clkpr: ReadWrite<u8, CLKPR::Register>),
+        (0x01 => _reserved0),
+        (0x03 => pub prr: ReadWrite<u8, PRR::Register>),
+        (0x04 => _reserved1),
+        (0x05 => pub osccal: ReadWrite<u8>),
+        (0x06 => @END),
+    }
+}
+
+register_bitfields![u8,
+    SMCR [
+        SM OFFSET(1) NUMBITS(3) [
+            Idle = 0,
+            AdcNoiseReduction = 1,
+            PowerDown = 2,
+            PowerSave = 3,
+            Standby = 6,
+            ExtendedStandby = 7
+        ],
+        SE OFFSET(0) NUMBITS(1) []
+    ],
+    MCUSR [
+        WDRF OFFSET(3) NUMBITS(1) [],
+        BORF OFFSET(2) NUMBITS(1) [],
+        EXTRF OFFSET(1) NUMBITS(1) [],
+        PORF OFFSET(0) NUMBITS(1) []
+    ],
+    MCUCR [
+        BODS OFFSET(6) NUMBITS(1) [],
+        BODSE OFFSET(5) NUMBITS(1) [],
+        PUD OFFSET(4) NUMBITS(1) []
+    ],
+    SPMCSR [
+        SPMIE OFFSET(7) NUMBITS(1) [],
+        RWWSB OFFSET(6) NUMBITS(1) [],
+        SIGRD OFFSET(5) NUMBITS(1) [],
+        RWWSRE OFFSET(4) NUMBITS(1) [],
+        BLBSET OFFSET(3) NUMBITS(1) [],
+        PGWRT OFFSET(2) NUMBITS(1) [],
+        PGERS OFFSET(1) NUMBITS(1) [],
+        SELFPRGEN OFFSET(0) NUMBITS(1) []
+    ],
+    SREG [
+        I OFFSET(7) NUMBITS(1) [],
+        T OFFSET(6) NUMBITS(1) [],
+        H OFFSET(5) NUMBITS(1) [],
+        S OFFSET(4) NUMBITS(1) [],
+        V OFFSET(3) NUMBITS(1) [],
+        N OFFSET(2) NUMBITS(1) [],
+        Z OFFSET(1) NUMBITS(1) [],
+        C OFFSET(0) NUMBITS(1) []
+    ],
+    CLKPR [
+        CLKPCE OFFSET(7) NUMBITS(1) [],
+        CLKPS OFFSET(0) NUMBITS(4) [
+            Div1 = 0,
+            Div2 = 1,
+            Div4 = 2,
+            Div8 = 3,
+            Div16 = 4,
+            Div32 = 5,
+            Div64 = 6,
+            Div128 = 7,
+            Div256 = 8
+        ]
+    ],
+    PRR [
+        PRTWI OFFSET(7) NUMBITS(1) [],
+        PRTIM2 OFFSET(6) NUMBITS(1) [],
+        PRTIM0 OFFSET(5) NUMBITS(1) [],
+        PRTIM1 OFFSET(3) NUMBITS(1) [],
+        PRSPI OFFSET(2) NUMBITS(1) [],
+        PRUSART0 OFFSET(1) NUMBITS(1) [],
+        PRADC OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+pub const CPU: StaticRef<CpuRegisters> = unsafe { StaticRef::new(0x53 as *const CpuRegisters) };
+
+pub const CLOCK: StaticRef<ClockRegisters> =
+    unsafe { StaticRef::new(0x61 as *const ClockRegisters) };
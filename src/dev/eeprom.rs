@@ -0,0 +1,33 @@
+//! EEPROM control registers.
+//!
+//! The ATmega48P has 256 bytes of EEPROM, so only `EEARL` is used and the
+//! high address byte is left reserved.
+
+use crate::mmio::{registers::ReadWrite, static_ref::StaticRef};
+
+register_structs! {
+    pub EepromRegisters {
+        (0x00 => pub eecr: ReadWrite<u8, EECR::Register>),
+        (0x01 => pub eedr: ReadWrite<u8>),
+        (0x02 => pub eearl: ReadWrite<u8>),
+        (0x03 => _reserved0),
+        (0x04 => @END),
+    }
+}
+
+register_bitfields![u8,
+    EECR [
+        EEPM OFFSET(4) NUMBITS(2) [
+            EraseWrite = 0,
+            Erase = 1,
+            Write = 2
+        ],
+        EERIE OFFSET(3) NUMBITS(1) [],
+        EEMPE OFFSET(2) NUMBITS(1) [],
+        EEPE OFFSET(1) NUMBITS(1) [],
+        EERE OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+pub const EEPROM: StaticRef<EepromRegisters> =
+    unsafe { StaticRef::new(0x3F as *const EepromRegisters) };
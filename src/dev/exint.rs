@@ -0,0 +1,91 @@
+//! External and pin change interrupts.
+
+use crate::mmio::{registers::ReadWrite, static_ref::StaticRef};
+
+register_structs! {
+    pub ExintFlagRegisters {
+        (0x00 => pub pcifr: ReadWrite<u8, PCIFR::Register>),
+        (0x01 => pub eifr: ReadWrite<u8, EIFR::Register>),
+        (0x02 => pub eimsk: ReadWrite<u8, EIMSK::Register>),
+        (0x03 => @END),
+    },
+    pub ExintRegisters {
+        (0x00 => pub pcicr: ReadWrite<u8, PCICR::Register>),
+        (0x01 => pub eicra: ReadWrite<u8, EICRA::Register>),
+        (0x02 => _reserved0),
+        (0x03 => pub pcmsk0: ReadWrite<u8, PCMSK0::Register>),
+        (0x04 => pub pcmsk1: ReadWrite<u8, PCMSK1::Register>),
+        (0x05 => pub pcmsk2: ReadWrite<u8, PCMSK2::Register>),
+        (0x06 => @END),
+    }
+}
+
+register_bitfields![u8,
+    PCIFR [
+        PCIF2 OFFSET(2) NUMBITS(1) [],
+        PCIF1 OFFSET(1) NUMBITS(1) [],
+        PCIF0 OFFSET(0) NUMBITS(1) []
+    ],
+    EIFR [
+        INTF1 OFFSET(1) NUMBITS(1) [],
+        INTF0 OFFSET(0) NUMBITS(1) []
+    ],
+    EIMSK [
+        INT1 OFFSET(1) NUMBITS(1) [],
+        INT0 OFFSET(0) NUMBITS(1) []
+    ],
+    PCICR [
+        PCIE2 OFFSET(2) NUMBITS(1) [],
+        PCIE1 OFFSET(1) NUMBITS(1) [],
+        PCIE0 OFFSET(0) NUMBITS(1) []
+    ],
+    EICRA [
+        ISC1 OFFSET(2) NUMBITS(2) [
+            LowLevel = 0,
+            AnyChange = 1,
+            Falling = 2,
+            Rising = 3
+        ],
+        ISC0 OFFSET(0) NUMBITS(2) [
+            LowLevel = 0,
+            AnyChange = 1,
+            Falling = 2,
+            Rising = 3
+        ]
+    ],
+    PCMSK0 [
+        PCINT7 OFFSET(7) NUMBITS(1) [],
+        PCINT6 OFFSET(6) NUMBITS(1) [],
+        PCINT5 OFFSET(5) NUMBITS(1) [],
+        PCINT4 OFFSET(4) NUMBITS(1) [],
+        PCINT3 OFFSET(3) NUMBITS(1) [],
+        PCINT2 OFFSET(2) NUMBITS(1) [],
+        PCINT1 OFFSET(1) NUMBITS(1) [],
+        PCINT0 OFFSET(0) NUMBITS(1) []
+    ],
+    PCMSK1 [
+        PCINT14 OFFSET(6) NUMBITS(1) [],
+        PCINT13 OFFSET(5) NUMBITS(1) [],
+        PCINT12 OFFSET(4) NUMBITS(1) [],
+        PCINT11 OFFSET(3) NUMBITS(1) [],
+        PCINT10 OFFSET(2) NUMBITS(1) [],
+        PCINT9 OFFSET(1) NUMBITS(1) [],
+        PCINT8 OFFSET(0) NUMBITS(1) []
+    ],
+    PCMSK2 [
+        PCINT23 OFFSET(7) NUMBITS(1) [],
+        PCINT22 OFFSET(6) NUMBITS(1) [],
+        PCINT21 OFFSET(5) NUMBITS(1) [],
+        PCINT20 OFFSET(4) NUMBITS(1) [],
+        PCINT19 OFFSET(3) NUMBITS(1) [],
+        PCINT18 OFFSET(2) NUMBITS(1) [],
+        PCINT17 OFFSET(1) NUMBITS(1) [],
+        PCINT16 OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+pub const EXINT_FLAGS: StaticRef<ExintFlagRegisters> =
+    unsafe { StaticRef::new(0x3B as *const ExintFlagRegisters) };
+
+pub const EXINT: StaticRef<ExintRegisters> =
+    unsafe { StaticRef::new(0x68 as *const ExintRegisters) };
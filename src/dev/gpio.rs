@@ -0,0 +1,129 @@
+//! General purpose I/O ports B, C and D.
+//!
+//! Writing a one to a bit of `PINx` toggles the matching `PORTx` bit, this is
+//! why the input registers are `Aliased` rather than `ReadOnly`.
+
+use crate::mmio::{
+    registers::{Aliased, ReadWrite},
+    static_ref::StaticRef,
+};
+
+register_structs! {
+    pub GpioBRegisters {
+        (0x00 => pub pinb: Aliased<u8, PINB::Register, PINB::Register>),
+        (0x01 => pub ddrb: ReadWrite<u8, DDRB::Register>),
+        (0x02 => pub portb: ReadWrite<u8, PORTB::Register>),
+        (0x03 => @END),
+    },
+    pub GpioCRegisters {
+        (0x00 => pub pinc: Aliased<u8, PINC::Register, PINC::Register>),
+        (0x01 => pub ddrc: ReadWrite<u8, DDRC::Register>),
+        (0x02 => pub portc: ReadWrite<u8, PORTC::Register>),
+        (0x03 => @END),
+    },
+    pub GpioDRegisters {
+        (0x00 => pub pind: Aliased<u8, PIND::Register, PIND::Register>),
+        (0x01 => pub ddrd: ReadWrite<u8, DDRD::Register>),
+        (0x02 => pub portd: ReadWrite<u8, PORTD::Register>),
+        (0x03 => @END),
+    }
+}
+
+register_bitfields![u8,
+    PINB [
+        PINB7 OFFSET(7) NUMBITS(1) [],
+        PINB6 OFFSET(6) NUMBITS(1) [],
+        PINB5 OFFSET(5) NUMBITS(1) [],
+        PINB4 OFFSET(4) NUMBITS(1) [],
+        PINB3 OFFSET(3) NUMBITS(1) [],
+        PINB2 OFFSET(2) NUMBITS(1) [],
+        PINB1 OFFSET(1) NUMBITS(1) [],
+        PINB0 OFFSET(0) NUMBITS(1) []
+    ],
+    DDRB IO(0x04) [
+        DDB7 OFFSET(7) NUMBITS(1) [],
+        DDB6 OFFSET(6) NUMBITS(1) [],
+        DDB5 OFFSET(5) NUMBITS(1) [],
+        DDB4 OFFSET(4) NUMBITS(1) [],
+        DDB3 OFFSET(3) NUMBITS(1) [],
+        DDB2 OFFSET(2) NUMBITS(1) [],
+        DDB1 OFFSET(1) NUMBITS(1) [],
+        DDB0 OFFSET(0) NUMBITS(1) []
+    ],
+    PORTB IO(0x05) [
+        PORTB7 OFFSET(7) NUMBITS(1) [],
+        PORTB6 OFFSET(6) NUMBITS(1) [],
+        PORTB5 OFFSET(5) NUMBITS(1) [],
+        PORTB4 OFFSET(4) NUMBITS(1) [],
+        PORTB3 OFFSET(3) NUMBITS(1) [],
+        PORTB2 OFFSET(2) NUMBITS(1) [],
+        PORTB1 OFFSET(1) NUMBITS(1) [],
+        PORTB0 OFFSET(0) NUMBITS(1) []
+    ],
+    PINC [
+        PINC6 OFFSET(6) NUMBITS(1) [],
+        PINC5 OFFSET(5) NUMBITS(1) [],
+        PINC4 OFFSET(4) NUMBITS(1) [],
+        PINC3 OFFSET(3) NUMBITS(1) [],
+        PINC2 OFFSET(2) NUMBITS(1) [],
+        PINC1 OFFSET(1) NUMBITS(1) [],
+        PINC0 OFFSET(0) NUMBITS(1) []
+    ],
+    DDRC IO(0x07) [
+        DDC6 OFFSET(6) NUMBITS(1) [],
+        DDC5 OFFSET(5) NUMBITS(1) [],
+        DDC4 OFFSET(4) NUMBITS(1) [],
+        DDC3 OFFSET(3) NUMBITS(1) [],
+        DDC2 OFFSET(2) NUMBITS(1) [],
+        DDC1 OFFSET(1) NUMBITS(1) [],
+        DDC0 OFFSET(0) NUMBITS(1) []
+    ],
+    PORTC IO(0x08) [
+        PORTC6 OFFSET(6) NUMBITS(1) [],
+        PORTC5 OFFSET(5) NUMBITS(1) [],
+        PORTC4 OFFSET(4) NUMBITS(1) [],
+        PORTC3 OFFSET(3) NUMBITS(1) [],
+        PORTC2 OFFSET(2) NUMBITS(1) [],
+        PORTC1 OFFSET(1) NUMBITS(1) [],
+        PORTC0 OFFSET(0) NUMBITS(1) []
+    ],
+    PIND [
+        PIND7 OFFSET(7) NUMBITS(1) [],
+        PIND6 OFFSET(6) NUMBITS(1) [],
+        PIND5 OFFSET(5) NUMBITS(1) [],
+        PIND4 OFFSET(4) NUMBITS(1) [],
+        PIND3 OFFSET(3) NUMBITS(1) [],
+        PIND2 OFFSET(2) NUMBITS(1) [],
+        PIND1 OFFSET(1) NUMBITS(1) [],
+        PIND0 OFFSET(0) NUMBITS(1) []
+    ],
+    DDRD IO(0x0A) [
+        DDD7 OFFSET(7) NUMBITS(1) [],
+        DDD6 OFFSET(6) NUMBITS(1) [],
+        DDD5 OFFSET(5) NUMBITS(1) [],
+        DDD4 OFFSET(4) NUMBITS(1) [],
+        DDD3 OFFSET(3) NUMBITS(1) [],
+        DDD2 OFFSET(2) NUMBITS(1) [],
+        DDD1 OFFSET(1) NUMBITS(1) [],
+        DDD0 OFFSET(0) NUMBITS(1) []
+    ],
+    PORTD IO(0x0B) [
+        PORTD7 OFFSET(7) NUMBITS(1) [],
+        PORTD6 OFFSET(6) NUMBITS(1) [],
+        PORTD5 OFFSET(5) NUMBITS(1) [],
+        PORTD4 OFFSET(4) NUMBITS(1) [],
+        PORTD3 OFFSET(3) NUMBITS(1) [],
+        PORTD2 OFFSET(2) NUMBITS(1) [],
+        PORTD1 OFFSET(1) NUMBITS(1) [],
+        PORTD0 OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+pub const GPIOB: StaticRef<GpioBRegisters> =
+    unsafe { StaticRef::new(0x23 as *const GpioBRegisters) };
+
+pub const GPIOC: StaticRef<GpioCRegisters> =
+    unsafe { StaticRef::new(0x26 as *const GpioCRegisters) };
+
+pub const GPIOD: StaticRef<GpioDRegisters> =
+    unsafe { StaticRef::new(0x29 as *const GpioDRegisters) };
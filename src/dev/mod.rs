@@ -0,0 +1,31 @@
+//! MMIO based device implementation.
+//!
+//! Register map of the ATmega48P built on top of the `mmio` types. Register
+//! blocks are placed at their data space addresses, single registers which
+//! are not adjacent to the rest of their peripheral get their own reference.
+
+pub mod ac;
+
+pub mod adc;
+
+pub mod cpu;
+
+pub mod eeprom;
+
+pub mod exint;
+
+pub mod gpio;
+
+pub mod spi;
+
+pub mod tc0;
+
+pub mod tc1;
+
+pub mod tc2;
+
+pub mod twi;
+
+pub mod usart;
+
+pub mod wdt;
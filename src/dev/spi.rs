@@ -0,0 +1,37 @@
+//! Serial Peripheral Interface.
+
+use crate::mmio::{registers::ReadWrite, static_ref::StaticRef};
+
+register_structs! {
+    pub SpiRegisters {
+        (0x00 => pub spcr: ReadWrite<u8, SPCR::Register>),
+        (0x01 => pub spsr: ReadWrite<u8, SPSR::Register>),
+        (0x02 => pub spdr: ReadWrite<u8>),
+        (0x03 => @END),
+    }
+}
+
+register_bitfields![u8,
+    SPCR [
+        SPIE OFFSET(7) NUMBITS(1) [],
+        SPE OFFSET(6) NUMBITS(1) [],
+        DORD OFFSET(5) NUMBITS(1) [],
+        MSTR OFFSET(4) NUMBITS(1) [],
+        CPOL OFFSET(3) NUMBITS(1) [],
+        CPHA OFFSET(2) NUMBITS(1) [],
+        /// Clock rate select, doubled by `SPI2X` in `SPSR`
+        SPR OFFSET(0) NUMBITS(2) [
+            Div4 = 0,
+            Div16 = 1,
+            Div64 = 2,
+            Div128 = 3
+        ]
+    ],
+    SPSR [
+        SPIF OFFSET(7) NUMBITS(1) [],
+        WCOL OFFSET(6) NUMBITS(1) [],
+        SPI2X OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+pub const SPI: StaticRef<SpiRegisters> = unsafe { StaticRef::new(0x4C as *const SpiRegisters) };
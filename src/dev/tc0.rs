@@ -0,0 +1,80 @@
+//! 8-bit Timer/Counter0 with PWM.
+
+use crate::mmio::{registers::ReadWrite, static_ref::StaticRef};
+
+register_structs! {
+    pub Tc0Registers {
+        (0x00 => pub gtccr: ReadWrite<u8, GTCCR::Register>),
+        (0x01 => pub tccr0a: ReadWrite<u8, TCCR0A::Register>),
+        (0x02 => pub tccr0b: ReadWrite<u8, TCCR0B::Register>),
+        (0x03 => pub tcnt0: ReadWrite<u8>),
+        (0x04 => pub ocr0a: ReadWrite<u8>),
+        (0x05 => pub ocr0b: ReadWrite<u8>),
+        (0x06 => @END),
+    }
+}
+
+register_bitfields![u8,
+    GTCCR [
+        /// Timer/Counter synchronization mode
+        TSM OFFSET(7) NUMBITS(1) [],
+        /// Prescaler reset Timer/Counter2
+        PSRASY OFFSET(1) NUMBITS(1) [],
+        /// Prescaler reset Timer/Counter1 and Timer/Counter0
+        PSRSYNC OFFSET(0) NUMBITS(1) []
+    ],
+    TCCR0A [
+        COM0A OFFSET(6) NUMBITS(2) [
+            Disconnected = 0,
+            Toggle = 1,
+            Clear = 2,
+            Set = 3
+        ],
+        COM0B OFFSET(4) NUMBITS(2) [
+            Disconnected = 0,
+            Toggle = 1,
+            Clear = 2,
+            Set = 3
+        ],
+        /// Low bits of the waveform generation mode, `WGM02` lives in `TCCR0B`
+        WGM0 OFFSET(0) NUMBITS(2) [
+            Normal = 0,
+            PwmPhaseCorrect = 1,
+            Ctc = 2,
+            FastPwm = 3
+        ]
+    ],
+    TCCR0B [
+        FOC0A OFFSET(7) NUMBITS(1) [],
+        FOC0B OFFSET(6) NUMBITS(1) [],
+        WGM02 OFFSET(3) NUMBITS(1) [],
+        CS0 OFFSET(0) NUMBITS(3) [
+            Stopped = 0,
+            Direct = 1,
+            Prescale8 = 2,
+            Prescale64 = 3,
+            Prescale256 = 4,
+            Prescale1024 = 5,
+            ExtFalling = 6,
+            ExtRising = 7
+        ]
+    ],
+    TIMSK0 [
+        OCIE0B OFFSET(2) NUMBITS(1) [],
+        OCIE0A OFFSET(1) NUMBITS(1) [],
+        TOIE0 OFFSET(0) NUMBITS(1) []
+    ],
+    TIFR0 [
+        OCF0B OFFSET(2) NUMBITS(1) [],
+        OCF0A OFFSET(1) NUMBITS(1) [],
+        TOV0 OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+pub const TC0: StaticRef<Tc0Registers> = unsafe { StaticRef::new(0x43 as *const Tc0Registers) };
+
+pub const TIMSK0: StaticRef<ReadWrite<u8, TIMSK0::Register>> =
+    unsafe { StaticRef::new(0x6E as *const ReadWrite<u8, TIMSK0::Register>) };
+
+pub const TIFR0: StaticRef<ReadWrite<u8, TIFR0::Register>> =
+    unsafe { StaticRef::new(0x35 as *const ReadWrite<u8, TIFR0::Register>) };
@@ -0,0 +1,79 @@
+//! 16-bit Timer/Counter1 with PWM.
+
+use crate::mmio::{
+    registers::{ReadWrite, ReadWrite16},
+    static_ref::StaticRef,
+};
+
+register_structs! {
+    pub Tc1Registers {
+        (0x00 => pub tccr1a: ReadWrite<u8, TCCR1A::Register>),
+        (0x01 => pub tccr1b: ReadWrite<u8, TCCR1B::Register>),
+        (0x02 => pub tccr1c: ReadWrite<u8, TCCR1C::Register>),
+        (0x03 => _reserved0),
+        (0x04 => pub tcnt1: ReadWrite16),
+        (0x06 => pub icr1: ReadWrite16),
+        (0x08 => pub ocr1a: ReadWrite16),
+        (0x0A => pub ocr1b: ReadWrite16),
+        (0x0C => @END),
+    }
+}
+
+register_bitfields![u8,
+    TCCR1A [
+        COM1A OFFSET(6) NUMBITS(2) [
+            Disconnected = 0,
+            Toggle = 1,
+            Clear = 2,
+            Set = 3
+        ],
+        COM1B OFFSET(4) NUMBITS(2) [
+            Disconnected = 0,
+            Toggle = 1,
+            Clear = 2,
+            Set = 3
+        ],
+        /// Low bits of the waveform generation mode (`WGM11:10`)
+        WGM1 OFFSET(0) NUMBITS(2) []
+    ],
+    TCCR1B [
+        ICNC1 OFFSET(7) NUMBITS(1) [],
+        ICES1 OFFSET(6) NUMBITS(1) [],
+        /// High bits of the waveform generation mode (`WGM13:12`)
+        WGM1 OFFSET(3) NUMBITS(2) [],
+        CS1 OFFSET(0) NUMBITS(3) [
+            Stopped = 0,
+            Direct = 1,
+            Prescale8 = 2,
+            Prescale64 = 3,
+            Prescale256 = 4,
+            Prescale1024 = 5,
+            ExtFalling = 6,
+            ExtRising = 7
+        ]
+    ],
+    TCCR1C [
+        FOC1A OFFSET(7) NUMBITS(1) [],
+        FOC1B OFFSET(6) NUMBITS(1) []
+    ],
+    TIMSK1 [
+        ICIE1 OFFSET(5) NUMBITS(1) [],
+        OCIE1B OFFSET(2) NUMBITS(1) [],
+        OCIE1A OFFSET(1) NUMBITS(1) [],
+        TOIE1 OFFSET(0) NUMBITS(1) []
+    ],
+    TIFR1 [
+        ICF1 OFFSET(5) NUMBITS(1) [],
+        OCF1B OFFSET(2) NUMBITS(1) [],
+        OCF1A OFFSET(1) NUMBITS(1) [],
+        TOV1 OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+pub const TC1: StaticRef<Tc1Registers> = unsafe { StaticRef::new(0x80 as *const Tc1Registers) };
+
+pub const TIMSK1: StaticRef<ReadWrite<u8, TIMSK1::Register>> =
+    unsafe { StaticRef::new(0x6F as *const ReadWrite<u8, TIMSK1::Register>) };
+
+pub const TIFR1: StaticRef<ReadWrite<u8, TIFR1::Register>> =
+    unsafe { StaticRef::new(0x36 as *const ReadWrite<u8, TIFR1::Register>) };
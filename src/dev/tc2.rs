@@ -0,0 +1,82 @@
+//! 8-bit Timer/Counter2 with PWM and asynchronous operation.
+
+use crate::mmio::{registers::ReadWrite, static_ref::StaticRef};
+
+register_structs! {
+    pub Tc2Registers {
+        (0x00 => pub tccr2a: ReadWrite<u8, TCCR2A::Register>),
+        (0x01 => pub tccr2b: ReadWrite<u8, TCCR2B::Register>),
+        (0x02 => pub tcnt2: ReadWrite<u8>),
+        (0x03 => pub ocr2a: ReadWrite<u8>),
+        (0x04 => pub ocr2b: ReadWrite<u8>),
+        (0x05 => _reserved0),
+        (0x06 => pub assr: ReadWrite<u8, ASSR::Register>),
+        (0x07 => @END),
+    }
+}
+
+register_bitfields![u8,
+    TCCR2A [
+        COM2A OFFSET(6) NUMBITS(2) [
+            Disconnected = 0,
+            Toggle = 1,
+            Clear = 2,
+            Set = 3
+        ],
+        COM2B OFFSET(4) NUMBITS(2) [
+            Disconnected = 0,
+            Toggle = 1,
+            Clear = 2,
+            Set = 3
+        ],
+        /// Low bits of the waveform generation mode, `WGM22` lives in `TCCR2B`
+        WGM2 OFFSET(0) NUMBITS(2) [
+            Normal = 0,
+            PwmPhaseCorrect = 1,
+            Ctc = 2,
+            FastPwm = 3
+        ]
+    ],
+    TCCR2B [
+        FOC2A OFFSET(7) NUMBITS(1) [],
+        FOC2B OFFSET(6) NUMBITS(1) [],
+        WGM22 OFFSET(3) NUMBITS(1) [],
+        CS2 OFFSET(0) NUMBITS(3) [
+            Stopped = 0,
+            Direct = 1,
+            Prescale8 = 2,
+            Prescale32 = 3,
+            Prescale64 = 4,
+            Prescale128 = 5,
+            Prescale256 = 6,
+            Prescale1024 = 7
+        ]
+    ],
+    ASSR [
+        EXCLK OFFSET(6) NUMBITS(1) [],
+        AS2 OFFSET(5) NUMBITS(1) [],
+        TCN2UB OFFSET(4) NUMBITS(1) [],
+        OCR2AUB OFFSET(3) NUMBITS(1) [],
+        OCR2BUB OFFSET(2) NUMBITS(1) [],
+        TCR2AUB OFFSET(1) NUMBITS(1) [],
+        TCR2BUB OFFSET(0) NUMBITS(1) []
+    ],
+    TIMSK2 [
+        OCIE2B OFFSET(2) NUMBITS(1) [],
+        OCIE2A OFFSET(1) NUMBITS(1) [],
+        TOIE2 OFFSET(0) NUMBITS(1) []
+    ],
+    TIFR2 [
+        OCF2B OFFSET(2) NUMBITS(1) [],
+        OCF2A OFFSET(1) NUMBITS(1) [],
+        TOV2 OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+pub const TC2: StaticRef<Tc2Registers> = unsafe { StaticRef::new(0xB0 as *const Tc2Registers) };
+
+pub const TIMSK2: StaticRef<ReadWrite<u8, TIMSK2::Register>> =
+    unsafe { StaticRef::new(0x70 as *const ReadWrite<u8, TIMSK2::Register>) };
+
+pub const TIFR2: StaticRef<ReadWrite<u8, TIFR2::Register>> =
+    unsafe { StaticRef::new(0x37 as *const ReadWrite<u8, TIFR2::Register>) };
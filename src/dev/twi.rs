@@ -0,0 +1,45 @@
+//! 2-wire Serial Interface (I2C).
+
+use crate::mmio::{registers::ReadWrite, static_ref::StaticRef};
+
+register_structs! {
+    pub TwiRegisters {
+        (0x00 => pub twbr: ReadWrite<u8>),
+        (0x01 => pub twsr: ReadWrite<u8, TWSR::Register>),
+        (0x02 => pub twar: ReadWrite<u8, TWAR::Register>),
+        (0x03 => pub twdr: ReadWrite<u8>),
+        (0x04 => pub twcr: ReadWrite<u8, TWCR::Register>),
+        (0x05 => pub twamr: ReadWrite<u8, TWAMR::Register>),
+        (0x06 => @END),
+    }
+}
+
+register_bitfields![u8,
+    TWSR [
+        TWS OFFSET(3) NUMBITS(5) [],
+        TWPS OFFSET(0) NUMBITS(2) [
+            Prescale1 = 0,
+            Prescale4 = 1,
+            Prescale16 = 2,
+            Prescale64 = 3
+        ]
+    ],
+    TWAR [
+        TWA OFFSET(1) NUMBITS(7) [],
+        TWGCE OFFSET(0) NUMBITS(1) []
+    ],
+    TWCR [
+        TWINT OFFSET(7) NUMBITS(1) [],
+        TWEA OFFSET(6) NUMBITS(1) [],
+        TWSTA OFFSET(5) NUMBITS(1) [],
+        TWSTO OFFSET(4) NUMBITS(1) [],
+        TWWC OFFSET(3) NUMBITS(1) [],
+        TWEN OFFSET(2) NUMBITS(1) [],
+        TWIE OFFSET(0) NUMBITS(1) []
+    ],
+    TWAMR [
+        TWAM OFFSET(1) NUMBITS(7) []
+    ]
+];
+
+pub const TWI: StaticRef<TwiRegisters> = unsafe { StaticRef::new(0xB8 as *const TwiRegisters) };
@@ -0,0 +1,64 @@
+//! USART0 serial interface.
+
+use crate::mmio::{
+    registers::{ReadWrite, ReadWrite16},
+    static_ref::StaticRef,
+};
+
+register_structs! {
+    pub UsartRegisters {
+        (0x00 => pub ucsr0a: ReadWrite<u8, UCSR0A::Register>),
+        (0x01 => pub ucsr0b: ReadWrite<u8, UCSR0B::Register>),
+        (0x02 => pub ucsr0c: ReadWrite<u8, UCSR0C::Register>),
+        (0x03 => _reserved0),
+        (0x04 => pub ubrr0: ReadWrite16),
+        (0x06 => pub udr0: ReadWrite<u8>),
+        (0x07 => @END),
+    }
+}
+
+register_bitfields![u8,
+    UCSR0A [
+        RXC0 OFFSET(7) NUMBITS(1) [],
+        TXC0 OFFSET(6) NUMBITS(1) [],
+        UDRE0 OFFSET(5) NUMBITS(1) [],
+        FE0 OFFSET(4) NUMBITS(1) [],
+        DOR0 OFFSET(3) NUMBITS(1) [],
+        UPE0 OFFSET(2) NUMBITS(1) [],
+        U2X0 OFFSET(1) NUMBITS(1) [],
+        MPCM0 OFFSET(0) NUMBITS(1) []
+    ],
+    UCSR0B [
+        RXCIE0 OFFSET(7) NUMBITS(1) [],
+        TXCIE0 OFFSET(6) NUMBITS(1) [],
+        UDRIE0 OFFSET(5) NUMBITS(1) [],
+        RXEN0 OFFSET(4) NUMBITS(1) [],
+        TXEN0 OFFSET(3) NUMBITS(1) [],
+        UCSZ02 OFFSET(2) NUMBITS(1) [],
+        RXB80 OFFSET(1) NUMBITS(1) [],
+        TXB80 OFFSET(0) NUMBITS(1) []
+    ],
+    UCSR0C [
+        UMSEL0 OFFSET(6) NUMBITS(2) [
+            Async = 0,
+            Sync = 1,
+            MasterSpi = 3
+        ],
+        UPM0 OFFSET(4) NUMBITS(2) [
+            Disabled = 0,
+            Even = 2,
+            Odd = 3
+        ],
+        USBS0 OFFSET(3) NUMBITS(1) [],
+        UCSZ0 OFFSET(1) NUMBITS(2) [
+            Chr5 = 0,
+            Chr6 = 1,
+            Chr7 = 2,
+            Chr8 = 3
+        ],
+        UCPOL0 OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+pub const USART0: StaticRef<UsartRegisters> =
+    unsafe { StaticRef::new(0xC0 as *const UsartRegisters) };
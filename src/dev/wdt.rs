@@ -0,0 +1,18 @@
+//! Watchdog Timer.
+
+use crate::mmio::{registers::ReadWrite, static_ref::StaticRef};
+
+register_bitfields![u8,
+    WDTCSR [
+        WDIF OFFSET(7) NUMBITS(1) [],
+        WDIE OFFSET(6) NUMBITS(1) [],
+        /// High bit of the prescaler, the low bits are `WDP`
+        WDP3 OFFSET(5) NUMBITS(1) [],
+        WDCE OFFSET(4) NUMBITS(1) [],
+        WDE OFFSET(3) NUMBITS(1) [],
+        WDP OFFSET(0) NUMBITS(3) []
+    ]
+];
+
+pub const WDTCSR: StaticRef<ReadWrite<u8, WDTCSR::Register>> =
+    unsafe { StaticRef::new(0x60 as *const ReadWrite<u8, WDTCSR::Register>) };
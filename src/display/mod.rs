@@ -0,0 +1,3 @@
+//! Display drivers.
+
+pub mod segment;
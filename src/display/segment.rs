@@ -0,0 +1,481 @@
+//! Multiplexed seven-segment displays.
+//!
+//! Segments are encoded one bit per segment, `SEG_A` in bit 0 up to `SEG_G`
+//! in bit 6 and the decimal point `SEG_DP` in bit 7:
+//!
+//! ```text
+//!  -A-
+//! F   B
+//!  -G-
+//! E   C
+//!  -D-  .DP
+//! ```
+//!
+//! [`Multiplexed`] drives the segment lines shared by all digits and one
+//! select line per digit. Only one digit is lit at a time, so its `refresh()`
+//! has to be called periodically, usually from a timer compare interrupt at
+//! a few hundred Hz per digit:
+//!
+//! ```no_run
+//! use m48_robo_rust::{
+//!     display::segment::{Multiplexed, Polarity},
+//!     gpio::{mode::Output, Pin, Pins},
+//!     interrupt::Shared,
+//! };
+//!
+//! static DISPLAY: Shared<Multiplexed<[Pin<Output>; 8], [Pin<Output>; 3]>> = Shared::new();
+//!
+//! let pins = Pins::take().unwrap();
+//! let c = pins.portc;
+//! let d = pins.portd;
+//! let display = Multiplexed::new(
+//!     [
+//!         c.pc0.into_output().downgrade(),
+//!         c.pc1.into_output().downgrade(),
+//!         c.pc2.into_output().downgrade(),
+//!         c.pc3.into_output().downgrade(),
+//!         c.pc4.into_output().downgrade(),
+//!         d.pd3.into_output().downgrade(),
+//!         d.pd4.into_output().downgrade(),
+//!         d.pd2.into_output().downgrade(),
+//!     ],
+//!     [
+//!         d.pd5.into_output().downgrade(),
+//!         d.pd6.into_output().downgrade(),
+//!         d.pd7.into_output().downgrade(),
+//!     ],
+//!     Polarity::CommonCathode,
+//! );
+//! DISPLAY.init(display).ok();
+//!
+//! // Printing a number, right aligned
+//! DISPLAY.lock(|_, display| {
+//!     display.clear();
+//!     ufmt::uwrite!(display, "{}", 42u16)
+//! });
+//!
+//! // and in the timer interrupt handler
+//! DISPLAY.lock(|_, display| display.refresh());
+//! ```
+//!
+//! [`Multiplexed`]: struct.Multiplexed.html
+
+use core::convert::Infallible;
+
+use embedded_hal::digital::v2::OutputPin;
+
+pub const SEG_A: u8 = 1 << 0;
+pub const SEG_B: u8 = 1 << 1;
+pub const SEG_C: u8 = 1 << 2;
+pub const SEG_D: u8 = 1 << 3;
+pub const SEG_E: u8 = 1 << 4;
+pub const SEG_F: u8 = 1 << 5;
+pub const SEG_G: u8 = 1 << 6;
+pub const SEG_DP: u8 = 1 << 7;
+
+pub const BLANK: u8 = 0;
+pub const MINUS: u8 = SEG_G;
+
+/// Hexadecimal digits `0`..`F`
+pub const HEX: [u8; 16] = [
+    SEG_A | SEG_B | SEG_C | SEG_D | SEG_E | SEG_F, // 0
+    SEG_B | SEG_C,                                 // 1
+    SEG_A | SEG_B | SEG_G | SEG_E | SEG_D,         // 2
+    SEG_A | SEG_B | SEG_G | SEG_C | SEG_D,         // 3
+    SEG_F | SEG_G | SEG_B | SEG_C,                 // 4
+    SEG_A | SEG_F | SEG_G | SEG_C | SEG_D,         // 5
+    SEG_A | SEG_F | SEG_G | SEG_C | SEG_D | SEG_E, // 6
+    SEG_A | SEG_B | SEG_C,                         // 7
+    SEG_A | SEG_B | SEG_C | SEG_D | SEG_E | SEG_F | SEG_G, // 8
+    SEG_F | SEG_A | SEG_B | SEG_G | SEG_C | SEG_D, // 9
+    SEG_A | SEG_B | SEG_C | SEG_E | SEG_F | SEG_G, // A
+    SEG_C | SEG_D | SEG_E | SEG_F | SEG_G,         // b
+    SEG_A | SEG_D | SEG_E | SEG_F,                 // C
+    SEG_B | SEG_C | SEG_D | SEG_E | SEG_G,         // d
+    SEG_A | SEG_D | SEG_E | SEG_F | SEG_G,         // E
+    SEG_A | SEG_E | SEG_F | SEG_G,                 // F
+];
+
+/// Segments of a character, `None` for characters without a glyph.
+///
+/// Besides the hex digits (in either case) this covers `-`, `_`, `=`, space
+/// and the letters `G H h J L n o P r S t U u y` which are readable on seven
+/// segments.
+pub fn glyph(c: char) -> Option<u8> {
+    let segments = match c {
+        '0'..='9' => HEX[c as usize - '0' as usize],
+        'A' | 'a' => HEX[10],
+        'B' | 'b' => HEX[11],
+        'C' => HEX[12],
+        'c' => SEG_D | SEG_E | SEG_G,
+        'D' | 'd' => HEX[13],
+        'E' | 'e' => HEX[14],
+        'F' | 'f' => HEX[15],
+        'G' | 'g' => SEG_A | SEG_C | SEG_D | SEG_E | SEG_F,
+        'H' => SEG_B | SEG_C | SEG_E | SEG_F | SEG_G,
+        'h' => SEG_C | SEG_E | SEG_F | SEG_G,
+        'J' | 'j' => SEG_B | SEG_C | SEG_D | SEG_E,
+        'L' | 'l' => SEG_D | SEG_E | SEG_F,
+        'N' | 'n' => SEG_C | SEG_E | SEG_G,
+        'O' | 'o' => SEG_C | SEG_D | SEG_E | SEG_G,
+        'P' | 'p' => SEG_A | SEG_B | SEG_E | SEG_F | SEG_G,
+        'R' | 'r' => SEG_E | SEG_G,
+        'S' | 's' => HEX[5],
+        'T' | 't' => SEG_D | SEG_E | SEG_F | SEG_G,
+        'U' => SEG_B | SEG_C | SEG_D | SEG_E | SEG_F,
+        'u' => SEG_C | SEG_D | SEG_E,
+        'Y' | 'y' => SEG_B | SEG_C | SEG_D | SEG_F | SEG_G,
+        '-' => MINUS,
+        '_' => SEG_D,
+        '=' => SEG_D | SEG_G,
+        ' ' => BLANK,
+        _ => return None,
+    };
+    Some(segments)
+}
+
+/// Wiring of the display
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Polarity {
+    /// Segments are lit high, the digit select lines are active low
+    CommonCathode,
+    /// Segments are lit low, the digit select lines are active high
+    CommonAnode,
+}
+
+/// Fixed group of output pins, implemented for arrays of up to 8
+/// `OutputPin`s
+pub trait OutputPins {
+    fn count(&self) -> usize;
+
+    fn set(&mut self, index: usize, high: bool);
+}
+
+macro_rules! output_pins {
+    ($($n:expr),*) => {
+        $(
+            impl<P: OutputPin> OutputPins for [P; $n] {
+                #[inline]
+                fn count(&self) -> usize {
+                    $n
+                }
+
+                #[inline]
+                fn set(&mut self, index: usize, high: bool) {
+                    let pin = &mut self[index];
+                    if high {
+                        pin.set_high().ok();
+                    } else {
+                        pin.set_low().ok();
+                    }
+                }
+            }
+        )*
+    };
+}
+
+output_pins!(1, 2, 3, 4, 5, 6, 7, 8);
+
+/// Maximum number of digits of a `Multiplexed` display
+pub const MAX_DIGITS: usize = 8;
+
+/// Display of up to `MAX_DIGITS` digits sharing segment lines.
+///
+/// `S` are the segment pins `A` to `G` and optionally `DP`, `D` the digit
+/// select pins from left to right.
+///
+/// Text written with `ufmt` enters from the right and shifts the digits left,
+/// so numbers written after `clear()` end up right aligned. A `.` lights the
+/// decimal point of the last digit and a line break clears the display
+/// before the next character.
+pub struct Multiplexed<S, D> {
+    segments: S,
+    digits: D,
+    polarity: Polarity,
+    invert_digits: bool,
+    buffer: [u8; MAX_DIGITS],
+    current: u8,
+    new_line: bool,
+}
+
+impl<S: OutputPins, D: OutputPins> Multiplexed<S, D> {
+    /// Create a blank display, all digits are switched off
+    pub fn new(segments: S, digits: D, polarity: Polarity) -> Multiplexed<S, D> {
+        let mut display = Multiplexed {
+            segments,
+            digits,
+            polarity,
+            invert_digits: false,
+            buffer: [BLANK; MAX_DIGITS],
+            current: 0,
+            new_line: false,
+        };
+        display.all_off();
+        display
+    }
+
+    /// Invert the digit select lines, for digits switched by a transistor
+    /// which inverts the level of the pin
+    pub fn invert_digits(mut self) -> Multiplexed<S, D> {
+        self.invert_digits = !self.invert_digits;
+        self.all_off();
+        self
+    }
+
+    /// Number of digits
+    pub fn width(&self) -> usize {
+        self.digits.count().min(MAX_DIGITS)
+    }
+
+    /// Raw segments of all digits, from left to right
+    pub fn buffer(&self) -> &[u8] {
+        &self.buffer[..self.width()]
+    }
+
+    /// Raw segments of all digits, from left to right
+    pub fn buffer_mut(&mut self) -> &mut [u8] {
+        let width = self.width();
+        &mut self.buffer[..width]
+    }
+
+    /// Set the raw segments of digit `index`, counted from the left
+    pub fn set_segments(&mut self, index: usize, segments: u8) {
+        if let Some(digit) = self.buffer_mut().get_mut(index) {
+            *digit = segments;
+        }
+    }
+
+    /// Show `c` on digit `index`, keeping its decimal point. Characters
+    /// without a glyph are shown blank.
+    pub fn set_char(&mut self, index: usize, c: char) {
+        if let Some(digit) = self.buffer_mut().get_mut(index) {
+            *digit = (*digit & SEG_DP) | glyph(c).unwrap_or(BLANK);
+        }
+    }
+
+    /// Switch the decimal point of digit `index`
+    pub fn set_dp(&mut self, index: usize, on: bool) {
+        if let Some(digit) = self.buffer_mut().get_mut(index) {
+            if on {
+                *digit |= SEG_DP;
+            } else {
+                *digit &= !SEG_DP;
+            }
+        }
+    }
+
+    /// Blank all digits
+    pub fn clear(&mut self) {
+        for digit in self.buffer_mut() {
+            *digit = BLANK;
+        }
+        self.new_line = false;
+    }
+
+    /// Shift a character in from the right, see the type docs
+    pub fn push_char(&mut self, c: char) {
+        match c {
+            '\n' | '\r' => self.new_line = true,
+            '.' | ',' => {
+                self.clear_on_new_line();
+                let last = self.width() - 1;
+                self.set_dp(last, true);
+            }
+            _ => {
+                self.clear_on_new_line();
+                let buffer = self.buffer_mut();
+                buffer.rotate_left(1);
+                let last = buffer.len() - 1;
+                buffer[last] = glyph(c).unwrap_or(BLANK);
+            }
+        }
+    }
+
+    /// Light the next digit, to be called periodically
+    pub fn refresh(&mut self) {
+        let width = self.width();
+        let (segment_on, digit_on) = self.levels();
+
+        self.digits.set(self.current as usize, !digit_on);
+        self.current = if self.current as usize + 1 < width {
+            self.current + 1
+        } else {
+            0
+        };
+
+        let segments = self.buffer[self.current as usize];
+        for i in 0..self.segments.count().min(8) {
+            let lit = segments & (1 << i) != 0;
+            self.segments.set(i, lit == segment_on);
+        }
+        self.digits.set(self.current as usize, digit_on);
+    }
+
+    /// Switch all digits off, e.g. before entering a sleep mode
+    pub fn all_off(&mut self) {
+        let (_, digit_on) = self.levels();
+        for i in 0..self.width() {
+            self.digits.set(i, !digit_on);
+        }
+    }
+
+    /// Give back the pins
+    pub fn release(self) -> (S, D) {
+        (self.segments, self.digits)
+    }
+
+    /// Pin levels lighting a segment and selecting a digit
+    fn levels(&self) -> (bool, bool) {
+        let (segment_on, digit_on) = match self.polarity {
+            Polarity::CommonCathode => (true, false),
+            Polarity::CommonAnode => (false, true),
+        };
+        (segment_on, digit_on != self.invert_digits)
+    }
+
+    fn clear_on_new_line(&mut self) {
+        if self.new_line {
+            self.clear();
+        }
+    }
+}
+
+impl<S: OutputPins, D: OutputPins> ufmt::uWrite for Multiplexed<S, D> {
+    type Error = Infallible;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Infallible> {
+        for c in s.chars() {
+            self.push_char(c);
+        }
+        Ok(())
+    }
+
+    fn write_char(&mut self, c: char) -> Result<(), Infallible> {
+        self.push_char(c);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+
+    use crate::mock::MockPin;
+
+    fn bits(cells: &[Cell<bool>]) -> u8 {
+        cells
+            .iter()
+            .enumerate()
+            .fold(0, |acc, (i, c)| acc | ((c.get() as u8) << i))
+    }
+
+    macro_rules! display {
+        ($seg:ident, $dig:ident, $polarity:expr) => {{
+            let s = &$seg;
+            let d = &$dig;
+            Multiplexed::new(
+                [
+                    MockPin(&s[0]),
+                    MockPin(&s[1]),
+                    MockPin(&s[2]),
+                    MockPin(&s[3]),
+                    MockPin(&s[4]),
+                    MockPin(&s[5]),
+                    MockPin(&s[6]),
+                    MockPin(&s[7]),
+                ],
+                [MockPin(&d[0]), MockPin(&d[1]), MockPin(&d[2])],
+                $polarity,
+            )
+        }};
+    }
+
+    #[test]
+    fn font() {
+        assert_eq!(glyph('8'), Some(0x7F));
+        assert_eq!(glyph('1'), Some(SEG_B | SEG_C));
+        assert_eq!(glyph('a'), glyph('A'));
+        assert_eq!(glyph('-'), Some(MINUS));
+        assert_eq!(glyph('K'), None);
+        for (i, c) in "0123456789ABCDEF".chars().enumerate() {
+            assert_eq!(glyph(c), Some(HEX[i]));
+        }
+    }
+
+    #[test]
+    fn write_right_aligned() {
+        let seg: [Cell<bool>; 8] = Default::default();
+        let dig: [Cell<bool>; 3] = Default::default();
+        let mut display = display!(seg, dig, Polarity::CommonCathode);
+
+        ufmt::uwrite!(&mut display, "{}", 42u8).unwrap();
+        assert_eq!(display.buffer(), &[BLANK, HEX[4], HEX[2]]);
+
+        display.clear();
+        ufmt::uwrite!(&mut display, "{}", 1234u16).unwrap();
+        assert_eq!(display.buffer(), &[HEX[2], HEX[3], HEX[4]]);
+
+        display.clear();
+        ufmt::uwrite!(&mut display, "-1.5").unwrap();
+        assert_eq!(display.buffer(), &[MINUS, HEX[1] | SEG_DP, HEX[5]]);
+    }
+
+    #[test]
+    fn line_break_clears_lazily() {
+        let seg: [Cell<bool>; 8] = Default::default();
+        let dig: [Cell<bool>; 3] = Default::default();
+        let mut display = display!(seg, dig, Polarity::CommonCathode);
+
+        ufmt::uwriteln!(&mut display, "{}", 123u8).unwrap();
+        assert_eq!(display.buffer(), &[HEX[1], HEX[2], HEX[3]]);
+        ufmt::uwrite!(&mut display, "7").unwrap();
+        assert_eq!(display.buffer(), &[BLANK, BLANK, HEX[7]]);
+    }
+
+    #[test]
+    fn refresh_common_cathode() {
+        let seg: [Cell<bool>; 8] = Default::default();
+        let dig: [Cell<bool>; 3] = Default::default();
+        let mut display = display!(seg, dig, Polarity::CommonCathode);
+        // All digits off after creation
+        assert_eq!(bits(&dig), 0b111);
+
+        display.set_segments(0, HEX[1]);
+        display.set_char(1, '2');
+        display.set_dp(2, true);
+
+        display.refresh();
+        assert_eq!(bits(&dig), 0b101);
+        assert_eq!(bits(&seg), HEX[2]);
+
+        display.refresh();
+        assert_eq!(bits(&dig), 0b011);
+        assert_eq!(bits(&seg), SEG_DP);
+
+        display.refresh();
+        assert_eq!(bits(&dig), 0b110);
+        assert_eq!(bits(&seg), HEX[1]);
+    }
+
+    #[test]
+    fn refresh_common_anode_inverted_digits() {
+        let seg: [Cell<bool>; 8] = Default::default();
+        let dig: [Cell<bool>; 3] = Default::default();
+        let display = display!(seg, dig, Polarity::CommonAnode);
+        assert_eq!(bits(&dig), 0b000);
+        // Selected by pulling the transistor input low, off is high then
+        let mut display = display.invert_digits();
+        assert_eq!(bits(&dig), 0b111);
+
+        display.set_char(0, 'H');
+        display.refresh();
+        display.refresh();
+        display.refresh();
+        assert_eq!(bits(&dig), 0b110);
+        assert_eq!(bits(&seg), !glyph('H').unwrap());
+
+        display.all_off();
+        assert_eq!(bits(&dig), 0b111);
+    }
+}
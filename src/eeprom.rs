@@ -0,0 +1,626 @@
+//! EEPROM driver with typed storage and wear leveling.
+//!
+//! [`Eeprom`] reads and writes bytes, slices and [`Persist`] values at
+//! byte addresses. Writes skip bytes which already hold the new value and
+//! only erase a byte when needed, which saves time and endurance (each byte
+//! survives about 100000 erase/write cycles).
+//!
+//! ```no_run
+//! use m48_robo_rust::eeprom::Eeprom;
+//!
+//! let dp = m48_robo_rust::Peripherals::take().unwrap();
+//! let mut eeprom = Eeprom::new(dp.EEPROM);
+//!
+//! eeprom.put(0x10, &1234u16).unwrap();
+//! let value: u16 = eeprom.get(0x10).unwrap();
+//! ```
+//!
+//! A value written very often, like a boot counter, is spread over several
+//! slots with a [`Ring`].
+//!
+//! The driver works on any [`Memory`], the internal EEPROM of the MCU is
+//! [`Internal`].
+//!
+//! [`Eeprom`]: struct.Eeprom.html
+//! [`Persist`]: trait.Persist.html
+//! [`Ring`]: struct.Ring.html
+//! [`Memory`]: trait.Memory.html
+//! [`Internal`]: struct.Internal.html
+
+use core::marker::PhantomData;
+
+use crate::fixed::{Q16_16, Q8_8};
+
+/// Largest `Persist::SIZE` `Eeprom::get` and `Eeprom::put` can handle
+pub const MAX_SIZE: usize = 32;
+
+/// EEPROM access error
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The access doesn't fit into the memory
+    OutOfRange,
+    /// The value is larger than `MAX_SIZE`
+    TooLarge,
+}
+
+/// How a byte is programmed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Erase the byte to `0xFF` and write the new value
+    EraseWrite,
+    /// Only erase the byte to `0xFF`
+    Erase,
+    /// Only write, which can clear bits but not set them
+    Write,
+}
+
+/// Byte addressed EEPROM
+pub trait Memory {
+    /// Size in bytes
+    fn size(&self) -> usize;
+    fn read(&mut self, address: usize) -> u8;
+    fn program(&mut self, address: usize, value: u8, mode: Mode);
+}
+
+/// Value with a fixed size representation in the EEPROM
+///
+/// Structs are stored field by field:
+///
+/// ```
+/// use m48_robo_rust::eeprom::Persist;
+///
+/// struct Calibration {
+///     offset: i16,
+///     gain: u16,
+/// }
+///
+/// impl Persist for Calibration {
+///     const SIZE: usize = 4;
+///
+///     fn save(&self, bytes: &mut [u8]) {
+///         self.offset.save(&mut bytes[0..2]);
+///         self.gain.save(&mut bytes[2..4]);
+///     }
+///
+///     fn load(bytes: &[u8]) -> Calibration {
+///         Calibration {
+///             offset: i16::load(&bytes[0..2]),
+///             gain: u16::load(&bytes[2..4]),
+///         }
+///     }
+/// }
+/// ```
+pub trait Persist: Sized {
+    /// Size in bytes
+    const SIZE: usize;
+    /// Store into `bytes`, which has `SIZE` bytes
+    fn save(&self, bytes: &mut [u8]);
+    /// Load from `bytes`, which has `SIZE` bytes
+    fn load(bytes: &[u8]) -> Self;
+}
+
+macro_rules! persist_int {
+    ($($t:ty),*) => {
+        $(
+            /// Little endian
+            impl Persist for $t {
+                const SIZE: usize = core::mem::size_of::<$t>();
+
+                fn save(&self, bytes: &mut [u8]) {
+                    bytes.copy_from_slice(&self.to_le_bytes());
+                }
+
+                fn load(bytes: &[u8]) -> $t {
+                    let mut raw = [0; core::mem::size_of::<$t>()];
+                    raw.copy_from_slice(bytes);
+                    <$t>::from_le_bytes(raw)
+                }
+            }
+        )*
+    };
+}
+
+persist_int!(u8, i8, u16, i16, u32, i32);
+
+impl Persist for bool {
+    const SIZE: usize = 1;
+
+    fn save(&self, bytes: &mut [u8]) {
+        bytes[0] = *self as u8;
+    }
+
+    /// Anything but 0 is `true`, so erased bytes load as `true`
+    fn load(bytes: &[u8]) -> bool {
+        bytes[0] != 0
+    }
+}
+
+impl Persist for Q8_8 {
+    const SIZE: usize = 2;
+
+    fn save(&self, bytes: &mut [u8]) {
+        self.to_bits().save(bytes);
+    }
+
+    fn load(bytes: &[u8]) -> Q8_8 {
+        Q8_8::from_bits(i16::load(bytes))
+    }
+}
+
+impl Persist for Q16_16 {
+    const SIZE: usize = 4;
+
+    fn save(&self, bytes: &mut [u8]) {
+        self.to_bits().save(bytes);
+    }
+
+    fn load(bytes: &[u8]) -> Q16_16 {
+        Q16_16::from_bits(i32::load(bytes))
+    }
+}
+
+/// EEPROM driver
+pub struct Eeprom<M> {
+    memory: M,
+}
+
+impl<M: Memory> Eeprom<M> {
+    /// Driver for any `memory`, see `Eeprom::new` for the internal EEPROM
+    pub fn with_memory(memory: M) -> Eeprom<M> {
+        Eeprom { memory }
+    }
+
+    /// Give back the memory
+    pub fn into_memory(self) -> M {
+        self.memory
+    }
+
+    /// Size in bytes
+    pub fn len(&self) -> usize {
+        self.memory.size()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn check(&self, address: usize, len: usize) -> Result<(), Error> {
+        match address.checked_add(len) {
+            Some(end) if end <= self.len() => Ok(()),
+            _ => Err(Error::OutOfRange),
+        }
+    }
+
+    pub fn read_byte(&mut self, address: usize) -> Result<u8, Error> {
+        self.check(address, 1)?;
+        Ok(self.memory.read(address))
+    }
+
+    /// Write a byte unless it already holds `value`
+    pub fn write_byte(&mut self, address: usize, value: u8) -> Result<(), Error> {
+        self.check(address, 1)?;
+        self.update(address, value);
+        Ok(())
+    }
+
+    /// Fill `bytes` starting at `address`
+    pub fn read(&mut self, address: usize, bytes: &mut [u8]) -> Result<(), Error> {
+        self.check(address, bytes.len())?;
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = self.memory.read(address + i);
+        }
+        Ok(())
+    }
+
+    /// Write `bytes` starting at `address`, skipping unchanged bytes
+    pub fn write(&mut self, address: usize, bytes: &[u8]) -> Result<(), Error> {
+        self.check(address, bytes.len())?;
+        for (i, &byte) in bytes.iter().enumerate() {
+            self.update(address + i, byte);
+        }
+        Ok(())
+    }
+
+    /// Load a value stored at `address`
+    pub fn get<T: Persist>(&mut self, address: usize) -> Result<T, Error> {
+        let mut buffer = [0; MAX_SIZE];
+        let bytes = buffer.get_mut(..T::SIZE).ok_or(Error::TooLarge)?;
+        self.read(address, bytes)?;
+        Ok(T::load(bytes))
+    }
+
+    /// Store `value` at `address`, skipping unchanged bytes
+    pub fn put<T: Persist>(&mut self, address: usize, value: &T) -> Result<(), Error> {
+        let mut buffer = [0; MAX_SIZE];
+        let bytes = buffer.get_mut(..T::SIZE).ok_or(Error::TooLarge)?;
+        value.save(bytes);
+        self.write(address, bytes)
+    }
+
+    /// Program the byte with the cheapest mode that gets it to `value`
+    fn update(&mut self, address: usize, value: u8) {
+        let old = self.memory.read(address);
+        if old == value {
+            return;
+        }
+        let mode = if value == 0xFF {
+            Mode::Erase
+        } else if old & value == value {
+            Mode::Write
+        } else {
+            Mode::EraseWrite
+        };
+        self.memory.program(address, value, mode);
+    }
+}
+
+/// EEPROM emulated in the array `B`, for host tests and simulations
+///
+/// Programming behaves like the real cells: erasing sets all bits and
+/// writing can only clear them.
+#[derive(Clone, Debug)]
+pub struct Ram<B> {
+    cells: B,
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> Ram<B> {
+    /// Memory with the contents of `cells`, `0xFF` is an erased byte
+    pub const fn new(cells: B) -> Ram<B> {
+        Ram { cells }
+    }
+
+    pub fn cells(&self) -> &[u8] {
+        self.cells.as_ref()
+    }
+
+    pub fn cells_mut(&mut self) -> &mut [u8] {
+        self.cells.as_mut()
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> Memory for Ram<B> {
+    fn size(&self) -> usize {
+        self.cells.as_ref().len()
+    }
+
+    fn read(&mut self, address: usize) -> u8 {
+        self.cells.as_ref()[address]
+    }
+
+    fn program(&mut self, address: usize, value: u8, mode: Mode) {
+        let cell = &mut self.cells.as_mut()[address];
+        if mode != Mode::Write {
+            *cell = 0xFF;
+        }
+        if mode != Mode::Erase {
+            *cell &= value;
+        }
+    }
+}
+
+/// Wear leveled storage of a value in `slots` copies
+///
+/// Each write goes to the next slot, so every byte is erased only once per
+/// `slots` writes. A status byte per slot holds a sequence number; the slot
+/// after which the sequence breaks is the current one. The value is written
+/// before its status byte, so a power loss during a write leaves the
+/// previous value in place.
+///
+/// The ring takes `slots * (T::SIZE + 1)` bytes from `start`, `slots` has
+/// to be 2 to 255.
+///
+/// ```no_run
+/// use m48_robo_rust::eeprom::{Eeprom, Ring};
+///
+/// static BOOTS: Ring<u16> = Ring::new(0, 16);
+///
+/// let dp = m48_robo_rust::Peripherals::take().unwrap();
+/// let mut eeprom = Eeprom::new(dp.EEPROM);
+/// let boots = BOOTS.read(&mut eeprom).unwrap().unwrap_or(0);
+/// BOOTS.write(&mut eeprom, &boots.wrapping_add(1)).unwrap();
+/// ```
+pub struct Ring<T> {
+    start: usize,
+    slots: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Persist> Ring<T> {
+    pub const fn new(start: usize, slots: usize) -> Ring<T> {
+        Ring {
+            start,
+            slots,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Bytes taken in the EEPROM
+    pub fn size(&self) -> usize {
+        self.slots * (T::SIZE + 1)
+    }
+
+    /// Current value, `None` if the ring was never written
+    pub fn read<M: Memory>(&self, eeprom: &mut Eeprom<M>) -> Result<Option<T>, Error> {
+        Ok(match self.current(eeprom)? {
+            Some((slot, _)) => Some(eeprom.get(self.value(slot))?),
+            None => None,
+        })
+    }
+
+    /// Replace the value, writing to the next slot
+    pub fn write<M: Memory>(&self, eeprom: &mut Eeprom<M>, value: &T) -> Result<(), Error> {
+        let (slot, seq) = match self.current(eeprom)? {
+            Some((slot, seq)) => ((slot + 1) % self.slots, seq.wrapping_add(1)),
+            None => (0, 0),
+        };
+        eeprom.put(self.value(slot), value)?;
+        eeprom.write_byte(self.start + slot, seq)
+    }
+
+    /// Erase the ring, `read` returns `None` afterwards
+    pub fn clear<M: Memory>(&self, eeprom: &mut Eeprom<M>) -> Result<(), Error> {
+        eeprom.check(self.start, self.size())?;
+        for slot in 0..self.slots {
+            eeprom.write_byte(self.start + slot, 0xFF)?;
+        }
+        Ok(())
+    }
+
+    /// Address of the value in `slot`
+    fn value(&self, slot: usize) -> usize {
+        self.start + self.slots + slot * T::SIZE
+    }
+
+    /// Current slot and its sequence number
+    fn current<M: Memory>(&self, eeprom: &mut Eeprom<M>) -> Result<Option<(usize, u8)>, Error> {
+        debug_assert!(self.slots >= 2 && self.slots <= 255);
+        eeprom.check(self.start, self.size())?;
+        let first = eeprom.read_byte(self.start)?;
+        let mut seq = first;
+        for slot in 0..self.slots {
+            let next = if slot + 1 == self.slots {
+                first
+            } else {
+                eeprom.read_byte(self.start + slot + 1)?
+            };
+            if next != seq.wrapping_add(1) {
+                // Equal status bytes are an erased or cleared ring, as
+                // written ones always differ somewhere
+                if next == seq && (0..self.slots).all(|i| eeprom.memory.read(self.start + i) == seq)
+                {
+                    return Ok(None);
+                }
+                return Ok(Some((slot, seq)));
+            }
+            seq = next;
+        }
+        // With at most 255 slots the sequence breaks somewhere
+        Ok(None)
+    }
+}
+
+#[cfg(target_arch = "avr")]
+pub use self::internal::Internal;
+
+#[cfg(target_arch = "avr")]
+mod internal {
+    use super::{Eeprom, Memory, Mode};
+    use crate::atmega48p;
+    use crate::dev::eeprom::{EECR, EEPROM};
+    use crate::interrupt;
+
+    /// The 256 bytes of the ATmega48P
+    pub struct Internal {
+        eeprom: atmega48p::EEPROM,
+    }
+
+    impl Eeprom<Internal> {
+        /// Driver for the internal EEPROM
+        pub fn new(eeprom: atmega48p::EEPROM) -> Eeprom<Internal> {
+            Eeprom::with_memory(Internal { eeprom })
+        }
+
+        /// Give back the EEPROM peripheral
+        pub fn release(self) -> atmega48p::EEPROM {
+            self.into_memory().eeprom
+        }
+    }
+
+    impl Memory for Internal {
+        fn size(&self) -> usize {
+            256
+        }
+
+        fn read(&mut self, address: usize) -> u8 {
+            read(address as u8)
+        }
+
+        fn program(&mut self, address: usize, value: u8, mode: Mode) {
+            program(address as u8, value, mode)
+        }
+    }
+
+    /// Read a byte after the last write has finished
+    pub(crate) fn read(address: u8) -> u8 {
+        while EEPROM.eecr.is_set(EECR::EEPE) {}
+        EEPROM.eearl.set(address);
+        EEPROM.eecr.write(EECR::EERE::SET);
+        EEPROM.eedr.get()
+    }
+
+    /// Start programming a byte after the last write has finished
+    pub(crate) fn program(address: u8, value: u8, mode: Mode) {
+        let mode = match mode {
+            Mode::EraseWrite => EECR::EEPM::EraseWrite,
+            Mode::Erase => EECR::EEPM::Erase,
+            Mode::Write => EECR::EEPM::Write,
+        };
+        while EEPROM.eecr.is_set(EECR::EEPE) {}
+        EEPROM.eearl.set(address);
+        EEPROM.eedr.set(value);
+        // EEPE has to be set within four cycles after EEMPE
+        interrupt::free(|_| {
+            EEPROM.eecr.write(EECR::EEMPE::SET + mode);
+            EEPROM.eecr.write(EECR::EEPE::SET + mode);
+        });
+    }
+}
+
+#[cfg(target_arch = "avr")]
+pub(crate) use self::internal::{program, read};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `Ram` counting erases per byte
+    struct Mock {
+        ram: Ram<[u8; 64]>,
+        erases: [u32; 64],
+        programs: usize,
+    }
+
+    impl Mock {
+        fn new() -> Mock {
+            Mock {
+                ram: Ram::new([0xFF; 64]),
+                erases: [0; 64],
+                programs: 0,
+            }
+        }
+    }
+
+    impl Memory for Mock {
+        fn size(&self) -> usize {
+            self.ram.size()
+        }
+
+        fn read(&mut self, address: usize) -> u8 {
+            self.ram.read(address)
+        }
+
+        fn program(&mut self, address: usize, value: u8, mode: Mode) {
+            self.programs += 1;
+            if mode != Mode::Write {
+                self.erases[address] += 1;
+            }
+            self.ram.program(address, value, mode);
+        }
+    }
+
+    #[test]
+    fn bytes_and_slices() {
+        let mut eeprom = Eeprom::with_memory(Mock::new());
+        assert_eq!(eeprom.read_byte(0), Ok(0xFF));
+        eeprom.write_byte(63, 0x12).unwrap();
+        assert_eq!(eeprom.read_byte(63), Ok(0x12));
+        assert_eq!(eeprom.read_byte(64), Err(Error::OutOfRange));
+        assert_eq!(eeprom.write_byte(64, 0), Err(Error::OutOfRange));
+
+        eeprom.write(10, &[1, 2, 3]).unwrap();
+        let mut bytes = [0; 4];
+        eeprom.read(9, &mut bytes).unwrap();
+        assert_eq!(bytes, [0xFF, 1, 2, 3]);
+        assert_eq!(eeprom.write(62, &[1, 2, 3]), Err(Error::OutOfRange));
+        assert_eq!(eeprom.read(usize::MAX, &mut bytes), Err(Error::OutOfRange));
+        // Nothing was written by the failed write
+        assert_eq!(eeprom.read_byte(62), Ok(0xFF));
+    }
+
+    #[test]
+    fn skip_unchanged_and_cheapest_mode() {
+        let mut eeprom = Eeprom::with_memory(Mock::new());
+        // Only clearing bits of an erased byte
+        eeprom.write(0, &[0x0F, 0xF0]).unwrap();
+        assert_eq!(eeprom.memory.programs, 2);
+        assert_eq!(eeprom.memory.erases[0], 0);
+
+        eeprom.write(0, &[0x0F, 0xF0]).unwrap();
+        assert_eq!(eeprom.memory.programs, 2);
+
+        // Clearing more bits still needs no erase, setting one does
+        eeprom.write(0, &[0x07, 0xF1]).unwrap();
+        assert_eq!(eeprom.memory.erases[..2], [0, 1]);
+        eeprom.write_byte(0, 0xFF).unwrap();
+        assert_eq!(eeprom.memory.erases[0], 1);
+        assert_eq!(eeprom.memory.programs, 5);
+
+        let mut bytes = [0; 2];
+        eeprom.read(0, &mut bytes).unwrap();
+        assert_eq!(bytes, [0xFF, 0xF1]);
+    }
+
+    #[test]
+    fn typed_values() {
+        let mut eeprom = Eeprom::with_memory(Mock::new());
+        eeprom.put(0, &0x1234u16).unwrap();
+        eeprom.put(2, &-2i32).unwrap();
+        eeprom.put(6, &false).unwrap();
+        eeprom.put(7, &Q8_8::from_int(-3)).unwrap();
+        eeprom.put(9, &Q16_16::PI).unwrap();
+        assert_eq!(eeprom.read_byte(0), Ok(0x34));
+        assert_eq!(eeprom.get::<u16>(0), Ok(0x1234));
+        assert_eq!(eeprom.get::<i32>(2), Ok(-2));
+        assert_eq!(eeprom.get::<bool>(6), Ok(false));
+        assert_eq!(eeprom.get::<bool>(20), Ok(true));
+        assert_eq!(eeprom.get::<Q8_8>(7), Ok(Q8_8::from_int(-3)));
+        assert_eq!(eeprom.get::<Q16_16>(9), Ok(Q16_16::PI));
+        assert_eq!(eeprom.get::<u32>(61), Err(Error::OutOfRange));
+
+        struct Big;
+
+        impl Persist for Big {
+            const SIZE: usize = MAX_SIZE + 1;
+            fn save(&self, _: &mut [u8]) {}
+            fn load(_: &[u8]) -> Big {
+                Big
+            }
+        }
+
+        assert_eq!(eeprom.put(0, &Big), Err(Error::TooLarge));
+        assert!(eeprom.get::<Big>(0).is_err());
+    }
+
+    #[test]
+    fn ring_levels_wear() {
+        let ring: Ring<u16> = Ring::new(4, 8);
+        assert_eq!(ring.size(), 24);
+        let mut eeprom = Eeprom::with_memory(Mock::new());
+        assert_eq!(ring.read(&mut eeprom), Ok(None));
+
+        for count in 0..1000u16 {
+            ring.write(&mut eeprom, &count).unwrap();
+            assert_eq!(ring.read(&mut eeprom), Ok(Some(count)));
+        }
+        // Each value byte is written once per 8 counts
+        let worst = eeprom.memory.erases.iter().max().copied().unwrap();
+        assert!(worst <= 1000 / 8 + 1, "{}", worst);
+        // Nothing outside the ring
+        assert!(eeprom.memory.erases[..4].iter().all(|&n| n == 0));
+        assert!(eeprom.memory.erases[28..].iter().all(|&n| n == 0));
+
+        ring.clear(&mut eeprom).unwrap();
+        assert_eq!(ring.read(&mut eeprom), Ok(None));
+        ring.write(&mut eeprom, &7).unwrap();
+        assert_eq!(ring.read(&mut eeprom), Ok(Some(7)));
+    }
+
+    #[test]
+    fn ring_survives_interrupted_write() {
+        let ring: Ring<u16> = Ring::new(0, 4);
+        let mut eeprom = Eeprom::with_memory(Mock::new());
+        for count in 0..6 {
+            ring.write(&mut eeprom, &count).unwrap();
+        }
+        // Power lost after the value of the next slot but before its status
+        eeprom.put(ring.value(2), &999u16).unwrap();
+        assert_eq!(ring.read(&mut eeprom), Ok(Some(5)));
+        ring.write(&mut eeprom, &6).unwrap();
+        assert_eq!(ring.read(&mut eeprom), Ok(Some(6)));
+
+        assert_eq!(
+            Ring::<u32>::new(40, 5).read(&mut eeprom),
+            Err(Error::OutOfRange)
+        );
+    }
+}
@@ -0,0 +1,528 @@
+//! Integer filters for ADC readings.
+//!
+//! All filters are plain structs without allocation and have `const`
+//! constructors, so they can be kept in a `static` and updated from the ADC
+//! interrupt. Windowed filters store their samples in an array provided by
+//! the caller, which also selects the window length:
+//!
+//! ```
+//! use m48_robo_rust::filter::{Exponential, Hysteresis, MovingAverage};
+//!
+//! static mut AVERAGE: MovingAverage<[u16; 8]> = MovingAverage::new([0; 8]);
+//!
+//! let mut smooth = Exponential::new(3);
+//! let mut threshold = Hysteresis::new(400, 600).debounce(3);
+//!
+//! for &raw in &[510, 498, 505, 502] {
+//!     let average = unsafe { AVERAGE.update(raw) };
+//!     assert!(average >= 500 && average <= 510);
+//!     smooth.update(raw);
+//!     assert!(!threshold.update(raw));
+//! }
+//! ```
+
+use crate::fixed::Q16_16;
+
+/// Arithmetic mean of the last `N` samples
+///
+/// Until the window is full the mean of the samples so far is returned.
+#[derive(Clone, Debug)]
+pub struct MovingAverage<B> {
+    window: B,
+    sum: u32,
+    next: usize,
+    filled: usize,
+}
+
+impl<B: AsRef<[u16]> + AsMut<[u16]>> MovingAverage<B> {
+    /// Filter over the length of `window`, its contents are ignored. The
+    /// window has to hold at least one sample, `update` panics otherwise.
+    pub const fn new(window: B) -> MovingAverage<B> {
+        MovingAverage {
+            window,
+            sum: 0,
+            next: 0,
+            filled: 0,
+        }
+    }
+
+    /// Add a sample and return the rounded mean
+    pub fn update(&mut self, sample: u16) -> u16 {
+        let window = self.window.as_mut();
+        // The length of a generic window is unknown to the const constructor
+        assert!(!window.is_empty(), "empty filter window");
+        if self.filled == window.len() {
+            self.sum -= window[self.next] as u32;
+        } else {
+            self.filled += 1;
+        }
+        self.sum += sample as u32;
+        window[self.next] = sample;
+        self.next = (self.next + 1) % window.len();
+        self.value()
+    }
+
+    /// Rounded mean, zero before the first sample
+    pub fn value(&self) -> u16 {
+        if self.filled == 0 {
+            return 0;
+        }
+        let filled = self.filled as u32;
+        ((self.sum + filled / 2) / filled) as u16
+    }
+
+    /// Forget all samples
+    pub fn reset(&mut self) {
+        self.sum = 0;
+        self.next = 0;
+        self.filled = 0;
+    }
+}
+
+/// Median of the last `N` samples, removes spikes while keeping edges
+///
+/// For an even number of samples the upper of the two middle samples is
+/// returned. Computing the median takes `N²` comparisons, so windows should
+/// stay short.
+#[derive(Clone, Debug)]
+pub struct Median<B> {
+    window: B,
+    next: usize,
+    filled: usize,
+}
+
+impl<B: AsRef<[u16]> + AsMut<[u16]>> Median<B> {
+    /// Filter over the length of `window`, its contents are ignored. The
+    /// window has to hold at least one sample, `update` panics otherwise.
+    pub const fn new(window: B) -> Median<B> {
+        Median {
+            window,
+            next: 0,
+            filled: 0,
+        }
+    }
+
+    /// Add a sample and return the median
+    pub fn update(&mut self, sample: u16) -> u16 {
+        let window = self.window.as_mut();
+        assert!(!window.is_empty(), "empty filter window");
+        window[self.next] = sample;
+        self.next = (self.next + 1) % window.len();
+        if self.filled < window.len() {
+            self.filled += 1;
+        }
+        self.value()
+    }
+
+    /// Median, zero before the first sample
+    pub fn value(&self) -> u16 {
+        let samples = &self.window.as_ref()[..self.filled];
+        let rank = samples.len() / 2;
+        for &candidate in samples {
+            let mut below = 0;
+            let mut equal = 0;
+            for &sample in samples {
+                if sample < candidate {
+                    below += 1;
+                } else if sample == candidate {
+                    equal += 1;
+                }
+            }
+            if below <= rank && rank < below + equal {
+                return candidate;
+            }
+        }
+        0
+    }
+
+    /// Forget all samples
+    pub fn reset(&mut self) {
+        self.next = 0;
+        self.filled = 0;
+    }
+}
+
+/// First order IIR low-pass, `y += (x - y) / 2^shift`
+///
+/// The state keeps `shift` extra bits, so the output settles exactly on a
+/// constant input. The time constant is about `2^shift` samples; the first
+/// sample initializes the output.
+#[derive(Clone, Debug)]
+pub struct Exponential {
+    shift: u8,
+    acc: u32,
+    primed: bool,
+}
+
+impl Exponential {
+    /// Filter with a smoothing factor of `1 / 2^shift`.
+    ///
+    /// Panics if `shift` is above 16, where the state no longer fits 32 bits.
+    /// In a `static` this is a compile time error.
+    pub const fn new(shift: u8) -> Exponential {
+        // `assert!` is not usable in a const fn, an out of bounds index is
+        let shift = [shift][(shift > 16) as usize];
+        Exponential {
+            shift,
+            acc: 0,
+            primed: false,
+        }
+    }
+
+    /// Add a sample and return the filtered value
+    pub fn update(&mut self, sample: u16) -> u16 {
+        if self.primed {
+            self.acc = self.acc - (self.acc >> self.shift) + sample as u32;
+        } else {
+            self.acc = (sample as u32) << self.shift;
+            self.primed = true;
+        }
+        self.value()
+    }
+
+    pub fn value(&self) -> u16 {
+        (self.acc >> self.shift) as u16
+    }
+
+    /// Start over, the next sample initializes the output again
+    pub fn reset(&mut self) {
+        self.acc = 0;
+        self.primed = false;
+    }
+}
+
+/// Second order IIR section
+///
+/// Computes `y = b0 x + b1 x[-1] + b2 x[-2] - a1 y[-1] - a2 y[-2]` with
+/// `Q16_16` coefficients, keeping 16 fractional bits of the output in the
+/// state. Higher orders are built by feeding one section into the next.
+#[derive(Clone, Debug)]
+pub struct Biquad {
+    b: [i32; 3],
+    a: [i32; 2],
+    x: [i32; 2],
+    y: [i64; 2],
+}
+
+impl Biquad {
+    /// Section with the given coefficients, normalized to `a0 = 1`
+    pub const fn new(b: [Q16_16; 3], a: [Q16_16; 2]) -> Biquad {
+        Biquad {
+            b: [b[0].to_bits(), b[1].to_bits(), b[2].to_bits()],
+            a: [a[0].to_bits(), a[1].to_bits()],
+            x: [0; 2],
+            y: [0; 2],
+        }
+    }
+
+    /// Butterworth-like low-pass at `cutoff`, given as a fraction of the
+    /// sample rate below `0.5`, with quality factor `q` (`1/√2` for a flat
+    /// pass band)
+    pub fn lowpass(cutoff: Q16_16, q: Q16_16) -> Biquad {
+        let (cos, alpha) = Biquad::prewarp(cutoff, q);
+        let b1 = Q16_16::ONE - cos;
+        let b0 = b1 / Q16_16::from_int(2);
+        Biquad::normalized([b0, b1, b0], cos, alpha)
+    }
+
+    /// High-pass at `cutoff`, see [`lowpass`](#method.lowpass)
+    pub fn highpass(cutoff: Q16_16, q: Q16_16) -> Biquad {
+        let (cos, alpha) = Biquad::prewarp(cutoff, q);
+        let b1 = Q16_16::ONE + cos;
+        let b0 = b1 / Q16_16::from_int(2);
+        Biquad::normalized([b0, -b1, b0], cos, alpha)
+    }
+
+    /// Add a sample and return the filtered value
+    pub fn update(&mut self, sample: i32) -> i32 {
+        let [b0, b1, b2] = self.b;
+        let [a1, a2] = self.a;
+        let feedforward =
+            b0 as i64 * sample as i64 + b1 as i64 * self.x[0] as i64 + b2 as i64 * self.x[1] as i64;
+        let feedback = (a1 as i64 * self.y[0] + a2 as i64 * self.y[1]) >> 16;
+        let y = feedforward - feedback;
+
+        self.x = [sample, self.x[0]];
+        self.y = [y, self.y[0]];
+        self.value()
+    }
+
+    /// Last output, rounded
+    pub fn value(&self) -> i32 {
+        ((self.y[0] + (1 << 15)) >> 16) as i32
+    }
+
+    /// Clear the state, as if all past samples were zero
+    pub fn reset(&mut self) {
+        self.x = [0; 2];
+        self.y = [0; 2];
+    }
+
+    // Filter design after the Audio EQ Cookbook by R. Bristow-Johnson
+    fn prewarp(cutoff: Q16_16, q: Q16_16) -> (Q16_16, Q16_16) {
+        let w0 = Q16_16::TAU * cutoff;
+        (w0.cos(), w0.sin() / (q * Q16_16::from_int(2)))
+    }
+
+    fn normalized(b: [Q16_16; 3], cos: Q16_16, alpha: Q16_16) -> Biquad {
+        let a0 = Q16_16::ONE + alpha;
+        let a1 = -cos * Q16_16::from_int(2);
+        let a2 = Q16_16::ONE - alpha;
+        Biquad::new([b[0] / a0, b[1] / a0, b[2] / a0], [a1 / a0, a2 / a0])
+    }
+}
+
+/// Comparator with hysteresis and optional debouncing
+///
+/// The output switches on above `high` and off below `low`, and only after
+/// the input stayed past the threshold for the debounce count of samples.
+#[derive(Clone, Debug)]
+pub struct Hysteresis {
+    low: u16,
+    high: u16,
+    debounce: u8,
+    count: u8,
+    state: bool,
+}
+
+impl Hysteresis {
+    /// Comparator switching on at `high` and off at `low`, starting off
+    pub const fn new(low: u16, high: u16) -> Hysteresis {
+        Hysteresis {
+            low,
+            high,
+            debounce: 1,
+            count: 0,
+            state: false,
+        }
+    }
+
+    /// Require `samples` consecutive samples past a threshold to switch
+    pub const fn debounce(mut self, samples: u8) -> Hysteresis {
+        self.debounce = samples;
+        self
+    }
+
+    /// Add a sample and return the output
+    pub fn update(&mut self, sample: u16) -> bool {
+        let past = if self.state {
+            sample <= self.low
+        } else {
+            sample >= self.high
+        };
+        if past {
+            self.count += 1;
+            if self.count >= self.debounce {
+                self.state = !self.state;
+                self.count = 0;
+            }
+        } else {
+            self.count = 0;
+        }
+        self.state
+    }
+
+    pub fn state(&self) -> bool {
+        self.state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic noise in `-amplitude..=amplitude`
+    struct Noise(u32);
+
+    impl Noise {
+        fn next(&mut self, amplitude: i32) -> i32 {
+            self.0 = self.0.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            ((self.0 >> 16) as i32 % (2 * amplitude + 1)) - amplitude
+        }
+    }
+
+    fn noisy(level: i32, samples: usize) -> Vec<u16> {
+        let mut noise = Noise(1);
+        (0..samples)
+            .map(|_| (level + noise.next(20)) as u16)
+            .collect()
+    }
+
+    fn spread(values: &[u16]) -> u16 {
+        values.iter().max().unwrap() - values.iter().min().unwrap()
+    }
+
+    #[test]
+    fn moving_average_step() {
+        let mut filter = MovingAverage::new([0u16; 4]);
+        assert_eq!(filter.value(), 0);
+        assert_eq!(filter.update(100), 100);
+        let outputs: Vec<_> = (0..5).map(|_| filter.update(200)).collect();
+        assert_eq!(outputs, [150, 167, 175, 200, 200]);
+        filter.reset();
+        assert_eq!(filter.update(7), 7);
+    }
+
+    #[test]
+    fn moving_average_noise() {
+        let input = noisy(500, 200);
+        let mut filter = MovingAverage::new([0u16; 16]);
+        let output: Vec<_> = input.iter().map(|&x| filter.update(x)).collect();
+        assert!(spread(&input) > 35);
+        assert!(spread(&output[16..]) < 15, "{}", spread(&output[16..]));
+        for &y in &output[16..] {
+            assert!((y as i32 - 500).abs() <= 7);
+        }
+    }
+
+    #[test]
+    fn median_rejects_spikes() {
+        let mut filter = Median::new([0u16; 5]);
+        let input = [100, 100, 1023, 100, 0, 100, 100, 300, 300, 300, 300];
+        let output: Vec<_> = input.iter().map(|&x| filter.update(x)).collect();
+        assert_eq!(
+            output,
+            [100, 100, 100, 100, 100, 100, 100, 100, 100, 300, 300]
+        );
+    }
+
+    #[test]
+    fn median_even_window() {
+        let mut filter = Median::new([0u16; 4]);
+        assert_eq!(filter.value(), 0);
+        for &x in &[4, 1, 3, 2] {
+            filter.update(x);
+        }
+        assert_eq!(filter.value(), 3);
+        filter.reset();
+        assert_eq!(filter.update(9), 9);
+    }
+
+    #[test]
+    fn exponential_step() {
+        let mut filter = Exponential::new(3);
+        assert_eq!(filter.update(100), 100);
+        let outputs: Vec<_> = (0..100).map(|_| filter.update(900)).collect();
+        // About 1 - 1/e of the step after 2^shift samples
+        assert!(outputs[7] >= 600 && outputs[7] <= 640, "{}", outputs[7]);
+        assert!(outputs.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(outputs[99], 900);
+
+        let outputs: Vec<_> = (0..100).map(|_| filter.update(3)).collect();
+        assert_eq!(outputs[99], 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "empty filter window")]
+    fn moving_average_empty_window() {
+        MovingAverage::new([0u16; 0]).update(1);
+    }
+
+    #[test]
+    #[should_panic(expected = "empty filter window")]
+    fn median_empty_window() {
+        Median::new([0u16; 0]).update(1);
+    }
+
+    #[test]
+    fn exponential_shift_limit() {
+        let mut filter = Exponential::new(16);
+        assert_eq!(filter.update(u16::MAX), u16::MAX);
+        assert_eq!(filter.update(u16::MAX), u16::MAX);
+    }
+
+    #[test]
+    #[should_panic]
+    fn exponential_shift_too_large() {
+        Exponential::new(17);
+    }
+
+    #[test]
+    fn exponential_noise() {
+        let input = noisy(500, 300);
+        let mut filter = Exponential::new(4);
+        let output: Vec<_> = input.iter().map(|&x| filter.update(x)).collect();
+        assert!(spread(&output[100..]) < 15, "{}", spread(&output[100..]));
+        filter.reset();
+        assert_eq!(filter.update(42), 42);
+    }
+
+    fn butterworth() -> Q16_16 {
+        Q16_16::from_f32(core::f32::consts::FRAC_1_SQRT_2)
+    }
+
+    #[test]
+    fn biquad_lowpass() {
+        let mut filter = Biquad::lowpass(Q16_16::from_ratio(1, 20), butterworth());
+        let step: Vec<_> = (0..200).map(|_| filter.update(1000)).collect();
+        // Critically flat response overshoots a few percent
+        let peak = *step.iter().max().unwrap();
+        assert!(peak > 1000 && peak < 1060, "{}", peak);
+        assert!((step[199] - 1000).abs() <= 2, "{}", step[199]);
+
+        // Nyquist is blocked
+        filter.reset();
+        let output: Vec<_> = (0..200)
+            .map(|i| filter.update(if i % 2 == 0 { 500 } else { -500 }))
+            .collect();
+        assert!(output[100..].iter().all(|y| y.abs() <= 2));
+    }
+
+    #[test]
+    fn biquad_highpass() {
+        let mut filter = Biquad::highpass(Q16_16::from_ratio(1, 40), butterworth());
+        let step: Vec<_> = (0..300).map(|_| filter.update(1000)).collect();
+        assert!(step[0] > 700);
+        assert!(step[299].abs() <= 2, "{}", step[299]);
+
+        filter.reset();
+        let output: Vec<_> = (0..200)
+            .map(|i| filter.update(if i % 2 == 0 { 500 } else { -500 }))
+            .collect();
+        assert!(output[100..].iter().all(|y| (y.abs() - 500).abs() <= 10));
+    }
+
+    #[test]
+    fn biquad_sections_cascade() {
+        let mut first = Biquad::lowpass(Q16_16::from_ratio(1, 20), butterworth());
+        let mut second = first.clone();
+        let input = noisy(500, 300);
+        let output: Vec<_> = input
+            .iter()
+            .map(|&x| second.update(first.update(x as i32)) as u16)
+            .collect();
+        assert!(spread(&output[50..]) < spread(&input) / 2);
+    }
+
+    #[test]
+    fn hysteresis() {
+        let mut comparator = Hysteresis::new(400, 600);
+        let input = [500, 599, 600, 500, 401, 400, 599, 650];
+        let output: Vec<_> = input.iter().map(|&x| comparator.update(x)).collect();
+        assert_eq!(output, [false, false, true, true, true, false, false, true]);
+    }
+
+    #[test]
+    fn hysteresis_debounce() {
+        let mut comparator = Hysteresis::new(400, 600).debounce(3);
+        let input = [700, 700, 500, 700, 700, 700, 300, 300, 700, 300];
+        let output: Vec<_> = input.iter().map(|&x| comparator.update(x)).collect();
+        assert_eq!(
+            output,
+            [false, false, false, false, false, true, true, true, true, true]
+        );
+        assert!(comparator.state());
+
+        // Noise around a single threshold doesn't chatter
+        let mut comparator = Hysteresis::new(480, 520).debounce(2);
+        let mut switches = 0;
+        let mut last = false;
+        for x in noisy(500, 100).into_iter().chain(noisy(560, 100)) {
+            if comparator.update(x) != last {
+                last = !last;
+                switches += 1;
+            }
+        }
+        assert!(switches <= 1, "{}", switches);
+    }
+}
@@ -0,0 +1,591 @@
+//! Saturating binary fixed-point numbers.
+//!
+//! [`Q8_8`] (16 bit, range ±128, resolution 1/256) and [`Q16_16`] (32 bit,
+//! range ±32768, resolution 1/65536) replace soft-float for control math.
+//! All arithmetic saturates at `MIN`/`MAX` instead of wrapping or panicking,
+//! division by zero saturates towards the sign of the dividend.
+//!
+//! ```
+//! use m48_robo_rust::fixed::Q16_16;
+//!
+//! // 10-bit ADC reading with a 5 V reference
+//! let volts = Q16_16::from_adc(512, 10, Q16_16::from_int(5));
+//! assert_eq!(volts, Q16_16::from_ratio(5, 2));
+//!
+//! let gain = Q16_16::from_f32(1.5);
+//! assert_eq!((volts * gain).round(), 4);
+//! ```
+//!
+//! Trigonometry works in radians. `sin`/`cos` interpolate a quarter-wave
+//! table and `atan2` an arctangent table, both accurate to about 1e-4; the
+//! tables are kept in program memory.
+//!
+//! [`Q8_8`]: struct.Q8_8.html
+//! [`Q16_16`]: struct.Q16_16.html
+
+use core::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+use ufmt::{uDisplay, uWrite, Formatter};
+
+use crate::progmem;
+
+macro_rules! fixed {
+    ($(#[$attr:meta])* $name:ident($repr:ident, $wide:ident), $frac:expr, $precision:expr,
+     pi: $pi:expr, frac_pi_2: $frac_pi_2:expr, tau: $tau:expr) => {
+        $(#[$attr])*
+        #[allow(non_camel_case_types)]
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name($repr);
+
+        impl $name {
+            /// Number of fractional bits
+            pub const FRAC_BITS: u8 = $frac;
+
+            pub const ZERO: $name = $name(0);
+            pub const ONE: $name = $name(1 << $frac);
+            pub const MIN: $name = $name($repr::MIN);
+            pub const MAX: $name = $name($repr::MAX);
+            /// Smallest positive value
+            pub const DELTA: $name = $name(1);
+
+            pub const PI: $name = $name($pi);
+            pub const FRAC_PI_2: $name = $name($frac_pi_2);
+            pub const TAU: $name = $name($tau);
+
+            /// Value from its raw representation, `bits / 2^FRAC_BITS`
+            pub const fn from_bits(bits: $repr) -> $name {
+                $name(bits)
+            }
+
+            /// Raw representation
+            pub const fn to_bits(self) -> $repr {
+                self.0
+            }
+
+            /// Integer value, saturating
+            pub fn from_int(value: $repr) -> $name {
+                $name::saturate((value as $wide) << $frac)
+            }
+
+            /// `num / den`, saturating
+            pub fn from_ratio(num: $repr, den: $repr) -> $name {
+                $name::divide((num as $wide) << $frac, den as $wide)
+            }
+
+            /// Nearest value to `value`, saturating. Uses soft-float on the
+            /// MCU, so better for constants and tests than for loops.
+            pub fn from_f32(value: f32) -> $name {
+                let scaled = value * (1u32 << $frac) as f32;
+                let rounded = if scaled < 0.0 { scaled - 0.5 } else { scaled + 0.5 };
+                // Float to int casts saturate
+                $name(rounded as $repr)
+            }
+
+            pub fn to_f32(self) -> f32 {
+                self.0 as f32 / (1u32 << $frac) as f32
+            }
+
+            /// Convert an ADC reading of `bits` resolution, `counts / 2^bits`
+            /// of `full_scale`
+            pub fn from_adc(counts: u16, bits: u8, full_scale: $name) -> $name {
+                $name::saturate((counts as $wide * full_scale.0 as $wide) >> bits)
+            }
+
+            /// Integer part, rounded towards negative infinity
+            pub fn to_int(self) -> $repr {
+                self.0 >> $frac
+            }
+
+            /// Integer nearest to the value, halves rounded up
+            pub fn round(self) -> $repr {
+                ((self.0 as $wide + (1 << ($frac - 1))) >> $frac) as $repr
+            }
+
+            pub fn abs(self) -> $name {
+                $name(self.0.saturating_abs())
+            }
+
+            pub fn is_negative(self) -> bool {
+                self.0 < 0
+            }
+
+            /// Restrict the value to `min..=max`
+            pub fn clamp(self, min: $name, max: $name) -> $name {
+                if self < min {
+                    min
+                } else if self > max {
+                    max
+                } else {
+                    self
+                }
+            }
+
+            pub fn saturating_add(self, rhs: $name) -> $name {
+                $name(self.0.saturating_add(rhs.0))
+            }
+
+            pub fn saturating_sub(self, rhs: $name) -> $name {
+                $name(self.0.saturating_sub(rhs.0))
+            }
+
+            /// Product rounded to nearest, saturating
+            pub fn saturating_mul(self, rhs: $name) -> $name {
+                let product = self.0 as $wide * rhs.0 as $wide;
+                $name::saturate((product + (1 << ($frac - 1))) >> $frac)
+            }
+
+            /// Quotient rounded towards zero, saturating
+            pub fn saturating_div(self, rhs: $name) -> $name {
+                $name::divide((self.0 as $wide) << $frac, rhs.0 as $wide)
+            }
+
+            /// Quotient, `None` on division by zero or overflow
+            pub fn checked_div(self, rhs: $name) -> Option<$name> {
+                if rhs.0 == 0 {
+                    return None;
+                }
+                let quotient = ((self.0 as $wide) << $frac) / rhs.0 as $wide;
+                if quotient < $repr::MIN as $wide || quotient > $repr::MAX as $wide {
+                    None
+                } else {
+                    Some($name(quotient as $repr))
+                }
+            }
+
+            /// Multiply by an integer, saturating
+            pub fn saturating_mul_int(self, rhs: $repr) -> $name {
+                $name::saturate(self.0 as $wide * rhs as $wide)
+            }
+
+            /// Square root, rounded down. Negative values give zero.
+            pub fn sqrt(self) -> $name {
+                if self.0 <= 0 {
+                    return $name::ZERO;
+                }
+                $name(isqrt((self.0 as u64) << $frac) as $repr)
+            }
+
+            fn divide(num: $wide, den: $wide) -> $name {
+                if den == 0 {
+                    return if num < 0 {
+                        $name::MIN
+                    } else if num > 0 {
+                        $name::MAX
+                    } else {
+                        $name::ZERO
+                    };
+                }
+                $name::saturate(num / den)
+            }
+
+            fn saturate(value: $wide) -> $name {
+                if value > $repr::MAX as $wide {
+                    $name::MAX
+                } else if value < $repr::MIN as $wide {
+                    $name::MIN
+                } else {
+                    $name(value as $repr)
+                }
+            }
+        }
+
+        impl Add for $name {
+            type Output = $name;
+
+            fn add(self, rhs: $name) -> $name {
+                self.saturating_add(rhs)
+            }
+        }
+
+        impl Sub for $name {
+            type Output = $name;
+
+            fn sub(self, rhs: $name) -> $name {
+                self.saturating_sub(rhs)
+            }
+        }
+
+        impl Mul for $name {
+            type Output = $name;
+
+            fn mul(self, rhs: $name) -> $name {
+                self.saturating_mul(rhs)
+            }
+        }
+
+        impl Div for $name {
+            type Output = $name;
+
+            fn div(self, rhs: $name) -> $name {
+                self.saturating_div(rhs)
+            }
+        }
+
+        impl Neg for $name {
+            type Output = $name;
+
+            fn neg(self) -> $name {
+                $name(self.0.saturating_neg())
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, rhs: $name) {
+                *self = *self + rhs;
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, rhs: $name) {
+                *self = *self - rhs;
+            }
+        }
+
+        impl MulAssign for $name {
+            fn mul_assign(&mut self, rhs: $name) {
+                *self = *self * rhs;
+            }
+        }
+
+        impl DivAssign for $name {
+            fn div_assign(&mut self, rhs: $name) {
+                *self = *self / rhs;
+            }
+        }
+
+        impl uDisplay for $name {
+            fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+            where
+                W: uWrite + ?Sized,
+            {
+                crate::fmt::Fixed::new(self.0 as i32, $frac, $precision).fmt(f)
+            }
+        }
+    };
+}
+
+fixed!(
+    /// Signed 8.8 fixed-point number, printed with 2 decimals
+    Q8_8(i16, i32), 8, 2,
+    pi: 804, frac_pi_2: 402, tau: 1608
+);
+
+fixed!(
+    /// Signed 16.16 fixed-point number, printed with 4 decimals
+    Q16_16(i32, i64), 16, 4,
+    pi: 205_887, frac_pi_2: 102_944, tau: 411_775
+);
+
+impl From<Q8_8> for Q16_16 {
+    fn from(value: Q8_8) -> Q16_16 {
+        Q16_16((value.0 as i32) << 8)
+    }
+}
+
+impl Q16_16 {
+    /// Convert to `Q8_8`, rounded to nearest and saturating
+    pub fn to_q8_8(self) -> Q8_8 {
+        Q8_8::saturate(((self.0 as i64 + (1 << 7)) >> 8) as i32)
+    }
+
+    pub fn sin(self) -> Q16_16 {
+        Q16_16(sin_turn(self.to_turn()))
+    }
+
+    pub fn cos(self) -> Q16_16 {
+        Q16_16(sin_turn(self.to_turn().wrapping_add(1 << 14)))
+    }
+
+    /// Angle of the point `(x, self)` in radians, in `-PI..=PI`
+    pub fn atan2(self, x: Q16_16) -> Q16_16 {
+        Q16_16(atan2(self.0 as i64, x.0 as i64))
+    }
+
+    /// Angle as a fraction of a full turn, `1 << 16` is 2π
+    fn to_turn(self) -> u16 {
+        // 2^32 / 2π
+        const TURNS_PER_RADIAN: i64 = 683_565_276;
+        ((self.0 as i64 * TURNS_PER_RADIAN) >> 32) as u16
+    }
+}
+
+impl Q8_8 {
+    pub fn sin(self) -> Q8_8 {
+        Q16_16::from(self).sin().to_q8_8()
+    }
+
+    pub fn cos(self) -> Q8_8 {
+        Q16_16::from(self).cos().to_q8_8()
+    }
+
+    /// Angle of the point `(x, self)` in radians, in `-PI..=PI`
+    pub fn atan2(self, x: Q8_8) -> Q8_8 {
+        Q16_16(atan2(self.0 as i64, x.0 as i64)).to_q8_8()
+    }
+}
+
+/// Integer square root, rounded down
+fn isqrt(value: u64) -> u32 {
+    let mut rem = value;
+    let mut root = 0u64;
+    let mut bit = 1u64 << 62;
+    while bit > value {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if rem >= root + bit {
+            rem -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root as u32
+}
+
+/// `sin(i/64 * π/2)` in Q0.16, without the final 1.0
+#[cfg_attr(target_arch = "avr", link_section = ".progmem.data")]
+static SIN_TABLE: [u16; 64] = [
+    0, 1608, 3216, 4821, 6424, 8022, 9616, 11204, 12785, 14359, 15924, 17479, 19024, 20557, 22078,
+    23586, 25080, 26558, 28020, 29466, 30893, 32303, 33692, 35062, 36410, 37736, 39040, 40320,
+    41576, 42806, 44011, 45190, 46341, 47464, 48559, 49624, 50660, 51665, 52639, 53581, 54491,
+    55368, 56212, 57022, 57798, 58538, 59244, 59914, 60547, 61145, 61705, 62228, 62714, 63162,
+    63572, 63944, 64277, 64571, 64827, 65043, 65220, 65358, 65457, 65516,
+];
+
+/// `atan(i/32)` in radians, Q0.16
+#[cfg_attr(target_arch = "avr", link_section = ".progmem.data")]
+static ATAN_TABLE: [u16; 33] = [
+    0, 2047, 4091, 6126, 8150, 10158, 12147, 14114, 16055, 17968, 19850, 21699, 23512, 25289,
+    27028, 28727, 30386, 32003, 33580, 35115, 36608, 38060, 39472, 40842, 42172, 43464, 44716,
+    45931, 47109, 48251, 49359, 50432, 51472,
+];
+
+fn sin_table(index: usize) -> i32 {
+    if index >= SIN_TABLE.len() {
+        1 << 16
+    } else {
+        unsafe { progmem::read_u16(&SIN_TABLE[index]) as i32 }
+    }
+}
+
+/// Sine of a fraction of a full turn in Q16.16
+fn sin_turn(turn: u16) -> i32 {
+    let quadrant = turn >> 14;
+    let mut phase = (turn & 0x3FFF) as i32;
+    if quadrant & 1 != 0 {
+        phase = 0x4000 - phase;
+    }
+    let index = (phase >> 8) as usize;
+    let frac = phase & 0xFF;
+    let low = sin_table(index);
+    let value = if frac == 0 {
+        low
+    } else {
+        low + (((sin_table(index + 1) - low) * frac + 0x80) >> 8)
+    };
+    if quadrant >= 2 {
+        -value
+    } else {
+        value
+    }
+}
+
+/// `atan2` of raw values with any common scale, result in Q16.16 radians
+fn atan2(y: i64, x: i64) -> i32 {
+    if x == 0 && y == 0 {
+        return 0;
+    }
+    let (ax, ay) = (x.abs(), y.abs());
+    let (num, den) = if ay <= ax { (ay, ax) } else { (ax, ay) };
+    // Ratio in 0..=1 as Q0.16
+    let ratio = ((num << 16) / den) as i32;
+    let index = (ratio >> 11) as usize;
+    let frac = ratio & 0x7FF;
+    let low = unsafe { progmem::read_u16(&ATAN_TABLE[index]) as i32 };
+    let mut angle = if frac == 0 {
+        low
+    } else {
+        let high = unsafe { progmem::read_u16(&ATAN_TABLE[index + 1]) as i32 };
+        low + (((high - low) * frac + 0x400) >> 11)
+    };
+
+    if ay > ax {
+        angle = Q16_16::FRAC_PI_2.0 - angle;
+    }
+    if x < 0 {
+        angle = Q16_16::PI.0 - angle;
+    }
+    if y < 0 {
+        -angle
+    } else {
+        angle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn q16(value: f64) -> Q16_16 {
+        Q16_16::from_f32(value as f32)
+    }
+
+    fn f64_of(value: Q16_16) -> f64 {
+        value.to_bits() as f64 / 65536.0
+    }
+
+    /// Reference result saturated to the `Q16_16` range
+    fn saturated(value: f64) -> f64 {
+        let max = 32767.0 + 65535.0 / 65536.0;
+        if value > max {
+            max
+        } else if value < -32768.0 {
+            -32768.0
+        } else {
+            value
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} is not within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(Q8_8::ONE.to_bits(), 256);
+        assert_eq!(Q16_16::from_int(3).to_bits(), 3 << 16);
+        assert_eq!(Q8_8::from_int(200), Q8_8::MAX);
+        assert_eq!(Q8_8::from_int(-200), Q8_8::MIN);
+        assert_eq!(Q8_8::from_f32(1.5).to_bits(), 384);
+        assert_eq!(Q8_8::from_f32(-1.5).to_bits(), -384);
+        assert_eq!(Q8_8::from_f32(1e9), Q8_8::MAX);
+        assert_eq!(Q16_16::from_ratio(1, 3).to_bits(), 21845);
+        assert_eq!(Q8_8::from_ratio(1000, 5), Q8_8::MAX);
+        assert_eq!(Q8_8::from_ratio(1000, -80), Q8_8::from_f32(-12.5));
+        assert_eq!(Q8_8::from_ratio(-1, 0), Q8_8::MIN);
+        assert_eq!(Q16_16::from_f32(-2.25).to_int(), -3);
+        assert_eq!(Q16_16::from_f32(-2.25).round(), -2);
+        assert_eq!(Q16_16::from_f32(2.5).round(), 3);
+        assert_eq!(Q16_16::from(Q8_8::from_f32(-1.5)), Q16_16::from_f32(-1.5));
+        assert_eq!(Q16_16::from_int(1000).to_q8_8(), Q8_8::MAX);
+        assert_eq!(Q16_16::from_bits(0x0180).to_q8_8().to_bits(), 2);
+    }
+
+    #[test]
+    fn adc() {
+        let vref = Q16_16::from_int(5);
+        assert_eq!(Q16_16::from_adc(0, 10, vref), Q16_16::ZERO);
+        assert_eq!(Q16_16::from_adc(1024, 10, vref), vref);
+        assert_close(
+            f64_of(Q16_16::from_adc(1023, 10, vref)),
+            5.0 * 1023.0 / 1024.0,
+            1e-4,
+        );
+        assert_eq!(
+            Q8_8::from_adc(512, 10, Q8_8::from_int(5)),
+            Q8_8::from_f32(2.5)
+        );
+        assert_eq!(Q8_8::from_adc(1023, 10, Q8_8::MAX).to_int(), 127);
+    }
+
+    #[test]
+    fn arithmetic_matches_f64() {
+        let values = [
+            -300.0, -17.25, -1.0, -0.3, 0.0, 0.001, 0.5, 2.0, 3.75, 99.9, 181.0,
+        ];
+        for &a in &values {
+            for &b in &values {
+                let (qa, qb) = (q16(a), q16(b));
+                let (fa, fb) = (f64_of(qa), f64_of(qb));
+                assert_close(f64_of(qa + qb), saturated(fa + fb), 1e-9);
+                assert_close(f64_of(qa - qb), saturated(fa - fb), 1e-9);
+                assert_close(f64_of(qa * qb), saturated(fa * fb), 1.0 / 65536.0);
+                if fb != 0.0 {
+                    assert_close(f64_of(qa / qb), saturated(fa / fb), 1.0 / 65536.0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn saturation() {
+        let big = Q16_16::from_int(30000);
+        assert_eq!(big + big, Q16_16::MAX);
+        assert_eq!(-big - big, Q16_16::MIN);
+        assert_eq!(big * big, Q16_16::MAX);
+        assert_eq!(big * -big, Q16_16::MIN);
+        assert_eq!(big / Q16_16::DELTA, Q16_16::MAX);
+        assert_eq!(big / Q16_16::ZERO, Q16_16::MAX);
+        assert_eq!(-big / Q16_16::ZERO, Q16_16::MIN);
+        assert_eq!(Q16_16::ZERO / Q16_16::ZERO, Q16_16::ZERO);
+        assert_eq!(big.checked_div(Q16_16::ZERO), None);
+        assert_eq!(big.checked_div(Q16_16::DELTA), None);
+        assert_eq!(-Q16_16::MIN, Q16_16::MAX);
+        assert_eq!(Q16_16::MIN.abs(), Q16_16::MAX);
+        assert_eq!(Q8_8::from_int(100).saturating_mul_int(2), Q8_8::MAX);
+        assert_eq!(
+            Q8_8::from_int(-3).saturating_mul_int(4),
+            Q8_8::from_int(-12)
+        );
+    }
+
+    #[test]
+    fn sqrt() {
+        for &x in &[0.0, 0.0001, 0.25, 1.0, 2.0, 10.0, 1234.5, 32767.0] {
+            let q = q16(x);
+            assert_close(f64_of(q.sqrt()), f64_of(q).sqrt(), 1.0 / 65536.0);
+        }
+        assert_eq!(q16(-4.0).sqrt(), Q16_16::ZERO);
+        assert_eq!(Q8_8::from_int(4).sqrt(), Q8_8::from_int(2));
+        assert_eq!(Q8_8::MAX.sqrt().to_int(), 11);
+    }
+
+    #[test]
+    fn sin_cos() {
+        let mut angle = -10.0;
+        while angle < 10.0 {
+            let q = q16(angle);
+            let a = f64_of(q);
+            assert_close(f64_of(q.sin()), a.sin(), 2e-4);
+            assert_close(f64_of(q.cos()), a.cos(), 2e-4);
+            angle += 0.01;
+        }
+        assert_eq!(Q16_16::ZERO.sin(), Q16_16::ZERO);
+        assert_eq!(Q16_16::ZERO.cos(), Q16_16::ONE);
+        assert_eq!(Q16_16::FRAC_PI_2.sin(), Q16_16::ONE);
+        assert_close(f64_of(Q8_8::PI.cos().into()), -1.0, 1.0 / 256.0);
+    }
+
+    #[test]
+    fn atan2_all_quadrants() {
+        let mut angle = -PI + 0.005;
+        while angle < PI {
+            for &radius in &[0.01, 1.0, 250.0] {
+                let y = q16(radius * angle.sin());
+                let x = q16(radius * angle.cos());
+                let expected = f64_of(y).atan2(f64_of(x));
+                assert_close(f64_of(y.atan2(x)), expected, 2e-4);
+            }
+            angle += 0.01;
+        }
+        let one = Q16_16::ONE;
+        assert_eq!(Q16_16::ZERO.atan2(Q16_16::ZERO), Q16_16::ZERO);
+        assert_eq!(Q16_16::ZERO.atan2(-one), Q16_16::PI);
+        assert_eq!(one.atan2(Q16_16::ZERO), Q16_16::FRAC_PI_2);
+        assert_eq!((-one).atan2(Q16_16::ZERO), -Q16_16::FRAC_PI_2);
+        assert_close(
+            f64_of(Q8_8::ONE.atan2(Q8_8::ONE).into()),
+            PI / 4.0,
+            1.0 / 256.0,
+        );
+    }
+}
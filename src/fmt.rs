@@ -0,0 +1,449 @@
+//! `ufmt` display helpers for numbers.
+//!
+//! `ufmt` only prints integers in decimal. The wrappers here add floats with
+//! a fixed number of decimals ([`Float`]), binary fixed-point values
+//! ([`Fixed`]), hexadecimal and binary dumps ([`Hex`], [`Bin`]) and padding
+//! to a minimum width ([`Pad`]):
+//!
+//! ```no_run
+//! use m48_robo_rust::fmt::{Bin, Fixed, Float, Hex, Pad};
+//! # let mut serial: m48_robo_rust::Serial<m48_robo_rust::hal::port::mode::Floating> = unimplemented!();
+//!
+//! // "-1.05 1.50 0x2A 0b00101010 [  7] 007"
+//! ufmt::uwriteln!(
+//!     &mut serial,
+//!     "{} {} 0x{} 0b{} [{}] {}",
+//!     Float::new(-1.05, 2),
+//!     Fixed::new(0x0180, 8, 2),
+//!     Hex(42u8),
+//!     Bin(42u8),
+//!     Pad::space(7u8, 3),
+//!     Pad::zero(7u8, 3),
+//! )
+//! .ok();
+//! ```
+//!
+//! [`Float`]: struct.Float.html
+//! [`Fixed`]: struct.Fixed.html
+//! [`Hex`]: struct.Hex.html
+//! [`Bin`]: struct.Bin.html
+//! [`Pad`]: struct.Pad.html
+
+use core::convert::Infallible;
+
+use ufmt::{uDisplay, uWrite, uwrite, Formatter};
+
+/// Powers of ten fitting into a `u32`
+const POW10: [u32; 10] = [
+    1,
+    10,
+    100,
+    1_000,
+    10_000,
+    100_000,
+    1_000_000,
+    10_000_000,
+    100_000_000,
+    1_000_000_000,
+];
+
+/// Most decimals printed by `Float` and `Fixed`
+pub const MAX_PRECISION: u8 = 9;
+
+/// Write `value` as exactly `digits` decimal digits, with leading zeros
+fn write_digits<W>(f: &mut Formatter<'_, W>, value: u32, digits: u8) -> Result<(), W::Error>
+where
+    W: uWrite + ?Sized,
+{
+    let mut buf = [b'0'; 10];
+    let mut value = value;
+    let digits = (digits as usize).min(buf.len());
+    for digit in buf[..digits].iter_mut().rev() {
+        *digit = b'0' + (value % 10) as u8;
+        value /= 10;
+    }
+    // Only ASCII digits in the buffer
+    f.write_str(unsafe { core::str::from_utf8_unchecked(&buf[..digits]) })
+}
+
+/// `f32` with a fixed number of decimals, rounded to nearest.
+///
+/// Values too large for a `u32` after scaling are printed in scientific
+/// notation, e.g. `1.50e12`.
+#[derive(Clone, Copy, Debug)]
+pub struct Float {
+    value: f32,
+    precision: u8,
+}
+
+impl Float {
+    /// Print `value` with `precision` decimals, at most `MAX_PRECISION`
+    pub fn new(value: f32, precision: u8) -> Float {
+        Float {
+            value,
+            precision: precision.min(MAX_PRECISION),
+        }
+    }
+}
+
+impl uDisplay for Float {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: uWrite + ?Sized,
+    {
+        let value = self.value;
+        if value.is_nan() {
+            return f.write_str("nan");
+        }
+        if value.is_infinite() {
+            return f.write_str(if value < 0.0 { "-inf" } else { "inf" });
+        }
+
+        let precision = self.precision;
+        let scale = POW10[precision as usize];
+        let abs = if value < 0.0 { -value } else { value };
+
+        let scaled = abs * scale as f32 + 0.5;
+        if scaled < u32::MAX as f32 {
+            let scaled = scaled as u32;
+            if value < 0.0 && scaled != 0 {
+                f.write_char('-')?;
+            }
+            return write_fraction(f, scaled / scale, scaled % scale, precision);
+        }
+
+        // Scientific notation, the mantissa is brought into 1..10. Ten times
+        // the scale of `MAX_PRECISION` needs more than 32 bits.
+        let scale = scale as u64;
+        let mut mantissa = abs;
+        let mut exponent = 0u8;
+        while mantissa >= 10.0 {
+            mantissa /= 10.0;
+            exponent += 1;
+        }
+        let mut scaled = (mantissa * scale as f32 + 0.5) as u64;
+        if scaled >= 10 * scale {
+            scaled /= 10;
+            exponent += 1;
+        }
+        if value < 0.0 {
+            f.write_char('-')?;
+        }
+        let (int, frac) = (scaled / scale, scaled % scale);
+        write_fraction(f, int as u32, frac as u32, precision)?;
+        f.write_char('e')?;
+        exponent.fmt(f)
+    }
+}
+
+fn write_fraction<W>(
+    f: &mut Formatter<'_, W>,
+    int: u32,
+    frac: u32,
+    precision: u8,
+) -> Result<(), W::Error>
+where
+    W: uWrite + ?Sized,
+{
+    int.fmt(f)?;
+    if precision > 0 {
+        f.write_char('.')?;
+        write_digits(f, frac, precision)?;
+    }
+    Ok(())
+}
+
+/// Binary fixed-point number, `raw / 2^frac_bits`, printed with a fixed
+/// number of decimals rounded to nearest.
+#[derive(Clone, Copy, Debug)]
+pub struct Fixed {
+    raw: i32,
+    frac_bits: u8,
+    precision: u8,
+}
+
+impl Fixed {
+    /// Print `raw` with `frac_bits` fractional bits (at most 31), e.g. 8 for
+    /// Q8.8, with `precision` decimals (at most `MAX_PRECISION`)
+    pub fn new(raw: i32, frac_bits: u8, precision: u8) -> Fixed {
+        Fixed {
+            raw,
+            frac_bits: frac_bits.min(31),
+            precision: precision.min(MAX_PRECISION),
+        }
+    }
+}
+
+impl uDisplay for Fixed {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: uWrite + ?Sized,
+    {
+        // Also correct for i32::MIN, whose absolute value is 2^31
+        let abs = self.raw.wrapping_abs() as u32;
+        let mut int = abs >> self.frac_bits;
+        let frac = abs & ((1u32 << self.frac_bits) - 1);
+
+        let scale = POW10[self.precision as usize];
+        let half = (1u64 << self.frac_bits) >> 1;
+        let mut frac = ((frac as u64 * scale as u64 + half) >> self.frac_bits) as u32;
+        if frac >= scale {
+            frac -= scale;
+            int += 1;
+        }
+
+        if self.raw < 0 && (int != 0 || frac != 0) {
+            f.write_char('-')?;
+        }
+        write_fraction(f, int, frac, self.precision)
+    }
+}
+
+/// Upper case hexadecimal, zero padded to the width of the type.
+///
+/// Byte slices are dumped as space separated bytes.
+#[derive(Clone, Copy, Debug)]
+pub struct Hex<T>(pub T);
+
+/// Binary, zero padded to the width of the type
+#[derive(Clone, Copy, Debug)]
+pub struct Bin<T>(pub T);
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+fn write_radix<W>(f: &mut Formatter<'_, W>, value: u32, bits: u8, shift: u8) -> Result<(), W::Error>
+where
+    W: uWrite + ?Sized,
+{
+    let mask = (1u32 << shift) - 1;
+    let mut buf = [b'0'; 32];
+    let digits = (bits / shift) as usize;
+    let mut value = value;
+    for digit in buf[..digits].iter_mut().rev() {
+        *digit = HEX_DIGITS[(value & mask) as usize];
+        value >>= shift;
+    }
+    // Only ASCII digits in the buffer
+    f.write_str(unsafe { core::str::from_utf8_unchecked(&buf[..digits]) })
+}
+
+macro_rules! radix {
+    ($($t:ty),*) => {
+        $(
+            impl uDisplay for Hex<$t> {
+                fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+                where
+                    W: uWrite + ?Sized,
+                {
+                    write_radix(f, self.0 as u32, 8 * core::mem::size_of::<$t>() as u8, 4)
+                }
+            }
+
+            impl uDisplay for Bin<$t> {
+                fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+                where
+                    W: uWrite + ?Sized,
+                {
+                    write_radix(f, self.0 as u32, 8 * core::mem::size_of::<$t>() as u8, 1)
+                }
+            }
+        )*
+    };
+}
+
+radix!(u8, u16, u32);
+
+impl uDisplay for Hex<&[u8]> {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: uWrite + ?Sized,
+    {
+        for (i, byte) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_char(' ')?;
+            }
+            write_radix(f, *byte as u32, 8, 4)?;
+        }
+        Ok(())
+    }
+}
+
+/// Right aligned value, padded to at least `width` characters.
+///
+/// Zero padding goes after a leading minus sign. Values longer than 16
+/// characters are printed without padding.
+#[derive(Clone, Copy, Debug)]
+pub struct Pad<T> {
+    value: T,
+    width: u8,
+    fill: u8,
+}
+
+impl<T: uDisplay> Pad<T> {
+    /// Pad with spaces
+    pub fn space(value: T, width: u8) -> Pad<T> {
+        Pad {
+            value,
+            width,
+            fill: b' ',
+        }
+    }
+
+    /// Pad with zeros
+    pub fn zero(value: T, width: u8) -> Pad<T> {
+        Pad {
+            value,
+            width,
+            fill: b'0',
+        }
+    }
+}
+
+impl<T: uDisplay> uDisplay for Pad<T> {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: uWrite + ?Sized,
+    {
+        let mut buf = Buffer::new();
+        uwrite!(&mut buf, "{}", self.value).ok();
+        if buf.overflow {
+            return self.value.fmt(f);
+        }
+
+        let mut text = buf.as_bytes();
+        if self.fill == b'0' && text.first() == Some(&b'-') {
+            f.write_char('-')?;
+            text = &text[1..];
+        }
+        let len = buf.len as u8;
+        for _ in len..self.width {
+            f.write_char(self.fill as char)?;
+        }
+        // Only complete strings were written to the buffer
+        f.write_str(unsafe { core::str::from_utf8_unchecked(text) })
+    }
+}
+
+/// Small stack buffer to measure formatted output
+struct Buffer {
+    buf: [u8; 16],
+    len: usize,
+    overflow: bool,
+}
+
+impl Buffer {
+    fn new() -> Buffer {
+        Buffer {
+            buf: [0; 16],
+            len: 0,
+            overflow: false,
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+impl uWrite for Buffer {
+    type Error = Infallible;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Infallible> {
+        let end = self.len + s.len();
+        if end > self.buf.len() {
+            self.overflow = true;
+        } else {
+            self.buf[self.len..end].copy_from_slice(s.as_bytes());
+            self.len = end;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::String;
+
+    struct Out(String);
+
+    impl uWrite for Out {
+        type Error = Infallible;
+
+        fn write_str(&mut self, s: &str) -> Result<(), Infallible> {
+            self.0.push_str(s);
+            Ok(())
+        }
+    }
+
+    fn show<T: uDisplay>(value: T) -> String {
+        let mut out = Out(String::new());
+        uwrite!(&mut out, "{}", value).unwrap();
+        out.0
+    }
+
+    #[test]
+    fn float() {
+        assert_eq!(show(Float::new(1.05, 2)), "1.05");
+        assert_eq!(show(Float::new(-1.05, 2)), "-1.05");
+        assert_eq!(show(Float::new(-0.5, 1)), "-0.5");
+        assert_eq!(show(Float::new(2.999, 2)), "3.00");
+        assert_eq!(show(Float::new(-0.001, 2)), "0.00");
+        assert_eq!(show(Float::new(9.75, 0)), "10");
+        assert_eq!(show(Float::new(11.75, 3)), "11.750");
+        assert_eq!(show(Float::new(0.0, 4)), "0.0000");
+    }
+
+    #[test]
+    fn float_special() {
+        assert_eq!(show(Float::new(f32::NAN, 2)), "nan");
+        assert_eq!(show(Float::new(f32::INFINITY, 2)), "inf");
+        assert_eq!(show(Float::new(f32::NEG_INFINITY, 2)), "-inf");
+        assert_eq!(show(Float::new(1.5e12, 2)), "1.50e12");
+    }
+
+    #[test]
+    fn float_max_precision() {
+        // Ten times the scale does not fit a u32, digits past the seventh
+        // are the rounding of f32
+        assert_eq!(show(Float::new(5.0, MAX_PRECISION)), "5.000000000e0");
+        assert_eq!(show(Float::new(9.9999, MAX_PRECISION)), "9.999899648e0");
+        assert_eq!(show(Float::new(1e20, MAX_PRECISION)), "1.000000256e20");
+        assert_eq!(show(Float::new(-9.999e20, 1)), "-1.0e21");
+    }
+
+    #[test]
+    fn fixed() {
+        assert_eq!(show(Fixed::new(0x0180, 8, 2)), "1.50");
+        assert_eq!(show(Fixed::new(-0x0180, 8, 2)), "-1.50");
+        assert_eq!(show(Fixed::new(1, 8, 3)), "0.004");
+        assert_eq!(show(Fixed::new(-1, 8, 2)), "0.00");
+        assert_eq!(show(Fixed::new(0x00FF, 8, 1)), "1.0");
+        assert_eq!(show(Fixed::new(0x0003_2000, 16, 3)), "3.125");
+        assert_eq!(show(Fixed::new(i32::MIN, 16, 1)), "-32768.0");
+        assert_eq!(show(Fixed::new(i32::MAX, 16, 4)), "32768.0000");
+        assert_eq!(show(Fixed::new(42, 0, 2)), "42.00");
+    }
+
+    #[test]
+    fn radix() {
+        assert_eq!(show(Hex(0x2Au8)), "2A");
+        assert_eq!(show(Hex(0x0BEEFu16)), "BEEF");
+        assert_eq!(show(Hex(0x12u32)), "00000012");
+        assert_eq!(show(Bin(5u8)), "00000101");
+        assert_eq!(show(Bin(0x8001u16)), "1000000000000001");
+        assert_eq!(show(Hex(&[0x01u8, 0xAB, 0xFF][..])), "01 AB FF");
+        assert_eq!(show(Hex(&[0u8; 0][..])), "");
+    }
+
+    #[test]
+    fn pad() {
+        assert_eq!(show(Pad::space(7u8, 3)), "  7");
+        assert_eq!(show(Pad::zero(7u8, 3)), "007");
+        assert_eq!(show(Pad::zero(-7i16, 4)), "-007");
+        assert_eq!(show(Pad::space(-7i16, 4)), "  -7");
+        assert_eq!(show(Pad::zero(12345u16, 3)), "12345");
+        assert_eq!(show(Pad::space(Float::new(1.5, 1), 5)), "  1.5");
+        assert_eq!(show(Pad::space(Hex(&[0u8; 8][..]), 2)).len(), 23);
+    }
+}
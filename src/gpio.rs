@@ -0,0 +1,479 @@
+//! Typestate GPIO pins built on the `dev::gpio` registers.
+//!
+//! Every pin is its own zero-sized type, parameterised by its mode, so mode
+//! changes and pin accesses compile down to single `sbi`/`cbi`/`sbis`
+//! instructions. Pins implement the `embedded_hal::digital::v2` traits and can
+//! be handed to drivers written against `embedded-hal`.
+//!
+//! # Example
+//! ```no_run
+//! let mut pins = m48_robo_rust::gpio::Pins::take().unwrap();
+//!
+//! let mut led = pins.portb.pb0.into_output();
+//! let button = pins.portd.pd2.into_pull_up_input();
+//!
+//! loop {
+//!     if button.is_low() {
+//!         led.toggle();
+//!     }
+//! }
+//! ```
+//!
+//! Pins of different ports can be stored together after [`downgrade`]-ing
+//! them into the type-erased [`Pin`].
+//!
+//! [`downgrade`]: portb/struct.PB0.html#method.downgrade
+//! [`Pin`]: struct.Pin.html
+
+use core::{convert::Infallible, marker::PhantomData, sync::atomic};
+
+use embedded_hal::digital::v2::{InputPin, OutputPin, StatefulOutputPin, ToggleableOutputPin};
+
+use crate::{
+    dev::gpio::{GPIOB, GPIOC, GPIOD},
+    interrupt,
+    mmio::{
+        registers::{Aliased, ReadWrite},
+        static_ref::StaticRef,
+    },
+};
+
+/// Pin modes.
+pub mod mode {
+    use core::marker::PhantomData;
+
+    /// Input mode (type state), `MODE` selects the pull-up.
+    pub struct Input<MODE> {
+        _mode: PhantomData<MODE>,
+    }
+
+    /// High impedance input.
+    pub struct Floating;
+
+    /// Input with the internal pull-up enabled.
+    pub struct PullUp;
+
+    /// Push-pull output.
+    pub struct Output;
+}
+
+use mode::{Input, Output};
+
+/// GPIO port of a type-erased pin.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Port {
+    B,
+    C,
+    D,
+}
+
+register_structs! {
+    /// Register block shared by all ports, without per-port bit field names
+    GpioRegisters {
+        (0x00 => pin: Aliased<u8>),
+        (0x01 => ddr: ReadWrite<u8>),
+        (0x02 => port: ReadWrite<u8>),
+        (0x03 => @END),
+    }
+}
+
+const PORTB_REGS: StaticRef<GpioRegisters> = unsafe { StaticRef::new(GPIOB.to_ptr()) };
+const PORTC_REGS: StaticRef<GpioRegisters> = unsafe { StaticRef::new(GPIOC.to_ptr()) };
+const PORTD_REGS: StaticRef<GpioRegisters> = unsafe { StaticRef::new(GPIOD.to_ptr()) };
+
+impl Port {
+    #[inline(always)]
+    fn regs(self) -> StaticRef<GpioRegisters> {
+        match self {
+            Port::B => PORTB_REGS,
+            Port::C => PORTC_REGS,
+            Port::D => PORTD_REGS,
+        }
+    }
+
+    /// Drive the `mask` pins of the port high, needs interrupts masked
+    #[inline(always)]
+    #[cfg_attr(not(feature = "servo-multi"), allow(dead_code))]
+    pub(crate) fn set_high(self, mask: u8) {
+        let port = &self.regs().port;
+        port.set(port.get() | mask);
+    }
+
+    /// Drive the `mask` pins of the port low, needs interrupts masked
+    #[inline(always)]
+    #[cfg_attr(not(feature = "servo-multi"), allow(dead_code))]
+    pub(crate) fn set_low(self, mask: u8) {
+        let port = &self.regs().port;
+        port.set(port.get() & !mask);
+    }
+}
+
+/// Type-erased pin of any port, produced by `downgrade()`.
+///
+/// Accesses need a few more instructions than the typed pins as the port and
+/// bit are only known at runtime, writes are done with interrupts masked.
+pub struct Pin<MODE> {
+    port: Port,
+    mask: u8,
+    _mode: PhantomData<MODE>,
+}
+
+impl<MODE> Pin<MODE> {
+    /// Port of the pin
+    pub fn port(&self) -> Port {
+        self.port
+    }
+
+    /// Bit mask of the pin inside its port registers
+    pub fn mask(&self) -> u8 {
+        self.mask
+    }
+}
+
+impl Pin<Output> {
+    /// Drive the pin high
+    #[inline]
+    pub fn set_high(&mut self) {
+        let port = &self.port.regs().port;
+        interrupt::free(|_| port.set(port.get() | self.mask));
+    }
+
+    /// Drive the pin low
+    #[inline]
+    pub fn set_low(&mut self) {
+        let port = &self.port.regs().port;
+        interrupt::free(|_| port.set(port.get() & !self.mask));
+    }
+
+    /// Check if the pin is driven high
+    #[inline]
+    pub fn is_set_high(&self) -> bool {
+        self.port.regs().port.get() & self.mask != 0
+    }
+
+    /// Check if the pin is driven low
+    #[inline]
+    pub fn is_set_low(&self) -> bool {
+        !self.is_set_high()
+    }
+
+    /// Toggle the pin by writing its `PINx` bit, which needs no read-modify-write
+    #[inline]
+    pub fn toggle(&mut self) {
+        self.port.regs().pin.set(self.mask);
+    }
+}
+
+impl<MODE> Pin<Input<MODE>> {
+    /// Check if the pin level is high
+    #[inline]
+    pub fn is_high(&self) -> bool {
+        self.port.regs().pin.get() & self.mask != 0
+    }
+
+    /// Check if the pin level is low
+    #[inline]
+    pub fn is_low(&self) -> bool {
+        !self.is_high()
+    }
+}
+
+impl OutputPin for Pin<Output> {
+    type Error = Infallible;
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        Pin::set_high(self);
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        Pin::set_low(self);
+        Ok(())
+    }
+}
+
+impl StatefulOutputPin for Pin<Output> {
+    fn is_set_high(&self) -> Result<bool, Self::Error> {
+        Ok(Pin::is_set_high(self))
+    }
+
+    fn is_set_low(&self) -> Result<bool, Self::Error> {
+        Ok(Pin::is_set_low(self))
+    }
+}
+
+impl ToggleableOutputPin for Pin<Output> {
+    type Error = Infallible;
+
+    fn toggle(&mut self) -> Result<(), Self::Error> {
+        Pin::toggle(self);
+        Ok(())
+    }
+}
+
+impl<MODE> InputPin for Pin<Input<MODE>> {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok(Pin::is_high(self))
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(Pin::is_low(self))
+    }
+}
+
+macro_rules! port {
+    (
+        $(#[$attr:meta])*
+        $portx:ident, $Port:ident, $GPIOX:ident, $pinx:ident, $ddrx:ident, $portr:ident,
+        $PINX:ident, $DDRX:ident, $PORTX:ident,
+        [$($PXi:ident: ($pxi:ident, $i:expr, $PINXi:ident, $DDXi:ident, $PORTXi:ident),)+]
+    ) => {
+        $(#[$attr])*
+        pub mod $portx {
+            use core::{convert::Infallible, marker::PhantomData};
+
+            use embedded_hal::digital::v2::{
+                InputPin, OutputPin, StatefulOutputPin, ToggleableOutputPin,
+            };
+
+            use super::{
+                mode::{Floating, Input, Output, PullUp},
+                Pin, Port,
+            };
+            use crate::dev::gpio::{$GPIOX, $DDRX, $PINX, $PORTX};
+
+            /// Pins of the port, all in their reset state (floating input).
+            pub struct Parts {
+                $(pub $pxi: $PXi<Input<Floating>>,)+
+            }
+
+            impl Parts {
+                /// Create the pins without checking that they were not taken before.
+                ///
+                /// ## Safety
+                ///
+                /// Pins must not be duplicated, prefer `gpio::Pins::take()`.
+                pub unsafe fn steal() -> Self {
+                    Parts {
+                        $($pxi: $PXi { _mode: PhantomData },)+
+                    }
+                }
+            }
+
+            $(
+                /// Pin of the port, `MODE` is its current configuration.
+                pub struct $PXi<MODE> {
+                    _mode: PhantomData<MODE>,
+                }
+
+                impl<MODE> $PXi<MODE> {
+                    /// Configure the pin as push-pull output
+                    #[inline(always)]
+                    pub fn into_output(self) -> $PXi<Output> {
+                        $GPIOX.$ddrx.sbi($DDRX::$DDXi);
+                        $PXi { _mode: PhantomData }
+                    }
+
+                    /// Configure the pin as high impedance input
+                    #[inline(always)]
+                    pub fn into_floating_input(self) -> $PXi<Input<Floating>> {
+                        $GPIOX.$ddrx.cbi($DDRX::$DDXi);
+                        $GPIOX.$portr.cbi($PORTX::$PORTXi);
+                        $PXi { _mode: PhantomData }
+                    }
+
+                    /// Configure the pin as input with the internal pull-up enabled
+                    #[inline(always)]
+                    pub fn into_pull_up_input(self) -> $PXi<Input<PullUp>> {
+                        $GPIOX.$ddrx.cbi($DDRX::$DDXi);
+                        $GPIOX.$portr.sbi($PORTX::$PORTXi);
+                        $PXi { _mode: PhantomData }
+                    }
+
+                    /// Erase the pin number from the type
+                    #[inline(always)]
+                    pub fn downgrade(self) -> Pin<MODE> {
+                        Pin {
+                            port: Port::$Port,
+                            mask: 1 << $i,
+                            _mode: PhantomData,
+                        }
+                    }
+                }
+
+                impl $PXi<Output> {
+                    /// Drive the pin high
+                    #[inline(always)]
+                    pub fn set_high(&mut self) {
+                        $GPIOX.$portr.sbi($PORTX::$PORTXi);
+                    }
+
+                    /// Drive the pin low
+                    #[inline(always)]
+                    pub fn set_low(&mut self) {
+                        $GPIOX.$portr.cbi($PORTX::$PORTXi);
+                    }
+
+                    /// Check if the pin is driven high
+                    #[inline(always)]
+                    pub fn is_set_high(&self) -> bool {
+                        $GPIOX.$portr.is_set($PORTX::$PORTXi)
+                    }
+
+                    /// Check if the pin is driven low
+                    #[inline(always)]
+                    pub fn is_set_low(&self) -> bool {
+                        !self.is_set_high()
+                    }
+
+                    /// Toggle the pin by writing its `PINx` bit, which needs no
+                    /// read-modify-write
+                    #[inline(always)]
+                    pub fn toggle(&mut self) {
+                        $GPIOX.$pinx.write($PINX::$PINXi::SET);
+                    }
+                }
+
+                impl<MODE> $PXi<Input<MODE>> {
+                    /// Check if the pin level is high
+                    #[inline(always)]
+                    pub fn is_high(&self) -> bool {
+                        $GPIOX.$pinx.is_set($PINX::$PINXi)
+                    }
+
+                    /// Check if the pin level is low
+                    #[inline(always)]
+                    pub fn is_low(&self) -> bool {
+                        !self.is_high()
+                    }
+                }
+
+                impl OutputPin for $PXi<Output> {
+                    type Error = Infallible;
+
+                    fn set_high(&mut self) -> Result<(), Self::Error> {
+                        $PXi::set_high(self);
+                        Ok(())
+                    }
+
+                    fn set_low(&mut self) -> Result<(), Self::Error> {
+                        $PXi::set_low(self);
+                        Ok(())
+                    }
+                }
+
+                impl StatefulOutputPin for $PXi<Output> {
+                    fn is_set_high(&self) -> Result<bool, Self::Error> {
+                        Ok($PXi::is_set_high(self))
+                    }
+
+                    fn is_set_low(&self) -> Result<bool, Self::Error> {
+                        Ok($PXi::is_set_low(self))
+                    }
+                }
+
+                impl ToggleableOutputPin for $PXi<Output> {
+                    type Error = Infallible;
+
+                    fn toggle(&mut self) -> Result<(), Self::Error> {
+                        $PXi::toggle(self);
+                        Ok(())
+                    }
+                }
+
+                impl<MODE> InputPin for $PXi<Input<MODE>> {
+                    type Error = Infallible;
+
+                    fn is_high(&self) -> Result<bool, Self::Error> {
+                        Ok($PXi::is_high(self))
+                    }
+
+                    fn is_low(&self) -> Result<bool, Self::Error> {
+                        Ok($PXi::is_low(self))
+                    }
+                }
+            )+
+        }
+    };
+}
+
+port!(
+    /// Port B pins.
+    portb, B, GPIOB, pinb, ddrb, portb, PINB, DDRB, PORTB,
+    [
+        PB0: (pb0, 0, PINB0, DDB0, PORTB0),
+        PB1: (pb1, 1, PINB1, DDB1, PORTB1),
+        PB2: (pb2, 2, PINB2, DDB2, PORTB2),
+        PB3: (pb3, 3, PINB3, DDB3, PORTB3),
+        PB4: (pb4, 4, PINB4, DDB4, PORTB4),
+        PB5: (pb5, 5, PINB5, DDB5, PORTB5),
+        PB6: (pb6, 6, PINB6, DDB6, PORTB6),
+        PB7: (pb7, 7, PINB7, DDB7, PORTB7),
+    ]
+);
+
+port!(
+    /// Port C pins, `PC6` is left out as it is the reset pin.
+    portc, C, GPIOC, pinc, ddrc, portc, PINC, DDRC, PORTC,
+    [
+        PC0: (pc0, 0, PINC0, DDC0, PORTC0),
+        PC1: (pc1, 1, PINC1, DDC1, PORTC1),
+        PC2: (pc2, 2, PINC2, DDC2, PORTC2),
+        PC3: (pc3, 3, PINC3, DDC3, PORTC3),
+        PC4: (pc4, 4, PINC4, DDC4, PORTC4),
+        PC5: (pc5, 5, PINC5, DDC5, PORTC5),
+    ]
+);
+
+port!(
+    /// Port D pins.
+    portd, D, GPIOD, pind, ddrd, portd, PIND, DDRD, PORTD,
+    [
+        PD0: (pd0, 0, PIND0, DDD0, PORTD0),
+        PD1: (pd1, 1, PIND1, DDD1, PORTD1),
+        PD2: (pd2, 2, PIND2, DDD2, PORTD2),
+        PD3: (pd3, 3, PIND3, DDD3, PORTD3),
+        PD4: (pd4, 4, PIND4, DDD4, PORTD4),
+        PD5: (pd5, 5, PIND5, DDD5, PORTD5),
+        PD6: (pd6, 6, PIND6, DDD6, PORTD6),
+        PD7: (pd7, 7, PIND7, DDD7, PORTD7),
+    ]
+);
+
+/// All pins of the MCU.
+pub struct Pins {
+    pub portb: portb::Parts,
+    pub portc: portc::Parts,
+    pub portd: portd::Parts,
+}
+
+static TAKEN: atomic::AtomicBool = atomic::AtomicBool::new(false);
+
+impl Pins {
+    /// Take the pins, returns `None` when they were already taken.
+    pub fn take() -> Option<Self> {
+        interrupt::free(|_| {
+            if TAKEN.load(atomic::Ordering::Relaxed) {
+                None
+            } else {
+                TAKEN.store(true, atomic::Ordering::Relaxed);
+                Some(unsafe { Self::steal() })
+            }
+        })
+    }
+
+    /// Create the pins without checking that they were not taken before.
+    ///
+    /// ## Safety
+    ///
+    /// Pins must not be duplicated.
+    pub unsafe fn steal() -> Self {
+        Pins {
+            portb: portb::Parts::steal(),
+            portc: portc::Parts::steal(),
+            portd: portd::Parts::steal(),
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! Interrupt masking usable from both the target and the host builds.
+
+use core::cell::{Cell, UnsafeCell};
+
+pub use bare_metal::{CriticalSection, Mutex};
+
+/// Execute closure `f` in an interrupt-free context.
+///
+/// On the MCU this is [`avr_device::interrupt::free`], which restores the
+/// previous state of the global interrupt flag afterwards. Host builds have no
+/// interrupts to mask, there a global reentrant lock serializes the critical
+/// sections of all threads (such as parallel tests) instead.
+///
+/// [`avr_device::interrupt::free`]: https://docs.rs/avr-device/latest/avr_device/interrupt/fn.free.html
+#[inline(always)]
+pub fn free<F, R>(f: F) -> R
+where
+    F: FnOnce(&CriticalSection) -> R,
+{
+    #[cfg(target_arch = "avr")]
+    {
+        avr_device::interrupt::free(f)
+    }
+
+    #[cfg(not(target_arch = "avr"))]
+    {
+        host::free(f)
+    }
+}
+
+#[cfg(not(target_arch = "avr"))]
+mod host {
+    extern crate std;
+
+    use core::{
+        cell::Cell,
+        sync::atomic::{AtomicBool, Ordering},
+    };
+
+    use super::CriticalSection;
+
+    static LOCKED: AtomicBool = AtomicBool::new(false);
+
+    std::thread_local! {
+        // Critical sections entered by the current thread, only the outermost
+        // one takes and releases `LOCKED`
+        static DEPTH: Cell<usize> = Cell::new(0);
+    }
+
+    /// Leaves the critical section, also when the closure panics
+    struct Section<'a>(&'a Cell<usize>);
+
+    impl Drop for Section<'_> {
+        fn drop(&mut self) {
+            let depth = self.0.get() - 1;
+            self.0.set(depth);
+            if depth == 0 {
+                LOCKED.store(false, Ordering::Release);
+            }
+        }
+    }
+
+    pub(super) fn free<F, R>(f: F) -> R
+    where
+        F: FnOnce(&CriticalSection) -> R,
+    {
+        DEPTH.with(|depth| {
+            if depth.get() == 0 {
+                while LOCKED
+                    .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+                    .is_err()
+                {
+                    std::thread::yield_now();
+                }
+            }
+            depth.set(depth.get() + 1);
+            let _section = Section(depth);
+            // Safe as no other thread is inside a critical section until
+            // `_section` is dropped
+            let cs = unsafe { CriticalSection::new() };
+            f(&cs)
+        })
+    }
+}
+
+/// Resource shared between the main program and interrupt handlers.
+///
+/// This replaces `static mut X: Option<T>` globals: the value is moved in once
+/// with [`init`] and only handed out inside a critical section by [`lock`].
+/// Accessing an uninitialized `Shared` or locking it again from within its own
+/// `lock` closure returns `None` instead of aliasing or panicking.
+///
+/// ```no_run
+/// use m48_robo_rust::{interrupt::Shared, prelude::*};
+///
+/// static SERIAL: Shared<m48_robo_rust::Serial<m48_robo_rust::hal::port::mode::Floating>> =
+///     Shared::new();
+///
+/// // in main
+/// # let serial = unimplemented!();
+/// SERIAL.init(serial).ok();
+///
+/// // in the handler
+/// SERIAL.lock(|_cs, serial| serial.write(b'!'));
+/// ```
+///
+/// [`init`]: #method.init
+/// [`lock`]: #method.lock
+pub struct Shared<T> {
+    inner: Mutex<Inner<T>>,
+}
+
+struct Inner<T> {
+    locked: Cell<bool>,
+    value: UnsafeCell<Option<T>>,
+}
+
+impl<T> Shared<T> {
+    /// Create an empty `Shared`, usable as a `static` initializer
+    pub const fn new() -> Shared<T> {
+        Shared {
+            inner: Mutex::new(Inner {
+                locked: Cell::new(false),
+                value: UnsafeCell::new(None),
+            }),
+        }
+    }
+
+    /// Move `value` in, gives it back if the `Shared` was already initialized
+    /// or is currently locked
+    pub fn init(&self, value: T) -> Result<(), T> {
+        free(|cs| {
+            let inner = self.inner.borrow(cs);
+            // Safe as the slot is not borrowed while unlocked
+            let slot = unsafe { &mut *inner.value.get() };
+            if inner.locked.get() || slot.is_some() {
+                return Err(value);
+            }
+            *slot = Some(value);
+            Ok(())
+        })
+    }
+
+    /// Move the value out again, `None` if empty or currently locked
+    pub fn take(&self) -> Option<T> {
+        free(|cs| {
+            let inner = self.inner.borrow(cs);
+            if inner.locked.get() {
+                return None;
+            }
+            unsafe { &mut *inner.value.get() }.take()
+        })
+    }
+
+    /// Whether `init` was called and the value was not taken since
+    pub fn is_initialized(&self) -> bool {
+        free(|cs| unsafe { &*self.inner.borrow(cs).value.get() }.is_some())
+    }
+
+    /// Run `f` on the value with interrupts masked.
+    ///
+    /// Returns `None` without calling `f` if the `Shared` is empty or `lock`
+    /// was re-entered from `f`.
+    #[inline]
+    pub fn lock<F, R>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&CriticalSection, &mut T) -> R,
+    {
+        free(|cs| self.lock_cs(cs, f))
+    }
+
+    /// Like `lock`, for code which already is in a critical section
+    pub fn lock_cs<F, R>(&self, cs: &CriticalSection, f: F) -> Option<R>
+    where
+        F: FnOnce(&CriticalSection, &mut T) -> R,
+    {
+        let inner = self.inner.borrow(cs);
+        if inner.locked.get() {
+            return None;
+        }
+        // Safe as `locked` keeps this the only reference until `f` returns
+        let value = unsafe { &mut *inner.value.get() }.as_mut()?;
+        inner.locked.set(true);
+        let result = f(cs, value);
+        inner.locked.set(false);
+        Some(result)
+    }
+}
+
+impl<T> Default for Shared<T> {
+    fn default() -> Shared<T> {
+        Shared::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_until_init() {
+        let shared: Shared<u8> = Shared::new();
+        assert!(!shared.is_initialized());
+        assert_eq!(shared.lock(|_, x| *x), None);
+        assert_eq!(shared.init(5), Ok(()));
+        assert!(shared.is_initialized());
+        assert_eq!(shared.init(6), Err(6));
+        assert_eq!(shared.lock(|_, x| *x), Some(5));
+    }
+
+    #[test]
+    fn lock_mutates() {
+        let shared = Shared::new();
+        shared.init(1u16).unwrap();
+        shared.lock(|_, x| *x += 41);
+        assert_eq!(shared.take(), Some(42));
+        assert_eq!(shared.take(), None);
+        assert_eq!(shared.lock(|_, x| *x), None);
+    }
+
+    #[test]
+    fn reentrant_lock_is_refused() {
+        let shared = Shared::new();
+        shared.init(0u8).unwrap();
+        let nested = shared.lock(|cs, x| {
+            *x = 1;
+            (shared.lock_cs(cs, |_, y| *y), shared.take(), shared.init(2))
+        });
+        assert_eq!(nested, Some((None, None, Err(2))));
+        // The lock is released again afterwards
+        assert_eq!(shared.lock(|_, x| *x), Some(1));
+    }
+
+    #[test]
+    fn usable_as_static() {
+        static COUNTER: Shared<u32> = Shared::new();
+        COUNTER.init(0).unwrap();
+        for _ in 0..10 {
+            COUNTER.lock(|_, c| *c += 1);
+        }
+        assert_eq!(COUNTER.lock(|_, c| *c), Some(10));
+    }
+
+    #[test]
+    fn free_serializes_threads() {
+        static COUNTER: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    for _ in 0..1000 {
+                        // Nested sections must not deadlock
+                        free(|_| {
+                            free(|cs| {
+                                let counter = COUNTER.borrow(cs);
+                                counter.set(counter.get() + 1);
+                            })
+                        });
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(free(|cs| COUNTER.borrow(cs).get()), 4000);
+    }
+}
@@ -1,22 +1,71 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 // Used by mmio module
 #![feature(const_fn)]
+#![cfg_attr(target_arch = "avr", feature(llvm_asm, abi_avr_interrupt))]
 
+#[cfg(target_arch = "avr")]
 pub extern crate atmega48p_hal as hal;
 /// See [`avr_device::entry`](https://docs.rs/avr-device/latest/avr_device/attr.entry.html).
-#[cfg(feature = "rt")]
+#[cfg(all(target_arch = "avr", feature = "rt"))]
 pub use hal::entry;
 
 //mod pins;
 //pub use crate::pins::*;
 
+#[macro_use]
 pub mod mmio;
 
+pub mod interrupt;
+
+pub mod clock;
+
+pub mod time;
+
+pub mod scheduler;
+
+pub mod display;
+
+pub mod fmt;
+
+pub mod fixed;
+
+pub mod control;
+
+pub mod filter;
+
+pub mod serial;
+
+pub mod queue;
+
+pub mod shell;
+
+pub mod telemetry;
+
+pub mod eeprom;
+
+pub mod config;
+
+pub mod servo;
+
+pub mod motor;
+
+mod progmem;
+
+#[cfg(test)]
+mod mock;
+
 // MMIO based device implementation.
+#[cfg(target_arch = "avr")]
 pub mod dev;
 
+#[cfg(target_arch = "avr")]
+pub mod gpio;
+
+#[cfg(target_arch = "avr")]
 pub use crate::atmega48p::Peripherals;
+#[cfg(target_arch = "avr")]
 pub use atmega48p_hal::atmega48p;
+#[cfg(target_arch = "avr")]
 pub use atmega48p_hal::prelude;
 
 /// Busy-Delay
@@ -27,20 +76,41 @@ pub use atmega48p_hal::prelude;
 ///
 /// [delay_ms]: fn.delay_ms.html
 /// [delay_us]: fn.delay_us.html
-pub type Delay = hal::delay::Delay<hal::clock::MHz1>;
+#[cfg(target_arch = "avr")]
+pub type Delay = hal::delay::Delay<clock::Clock>;
 
 /// Wait (busy spin) for `ms` milliseconds
+///
+/// Follows prescaler changes made with [`clock::set_prescaler()`][set_prescaler].
+///
+/// [set_prescaler]: clock/fn.set_prescaler.html
+#[cfg(target_arch = "avr")]
 pub fn delay_ms(ms: u16) {
-    use prelude::*;
-
-    Delay::new().delay_ms(ms)
+    delay_scaled_us(ms as u32 * 1000)
 }
 
 /// Wait (busy spin) for `us` microseconds
+///
+/// Follows prescaler changes made with [`clock::set_prescaler()`][set_prescaler].
+///
+/// [set_prescaler]: clock/fn.set_prescaler.html
+#[cfg(target_arch = "avr")]
 pub fn delay_us(us: u16) {
+    delay_scaled_us(us as u32)
+}
+
+/// `Delay` counts cycles for `clock::FREQ_HZ`, so the wait is rescaled to the
+/// current frequency and split into chunks it accepts.
+#[cfg(target_arch = "avr")]
+fn delay_scaled_us(us: u32) {
     use prelude::*;
 
-    Delay::new().delay_us(us)
+    let mut remaining = clock::scale(us);
+    while remaining > 0 {
+        let chunk = core::cmp::min(remaining, u16::MAX as u32) as u16;
+        Delay::new().delay_us(chunk);
+        remaining -= chunk as u32;
+    }
 }
 
 /// Support for the Serial Peripheral Interface
@@ -65,6 +135,7 @@ pub fn delay_us(us: u16) {
 /// ```
 ///
 /// [ex-spi]: https://github.com/Rahix/avr-hal/blob/master/boards/arduino-uno/examples/uno-spi-feedback.rs
+#[cfg(target_arch = "avr")]
 pub mod spi {
     pub use atmega48p_hal::spi::*;
 }
@@ -87,6 +158,7 @@ pub mod spi {
 /// ```
 ///
 /// [ex-adc]: https://github.com/Rahix/avr-hal/blob/master/boards/arduino-uno/examples/uno-adc.rs
+#[cfg(target_arch = "avr")]
 pub mod adc {
     pub use atmega48p_hal::adc::*;
 }
@@ -124,6 +196,7 @@ pub mod adc {
 /// | `pins.d11` | `.into_pwm(&mut timer2)` |
 ///
 /// [ex-pwm]: https://github.com/Rahix/avr-hal/blob/master/boards/arduino-uno/examples/uno-pwm.rs
+#[cfg(target_arch = "avr")]
 pub mod pwm {
     pub use atmega48p_hal::pwm::*;
 }
@@ -148,7 +221,8 @@ pub mod pwm {
 /// ```
 ///
 /// [ex-serial]: https://github.com/Rahix/avr-hal/blob/master/boards/arduino-uno/examples/uno-serial.rs
-pub type Serial<IMODE> = hal::usart::Usart0<hal::clock::MHz1, IMODE>;
+#[cfg(target_arch = "avr")]
+pub type Serial<IMODE> = hal::usart::Usart0<clock::Clock, IMODE>;
 
 /// I2C Master on pins `A4` (SDA) and `A5` (SCL)
 ///
@@ -168,4 +242,5 @@ pub type Serial<IMODE> = hal::usart::Usart0<hal::clock::MHz1, IMODE>;
 /// ```
 ///
 /// [ex-i2c]: https://github.com/Rahix/avr-hal/blob/master/boards/arduino-uno/examples/uno-i2cdetect.rs
-pub type I2c<M> = hal::i2c::I2c<hal::clock::MHz1, M>;
+#[cfg(target_arch = "avr")]
+pub type I2c<M> = hal::i2c::I2c<clock::Clock, M>;
@@ -75,6 +75,12 @@ pub struct Field<T: IntLike, R: RegisterLongName> {
 }
 
 impl<T: IntLike, R: RegisterLongName> Field<T, R> {
+    #[inline]
+    /// Get the unshifted bitmask of the field
+    pub fn mask(&self) -> T {
+        self.mask
+    }
+
     #[inline]
     pub fn read(self, val: T) -> T {
         (val & (self.mask << self.shift)) >> self.shift
@@ -0,0 +1,370 @@
+//! Macros for cleanly defining peripheral registers.
+
+/// Helper macro for computing bitmask of variable number of bits
+#[macro_export]
+macro_rules! bitmask {
+    ($numbits:expr) => {
+        (1 << ($numbits - 1)) + ((1 << ($numbits - 1)) - 1)
+    };
+}
+
+/// Helper macro for defining register fields.
+#[macro_export]
+macro_rules! register_bitmasks {
+    {
+        // BITFIELD_NAME OFFSET(x)
+        $valtype:ident, $reg_desc:ident, [
+            $( $(#[$inner:meta])* $field:ident OFFSET($offset:expr) ),+ $(,)?
+        ]
+    } => {
+        $( $crate::register_bitmasks!($valtype, $reg_desc, $(#[$inner])* $field, $offset, 1, []); )*
+    };
+    {
+        // BITFIELD_NAME OFFSET(x) NUMBITS(y)
+        $valtype:ident, $reg_desc:ident, [
+            $( $(#[$inner:meta])* $field:ident OFFSET($offset:expr) NUMBITS($numbits:expr) ),+ $(,)?
+        ]
+    } => {
+        $( $crate::register_bitmasks!($valtype, $reg_desc, $(#[$inner])* $field, $offset, $numbits, []); )*
+    };
+    {
+        // BITFIELD_NAME OFFSET(x) NUMBITS(y) []
+        $valtype:ident, $reg_desc:ident, [
+            $( $(#[$inner:meta])* $field:ident OFFSET($offset:expr) NUMBITS($numbits:expr)
+               $values:tt ),+ $(,)?
+        ]
+    } => {
+        $( $crate::register_bitmasks!($valtype, $reg_desc, $(#[$inner])* $field, $offset, $numbits,
+                                      $values); )*
+    };
+    {
+        // Single field with enumerated values
+        $valtype:ident, $reg_desc:ident, $(#[$outer:meta])* $field:ident,
+                    $offset:expr, $numbits:expr,
+                    [$( $(#[$inner:meta])* $valname:ident = $value:expr ),+ $(,)?]
+    } => {
+        #[allow(non_upper_case_globals)]
+        #[allow(unused)]
+        $(#[$outer])*
+        pub const $field: $crate::mmio::bitopts::Field<$valtype, $reg_desc> =
+            $crate::mmio::bitopts::Field::<$valtype, $reg_desc>::new($crate::bitmask!($numbits), $offset);
+
+        #[allow(non_snake_case)]
+        #[allow(unused)]
+        $(#[$outer])*
+        pub mod $field {
+            #[allow(unused_imports)]
+            use $crate::mmio::{bitopts::FieldValue, TryFromValue};
+            use super::$reg_desc;
+
+            $(
+            #[allow(non_upper_case_globals)]
+            #[allow(unused)]
+            $(#[$inner])*
+            pub const $valname: FieldValue<$valtype, $reg_desc> =
+                FieldValue::<$valtype, $reg_desc>::new($crate::bitmask!($numbits), $offset, $value);
+            )*
+
+            #[allow(non_upper_case_globals)]
+            #[allow(unused)]
+            pub const SET: FieldValue<$valtype, $reg_desc> =
+                FieldValue::<$valtype, $reg_desc>::new($crate::bitmask!($numbits), $offset,
+                                                      $crate::bitmask!($numbits));
+
+            #[allow(non_upper_case_globals)]
+            #[allow(unused)]
+            pub const CLEAR: FieldValue<$valtype, $reg_desc> =
+                FieldValue::<$valtype, $reg_desc>::new($crate::bitmask!($numbits), $offset, 0);
+
+            #[allow(dead_code)]
+            #[allow(non_camel_case_types)]
+            #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+            #[repr($valtype)]
+            $(#[$outer])*
+            pub enum Value {
+                $(
+                    $(#[$inner])*
+                    $valname = $value,
+                )*
+            }
+
+            impl TryFromValue<$valtype> for Value {
+                type EnumType = Value;
+
+                fn try_from(v: $valtype) -> Option<Self::EnumType> {
+                    match v {
+                        $(
+                            x if x == Value::$valname as $valtype => Some(Value::$valname),
+                        )*
+
+                        _ => Option::None
+                    }
+                }
+            }
+        }
+    };
+    {
+        // Single field without enumerated values
+        $valtype:ident, $reg_desc:ident, $(#[$outer:meta])* $field:ident,
+                    $offset:expr, $numbits:expr,
+                    []
+    } => {
+        #[allow(non_upper_case_globals)]
+        #[allow(unused)]
+        $(#[$outer])*
+        pub const $field: $crate::mmio::bitopts::Field<$valtype, $reg_desc> =
+            $crate::mmio::bitopts::Field::<$valtype, $reg_desc>::new($crate::bitmask!($numbits), $offset);
+
+        #[allow(non_snake_case)]
+        #[allow(unused)]
+        $(#[$outer])*
+        pub mod $field {
+            #[allow(unused_imports)]
+            use $crate::mmio::bitopts::FieldValue;
+            use super::$reg_desc;
+
+            #[allow(non_upper_case_globals)]
+            #[allow(unused)]
+            pub const SET: FieldValue<$valtype, $reg_desc> =
+                FieldValue::<$valtype, $reg_desc>::new($crate::bitmask!($numbits), $offset,
+                                                      $crate::bitmask!($numbits));
+
+            #[allow(non_upper_case_globals)]
+            #[allow(unused)]
+            pub const CLEAR: FieldValue<$valtype, $reg_desc> =
+                FieldValue::<$valtype, $reg_desc>::new($crate::bitmask!($numbits), $offset, 0);
+        }
+    };
+}
+
+/// Define register types and fields.
+///
+/// Every register gets its own module with a `Register` marker type
+/// implementing `RegisterLongName`, a `Field` constant per bit field and a
+/// sub-module per bit field holding `SET`, `CLEAR` and the enumerated
+/// `FieldValue` constants (if any) together with the `Value` enum used by
+/// `read_as_enum`.
+///
+/// Registers in the low I/O space can be declared with their I/O address as
+/// `PORTB IO(0x05) [ .. ]`, which lets `ReadWrite::sbi`/`cbi` emit single
+/// instructions for them.
+///
+/// ```ignore
+/// register_bitfields![u8,
+///     TCCR0B [
+///         FOC0A OFFSET(7) NUMBITS(1) [],
+///         FOC0B OFFSET(6) NUMBITS(1) [],
+///         WGM02 OFFSET(3) NUMBITS(1) [],
+///         CS0 OFFSET(0) NUMBITS(3) [
+///             Stopped = 0,
+///             Direct = 1,
+///             Prescale8 = 2
+///         ]
+///     ]
+/// ];
+/// ```
+#[macro_export]
+macro_rules! register_bitfields {
+    {
+        $valtype:ident, $( $(#[$inner:meta])* $reg:ident $(IO($io:literal))? [$($fields:tt)*] ),* $(,)?
+    } => {
+        $(
+            #[allow(non_snake_case)]
+            $(#[$inner])*
+            pub mod $reg {
+                #[derive(Clone, Copy)]
+                pub struct Register;
+
+                impl $crate::mmio::RegisterLongName for Register {
+                    $(
+                        const LOW_IO: bool = true;
+                        const IO_ADDR: u8 = $io;
+                    )?
+                }
+
+                $crate::register_bitmasks!( $valtype, Register, [$($fields)*] );
+            }
+        )*
+    }
+}
+
+/// Helper macro for generating register structures with explicit offsets.
+#[macro_export]
+macro_rules! register_fields {
+    // Entry point: struct attributes, visibility and name
+    (@root $(#[$attr_struct:meta])* $vis_struct:vis $name:ident { $($input:tt)* } ) => {
+        $crate::register_fields!(
+            @munch (
+                $($input)*
+            ) -> {
+                $vis_struct struct $(#[$attr_struct])* $name
+            }
+        );
+    };
+
+    // Print the struct once all fields have been munched.
+    (@munch
+        (
+            $(#[$attr_end:meta])*
+            ($offset:expr => @END),
+        )
+        -> {$vis_struct:vis struct $(#[$attr_struct:meta])* $name:ident $( $output:tt )*}
+    ) => {
+        $(#[$attr_struct])*
+        #[repr(C)]
+        $vis_struct struct $name {
+            $( $output )*
+        }
+    };
+
+    // Munch field.
+    (@munch
+        (
+            $(#[$attr:meta])*
+            ($offset_start:expr => $vis:vis $field:ident: $ty:ty),
+            $($after:tt)*
+        )
+        -> {$($output:tt)*}
+    ) => {
+        $crate::register_fields!(
+            @munch (
+                $($after)*
+            ) -> {
+                $($output)*
+                $(#[$attr])*
+                $vis $field: $ty,
+            }
+        );
+    };
+
+    // Munch padding, its size is taken from the offset of the next entry.
+    (@munch
+        (
+            $(#[$attr:meta])*
+            ($offset_start:expr => $padding:ident),
+            $(#[$attr_next:meta])*
+            ($offset_end:expr => $($next:tt)*),
+            $($after:tt)*
+        )
+        -> {$($output:tt)*}
+    ) => {
+        $crate::register_fields!(
+            @munch (
+                $(#[$attr_next])*
+                ($offset_end => $($next)*),
+                $($after)*
+            ) -> {
+                $($output)*
+                $(#[$attr])*
+                $padding: [u8; $offset_end - $offset_start],
+            }
+        );
+    };
+}
+
+/// Helper macro for compile-time checks of the register structure layout.
+///
+/// Every entry must start exactly where the previous one ends, reserved
+/// padding must be non-empty and the `@END` offset must match the size of the
+/// generated struct. Violations fail to compile with an overflow error in the
+/// array length of the offending check.
+#[macro_export]
+macro_rules! test_fields {
+    // Entry point
+    (@root $name:ident { $($input:tt)* } ) => {
+        $crate::test_fields!(@munch $name ($($input)*) : 0);
+    };
+
+    // Check the struct size at the end.
+    (@munch $name:ident
+        (
+            $(#[$attr_end:meta])*
+            ($size:expr => @END),
+        )
+        : $prev_end:expr
+    ) => {
+        #[allow(dead_code)]
+        const _: [(); 0 - !($size == $prev_end) as usize] = [];
+
+        #[allow(dead_code)]
+        const _: [(); 0 - !(::core::mem::size_of::<$name>() == $size) as usize] = [];
+    };
+
+    // Check field offset.
+    (@munch $name:ident
+        (
+            $(#[$attr:meta])*
+            ($offset_start:expr => $vis:vis $field:ident: $ty:ty),
+            $($after:tt)*
+        )
+        : $prev_end:expr
+    ) => {
+        #[allow(dead_code)]
+        const _: [(); 0 - !($offset_start == $prev_end) as usize] = [];
+
+        $crate::test_fields!(
+            @munch $name (
+                $($after)*
+            ) : ($offset_start + ::core::mem::size_of::<$ty>())
+        );
+    };
+
+    // Check padding offset and size.
+    (@munch $name:ident
+        (
+            $(#[$attr:meta])*
+            ($offset_start:expr => $padding:ident),
+            $(#[$attr_next:meta])*
+            ($offset_end:expr => $($next:tt)*),
+            $($after:tt)*
+        )
+        : $prev_end:expr
+    ) => {
+        #[allow(dead_code)]
+        const _: [(); 0 - !($offset_start == $prev_end) as usize] = [];
+
+        #[allow(dead_code)]
+        const _: [(); 0 - !($offset_end > $offset_start) as usize] = [];
+
+        $crate::test_fields!(
+            @munch $name (
+                $(#[$attr_next])*
+                ($offset_end => $($next)*),
+                $($after)*
+            ) : $offset_end
+        );
+    };
+}
+
+/// Define register structures with registers placed at explicit offsets.
+///
+/// Reserved entries are turned into `u8` arrays spanning up to the next
+/// entry, so gaps in the register map are filled automatically. The layout is
+/// verified at compile time: overlapping or skipped offsets, as well as an
+/// `@END` marker that does not match the struct size, are rejected.
+///
+/// ```ignore
+/// register_structs! {
+///     pub GpioRegisters {
+///         (0x00 => pub pin: ReadOnly<u8>),
+///         (0x01 => pub ddr: ReadWrite<u8>),
+///         (0x02 => _reserved0),
+///         (0x04 => pub port: ReadWrite<u8>),
+///         (0x05 => @END),
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! register_structs {
+    {
+        $(
+            $(#[$attr:meta])*
+            $vis_struct:vis $name:ident {
+                $( $fields:tt )*
+            }
+        ),* $(,)?
+    } => {
+        $( $crate::register_fields!(@root $(#[$attr])* $vis_struct $name { $($fields)* } ); )*
+        $( $crate::test_fields!(@root $name { $($fields)* } ); )*
+    };
+}
@@ -1,5 +1,8 @@
 //! Memory Mapped Input/Output types and functions.
 
+#[macro_use]
+pub mod macros;
+
 pub mod bitopts;
 
 pub mod registers;
@@ -7,7 +10,12 @@ pub mod registers;
 pub mod static_ref;
 
 /// Descriptive name for each register.
-pub trait RegisterLongName {}
+pub trait RegisterLongName {
+    /// Whether the register lives in the low I/O space reachable by `sbi`/`cbi`.
+    const LOW_IO: bool = false;
+    /// I/O space address of the register, only meaningful if `LOW_IO` is set.
+    const IO_ADDR: u8 = 0;
+}
 
 impl RegisterLongName for () {}
 
@@ -18,3 +26,6 @@ pub trait TryFromValue<V> {
 
     fn try_from(v: V) -> Option<Self::EnumType>;
 }
+
+#[cfg(test)]
+mod tests;
@@ -1,6 +1,9 @@
-use crate::mmio::{
-    bitopts::{Field, FieldValue, IntLike},
-    RegisterLongName, TryFromValue,
+use crate::{
+    interrupt::{self, CriticalSection},
+    mmio::{
+        bitopts::{Field, FieldValue, IntLike},
+        RegisterLongName, TryFromValue,
+    },
 };
 
 use core::{cell::UnsafeCell, fmt, marker::PhantomData};
@@ -57,6 +60,20 @@ impl<T: IntLike, R: RegisterLongName> ReadWrite<T, R> {
         self.set(field.modify(self.get()));
     }
 
+    #[inline]
+    /// Write the value of one or more fields, leaving the other fields unchanged, with
+    /// interrupts masked between the read and the write
+    pub fn modify_atomic(&self, field: FieldValue<T, R>) {
+        interrupt::free(|cs| self.modify_cs(cs, field))
+    }
+
+    #[inline]
+    /// Write the value of one or more fields, leaving the other fields unchanged, inside an
+    /// already entered critical section (e.g. an interrupt handler or `interrupt::free`)
+    pub fn modify_cs(&self, _cs: &CriticalSection, field: FieldValue<T, R>) {
+        self.modify(field);
+    }
+
     #[inline]
     /// Write the value of one or more fields, maintaining the value of unchanged fields via a
     /// provided original value, rather than a register read.
@@ -83,6 +100,69 @@ impl<T: IntLike, R: RegisterLongName> ReadWrite<T, R> {
     }
 }
 
+/// Data space address of I/O address zero.
+#[cfg(target_arch = "avr")]
+const LOW_IO_START: usize = 0x20;
+
+/// Emit `$op` for the register's I/O address and a bit number, the bit has to be spelled
+/// out as a literal because the `I` constraint needs an immediate at every optimization
+/// level.
+#[cfg(target_arch = "avr")]
+macro_rules! io_bit {
+    ($io:expr, $bit:expr, [$($n:literal => $template:literal),*]) => {
+        match $bit {
+            $($n => llvm_asm!($template :: "I"($io) :: "volatile"),)*
+            _ => unreachable!(),
+        }
+    };
+}
+
+impl<R: RegisterLongName> ReadWrite<u8, R> {
+    #[inline(always)]
+    /// Set a single-bit field with one `sbi` instruction.
+    ///
+    /// `sbi`/`cbi` can not be interrupted, so this is the cheapest interrupt-safe way to
+    /// change a pin in `PORTx`/`DDRx`. Only registers declared with their I/O address
+    /// (`IO(..)` in `register_bitfields!`) take this path, the address then is a constant
+    /// of the register type. Other registers and multi-bit fields fall back to
+    /// `modify_atomic`.
+    pub fn sbi(&self, field: Field<u8, R>) {
+        let (mask, shift) = (field.mask(), field.shift);
+        if cfg!(target_arch = "avr") && R::LOW_IO && mask == 1 {
+            #[cfg(target_arch = "avr")]
+            unsafe {
+                debug_assert!(self as *const Self as usize == LOW_IO_START + R::IO_ADDR as usize);
+                io_bit!(R::IO_ADDR, shift, [
+                    0 => "sbi $0, 0", 1 => "sbi $0, 1", 2 => "sbi $0, 2", 3 => "sbi $0, 3",
+                    4 => "sbi $0, 4", 5 => "sbi $0, 5", 6 => "sbi $0, 6", 7 => "sbi $0, 7"
+                ])
+            }
+        } else {
+            self.modify_atomic(FieldValue::<u8, R>::new(mask, shift, 0xFF));
+        }
+    }
+
+    #[inline(always)]
+    /// Clear a single-bit field with one `cbi` instruction.
+    ///
+    /// See `sbi` for the constraints, the same fallback applies.
+    pub fn cbi(&self, field: Field<u8, R>) {
+        let (mask, shift) = (field.mask(), field.shift);
+        if cfg!(target_arch = "avr") && R::LOW_IO && mask == 1 {
+            #[cfg(target_arch = "avr")]
+            unsafe {
+                debug_assert!(self as *const Self as usize == LOW_IO_START + R::IO_ADDR as usize);
+                io_bit!(R::IO_ADDR, shift, [
+                    0 => "cbi $0, 0", 1 => "cbi $0, 1", 2 => "cbi $0, 2", 3 => "cbi $0, 3",
+                    4 => "cbi $0, 4", 5 => "cbi $0, 5", 6 => "cbi $0, 6", 7 => "cbi $0, 7"
+                ])
+            }
+        } else {
+            self.modify_atomic(FieldValue::<u8, R>::new(mask, shift, 0));
+        }
+    }
+}
+
 /// Read-only registers.
 // To successfully alias this structure onto hardware registers in memory, this
 // struct must be exactly the size of the `T`.
@@ -228,6 +308,180 @@ impl<T: IntLike, R: RegisterLongName, W: RegisterLongName> Aliased<T, R, W> {
     }
 }
 
+/// 16-bit read/write registers accessed through the shared `TEMP` register.
+///
+/// The AVR core accesses 16-bit I/O registers (`TCNT1`, `OCR1A`, `ICR1`, ...) one byte at a
+/// time, the second byte goes through a temporary register shared by all 16-bit registers of
+/// the peripheral. A correct access writes the high byte first and reads the low byte first
+/// and must not be interrupted by an ISR touching another 16-bit register in between, so
+/// every access masks interrupts. The `_cs` variants skip the masking when the caller is
+/// already in a critical section (e.g. inside an interrupt handler).
+// The registers are laid out as two consecutive bytes, low byte first, which keeps the
+// alignment of this struct at one byte.
+#[repr(C)]
+pub struct ReadWrite16<R: RegisterLongName = ()> {
+    low: UnsafeCell<u8>,
+    high: UnsafeCell<u8>,
+    associated_register: PhantomData<R>,
+}
+
+impl<R: RegisterLongName> ReadWrite16<R> {
+    #[inline]
+    /// Get the raw register value
+    pub fn get(&self) -> u16 {
+        interrupt::free(|cs| self.get_cs(cs))
+    }
+
+    #[inline]
+    /// Get the raw register value inside an already entered critical section
+    pub fn get_cs(&self, _cs: &CriticalSection) -> u16 {
+        let low = unsafe { ::core::ptr::read_volatile(self.low.get()) };
+        let high = unsafe { ::core::ptr::read_volatile(self.high.get()) };
+        u16::from(high) << 8 | u16::from(low)
+    }
+
+    #[inline]
+    /// Set the raw register value
+    pub fn set(&self, value: u16) {
+        interrupt::free(|cs| self.set_cs(cs, value))
+    }
+
+    #[inline]
+    /// Set the raw register value inside an already entered critical section
+    pub fn set_cs(&self, _cs: &CriticalSection, value: u16) {
+        unsafe {
+            ::core::ptr::write_volatile(self.high.get(), (value >> 8) as u8);
+            ::core::ptr::write_volatile(self.low.get(), value as u8);
+        }
+    }
+
+    #[inline]
+    /// Read the value of the given field
+    pub fn read(&self, field: Field<u16, R>) -> u16 {
+        field.read(self.get())
+    }
+
+    #[inline]
+    /// Read value of the given field as an enum member
+    pub fn read_as_enum<E: TryFromValue<u16, EnumType = E>>(
+        &self,
+        field: Field<u16, R>,
+    ) -> Option<E> {
+        field.read_as_enum(self.get())
+    }
+
+    #[inline]
+    /// Make a local copy of the register
+    pub fn extract(&self) -> LocalRegisterCopy<u16, R> {
+        LocalRegisterCopy::new(self.get())
+    }
+
+    #[inline]
+    /// Write the value of one or more fields, overwriting the other fields with zero
+    pub fn write(&self, field: FieldValue<u16, R>) {
+        self.set(field.value);
+    }
+
+    #[inline]
+    /// Write the value of one or more fields, leaving the other fields unchanged.
+    ///
+    /// The read and the write happen in the same critical section.
+    pub fn modify(&self, field: FieldValue<u16, R>) {
+        interrupt::free(|cs| self.set_cs(cs, field.modify(self.get_cs(cs))))
+    }
+
+    #[inline]
+    /// Write the value of one or more fields, maintaining the value of unchanged fields via a
+    /// provided original value, rather than a register read.
+    pub fn modify_no_read(&self, original: LocalRegisterCopy<u16, R>, field: FieldValue<u16, R>) {
+        self.set(field.modify(original.get()));
+    }
+
+    #[inline]
+    /// Check if one or more bits in a field are set
+    pub fn is_set(&self, field: Field<u16, R>) -> bool {
+        field.is_set(self.get())
+    }
+
+    #[inline]
+    /// Check if any specified parts of a field match
+    pub fn matches_any(&self, field: FieldValue<u16, R>) -> bool {
+        field.matches_any(self.get())
+    }
+
+    #[inline]
+    /// Check if all specified parts of a field match
+    pub fn matches_all(&self, field: FieldValue<u16, R>) -> bool {
+        field.matches_all(self.get())
+    }
+}
+
+/// 16-bit read-only registers accessed through the shared `TEMP` register.
+///
+/// The low byte is read first, which latches the high byte, with interrupts masked.
+/// See `ReadWrite16` for details.
+#[repr(C)]
+pub struct ReadOnly16<R: RegisterLongName = ()> {
+    low: u8,
+    high: u8,
+    associated_register: PhantomData<R>,
+}
+
+impl<R: RegisterLongName> ReadOnly16<R> {
+    #[inline]
+    /// Get the raw register value
+    pub fn get(&self) -> u16 {
+        interrupt::free(|cs| self.get_cs(cs))
+    }
+
+    #[inline]
+    /// Get the raw register value inside an already entered critical section
+    pub fn get_cs(&self, _cs: &CriticalSection) -> u16 {
+        let low = unsafe { ::core::ptr::read_volatile(&self.low) };
+        let high = unsafe { ::core::ptr::read_volatile(&self.high) };
+        u16::from(high) << 8 | u16::from(low)
+    }
+
+    #[inline]
+    /// Read the value of the given field
+    pub fn read(&self, field: Field<u16, R>) -> u16 {
+        field.read(self.get())
+    }
+
+    #[inline]
+    /// Read value of the given field as an enum member
+    pub fn read_as_enum<E: TryFromValue<u16, EnumType = E>>(
+        &self,
+        field: Field<u16, R>,
+    ) -> Option<E> {
+        field.read_as_enum(self.get())
+    }
+
+    #[inline]
+    /// Make a local copy of the register
+    pub fn extract(&self) -> LocalRegisterCopy<u16, R> {
+        LocalRegisterCopy::new(self.get())
+    }
+
+    #[inline]
+    /// Check if one or more bits in a field are set
+    pub fn is_set(&self, field: Field<u16, R>) -> bool {
+        field.is_set(self.get())
+    }
+
+    #[inline]
+    /// Check if any specified parts of a field match
+    pub fn matches_any(&self, field: FieldValue<u16, R>) -> bool {
+        field.matches_any(self.get())
+    }
+
+    #[inline]
+    /// Check if all specified parts of a field match
+    pub fn matches_all(&self, field: FieldValue<u16, R>) -> bool {
+        field.matches_all(self.get())
+    }
+}
+
 /// A read-only copy register contents
 ///
 /// This behaves very similarly to a read-only register, but instead of doing a
@@ -371,6 +625,16 @@ impl<T: IntLike, R: RegisterLongName> InMemoryRegister<T, R> {
         self.set(field.modify(self.get()));
     }
 
+    #[inline]
+    pub fn modify_atomic(&self, field: FieldValue<T, R>) {
+        interrupt::free(|cs| self.modify_cs(cs, field))
+    }
+
+    #[inline]
+    pub fn modify_cs(&self, _cs: &CriticalSection, field: FieldValue<T, R>) {
+        self.modify(field);
+    }
+
     #[inline]
     pub fn modify_no_read(&self, original: LocalRegisterCopy<T, R>, field: FieldValue<T, R>) {
         self.set(field.modify(original.get()));
@@ -0,0 +1,345 @@
+//! Host side tests of the register layer.
+//!
+//! The same suite is instantiated for every register width, hardware access is
+//! replaced with `InMemoryRegister`.
+
+macro_rules! register_tests {
+    ($name:ident, $valtype:ident, $top:expr) => {
+        mod $name {
+            use crate::mmio::{
+                registers::{InMemoryRegister, LocalRegisterCopy},
+                TryFromValue,
+            };
+
+            register_bitfields![$valtype,
+                CTRL [
+                    EN OFFSET($top) NUMBITS(1) [],
+                    MODE OFFSET(4) NUMBITS(2) [
+                        Off = 0,
+                        Slow = 1,
+                        Fast = 2
+                    ],
+                    DIV OFFSET(0) NUMBITS(3) []
+                ]
+            ];
+
+            const EN_BIT: $valtype = 1 << $top;
+
+            #[test]
+            fn field_read() {
+                assert_eq!(CTRL::DIV.read(0b0101_0110), 0b110);
+                assert_eq!(CTRL::MODE.read(0b0101_0110), 0b01);
+                assert_eq!(CTRL::EN.read(EN_BIT), 1);
+                assert_eq!(CTRL::EN.read(!EN_BIT), 0);
+            }
+
+            #[test]
+            fn field_is_set() {
+                assert!(CTRL::MODE.is_set(0b0010_0000));
+                assert!(!CTRL::MODE.is_set(0b1100_1111));
+                assert!(CTRL::EN.is_set(EN_BIT));
+            }
+
+            #[test]
+            fn field_val() {
+                assert_eq!(CTRL::DIV.val(5).value, 5);
+                assert_eq!(CTRL::MODE.val(3).value, 0b0011_0000);
+                // Values wider than the field are truncated
+                assert_eq!(CTRL::DIV.val(0b1111).value, 0b111);
+                assert_eq!(CTRL::MODE.val(0b111).mask(), 0b0011_0000);
+            }
+
+            #[test]
+            fn field_value_constants() {
+                assert_eq!(CTRL::EN::SET.value, EN_BIT);
+                assert_eq!(CTRL::EN::SET.mask(), EN_BIT);
+                assert_eq!(CTRL::EN::CLEAR.value, 0);
+                assert_eq!(CTRL::EN::CLEAR.mask(), EN_BIT);
+                assert_eq!(CTRL::MODE::Fast.value, 0b0010_0000);
+                assert_eq!(CTRL::MODE::SET.value, 0b0011_0000);
+                assert_eq!(CTRL::DIV::SET.value, 0b111);
+            }
+
+            #[test]
+            fn field_value_read() {
+                let fv = CTRL::MODE::Slow + CTRL::DIV.val(6);
+                assert_eq!(fv.read(CTRL::MODE), 1);
+                assert_eq!(fv.read(CTRL::DIV), 6);
+                assert_eq!(fv.read(CTRL::EN), 0);
+            }
+
+            #[test]
+            fn field_value_add() {
+                let fv = CTRL::EN::SET + CTRL::MODE::Fast + CTRL::DIV.val(3);
+                assert_eq!(fv.value, EN_BIT | 0b0010_0011);
+                assert_eq!(fv.mask(), EN_BIT | 0b0011_0111);
+            }
+
+            #[test]
+            fn field_value_or() {
+                let fv = CTRL::EN::CLEAR | CTRL::MODE::Slow;
+                assert_eq!(fv.value, 0b0001_0000);
+                assert_eq!(fv.mask(), EN_BIT | 0b0011_0000);
+                assert_eq!(fv.value, (CTRL::EN::CLEAR + CTRL::MODE::Slow).value);
+            }
+
+            #[test]
+            fn field_value_add_assign() {
+                let mut fv = CTRL::DIV.val(1);
+                fv += CTRL::EN::SET;
+                fv += CTRL::MODE::Off;
+                assert_eq!(fv.value, EN_BIT | 0b0000_0001);
+                assert_eq!(fv.mask(), EN_BIT | 0b0011_0111);
+            }
+
+            #[test]
+            fn field_value_not() {
+                let fv = !CTRL::MODE::Slow;
+                assert_eq!(fv.mask(), !0b0011_0000);
+                assert_eq!(fv.value, !0b0001_0000);
+            }
+
+            #[test]
+            fn field_value_modify() {
+                assert_eq!(CTRL::MODE::Fast.modify(0b0001_0101), 0b0010_0101);
+                assert_eq!(CTRL::EN::SET.modify(0), EN_BIT);
+                assert_eq!(CTRL::EN::CLEAR.modify(EN_BIT | 0b111), 0b111);
+                assert_eq!(
+                    (CTRL::MODE::Off + CTRL::DIV.val(2)).modify(EN_BIT | 0b0011_0111),
+                    EN_BIT | 0b0000_0010
+                );
+            }
+
+            #[test]
+            fn field_value_matches() {
+                let val = EN_BIT | 0b0010_0001;
+                assert!(CTRL::MODE::Fast.matches_all(val));
+                assert!(!CTRL::MODE::Slow.matches_all(val));
+                assert!(CTRL::MODE::Slow.matches_any(val));
+                assert!(!CTRL::MODE::Slow.matches_any(0b0000_0111));
+                assert!((CTRL::EN::SET + CTRL::DIV.val(1)).matches_all(val));
+                assert!(!(CTRL::EN::SET + CTRL::DIV.val(2)).matches_all(val));
+                assert!(CTRL::EN::CLEAR.matches_all(0b0011_0111));
+            }
+
+            #[test]
+            fn read_as_enum() {
+                assert_eq!(
+                    CTRL::MODE.read_as_enum::<CTRL::MODE::Value>(0b0001_0000),
+                    Some(CTRL::MODE::Value::Slow)
+                );
+                assert_eq!(
+                    CTRL::MODE.read_as_enum::<CTRL::MODE::Value>(0b0010_0000),
+                    Some(CTRL::MODE::Value::Fast)
+                );
+                assert_eq!(
+                    CTRL::MODE.read_as_enum::<CTRL::MODE::Value>(0b0011_0000),
+                    None
+                );
+                assert_eq!(
+                    <CTRL::MODE::Value as TryFromValue<$valtype>>::try_from(0),
+                    Some(CTRL::MODE::Value::Off)
+                );
+            }
+
+            #[test]
+            fn register_get_set() {
+                let reg = InMemoryRegister::<$valtype, CTRL::Register>::new(0b0000_0101);
+                assert_eq!(reg.get(), 0b0000_0101);
+                reg.set(EN_BIT);
+                assert_eq!(reg.get(), EN_BIT);
+            }
+
+            #[test]
+            fn register_read() {
+                let reg = InMemoryRegister::<$valtype, CTRL::Register>::new(EN_BIT | 0b0010_0110);
+                assert_eq!(reg.read(CTRL::DIV), 0b110);
+                assert_eq!(reg.read(CTRL::MODE), 0b10);
+                assert!(reg.is_set(CTRL::EN));
+                assert_eq!(
+                    reg.read_as_enum(CTRL::MODE),
+                    Some(CTRL::MODE::Value::Fast)
+                );
+            }
+
+            #[test]
+            fn register_write() {
+                let reg = InMemoryRegister::<$valtype, CTRL::Register>::new(0b0000_0111);
+                reg.write(CTRL::MODE::Slow);
+                assert_eq!(reg.get(), 0b0001_0000);
+                reg.write(CTRL::EN::SET + CTRL::DIV.val(2));
+                assert_eq!(reg.get(), EN_BIT | 0b0000_0010);
+            }
+
+            #[test]
+            fn register_modify() {
+                let reg = InMemoryRegister::<$valtype, CTRL::Register>::new(0b0000_0111);
+                reg.modify(CTRL::MODE::Slow);
+                assert_eq!(reg.get(), 0b0001_0111);
+                reg.modify(CTRL::EN::SET + CTRL::DIV.val(2));
+                assert_eq!(reg.get(), EN_BIT | 0b0001_0010);
+                reg.modify(CTRL::EN::CLEAR);
+                assert_eq!(reg.get(), 0b0001_0010);
+            }
+
+            #[test]
+            fn register_modify_no_read() {
+                let reg = InMemoryRegister::<$valtype, CTRL::Register>::new(0b0000_0111);
+                let original = reg.extract();
+                reg.set(0);
+                reg.modify_no_read(original, CTRL::MODE::Fast);
+                assert_eq!(reg.get(), 0b0010_0111);
+            }
+
+            #[test]
+            fn register_modify_atomic() {
+                let reg = InMemoryRegister::<$valtype, CTRL::Register>::new(0b0000_0111);
+                reg.modify_atomic(CTRL::MODE::Fast + CTRL::EN::SET);
+                assert_eq!(reg.get(), EN_BIT | 0b0010_0111);
+                crate::interrupt::free(|cs| reg.modify_cs(cs, CTRL::DIV::CLEAR));
+                assert_eq!(reg.get(), EN_BIT | 0b0010_0000);
+            }
+
+            #[test]
+            fn register_matches() {
+                let reg = InMemoryRegister::<$valtype, CTRL::Register>::new(EN_BIT | 0b0001_0011);
+                assert!(reg.matches_all(CTRL::EN::SET + CTRL::MODE::Slow));
+                assert!(!reg.matches_all(CTRL::EN::SET + CTRL::MODE::Fast));
+                assert!(reg.matches_any(CTRL::DIV::SET));
+                // Any bit of the field being set counts, whatever the value
+                assert!(reg.matches_any(CTRL::MODE::Fast));
+                reg.modify(CTRL::MODE::Off);
+                assert!(!reg.matches_any(CTRL::MODE::Fast));
+            }
+
+            #[test]
+            fn local_register_copy() {
+                let reg = InMemoryRegister::<$valtype, CTRL::Register>::new(EN_BIT | 0b0010_0101);
+                let copy: LocalRegisterCopy<$valtype, CTRL::Register> = reg.extract();
+                reg.set(0);
+                assert_eq!(copy.get(), EN_BIT | 0b0010_0101);
+                assert_eq!(copy.read(CTRL::DIV), 0b101);
+                assert!(copy.is_set(CTRL::EN));
+                assert_eq!(copy.read_as_enum(CTRL::MODE), Some(CTRL::MODE::Value::Fast));
+                assert!(copy.matches_all(CTRL::MODE::Fast));
+                assert!(copy.matches_any(CTRL::EN::SET));
+                assert_eq!(copy.bitand(0b0000_0111).get(), 0b0000_0101);
+                assert_eq!($valtype::from(copy), EN_BIT | 0b0010_0101);
+            }
+        }
+    };
+}
+
+register_tests!(u8_registers, u8, 7);
+register_tests!(u16_registers, u16, 15);
+register_tests!(u32_registers, u32, 31);
+register_tests!(u64_registers, u64, 63);
+
+mod io_bits {
+    use crate::mmio::{
+        registers::{InMemoryRegister, ReadWrite},
+        RegisterLongName,
+    };
+
+    register_bitfields![u8,
+        PORT IO(0x05) [
+            P7 OFFSET(7) NUMBITS(1) [],
+            P1 OFFSET(1) NUMBITS(1) [],
+            LOW OFFSET(0) NUMBITS(2) []
+        ],
+        PLAIN [
+            P0 OFFSET(0) NUMBITS(1) []
+        ]
+    ];
+
+    #[test]
+    fn io_address() {
+        fn io<R: RegisterLongName>() -> (bool, u8) {
+            (R::LOW_IO, R::IO_ADDR)
+        }
+        assert_eq!(io::<PORT::Register>(), (true, 0x05));
+        assert_eq!(io::<PLAIN::Register>(), (false, 0));
+    }
+
+    // The instructions only exist on AVR, host builds always take the fallback path
+    fn as_read_write(reg: &InMemoryRegister<u8, PORT::Register>) -> &ReadWrite<u8, PORT::Register> {
+        unsafe { &*(reg as *const _ as *const ReadWrite<u8, PORT::Register>) }
+    }
+
+    #[test]
+    fn sbi_cbi() {
+        let mem = InMemoryRegister::<u8, PORT::Register>::new(0b0100_0000);
+        let reg = as_read_write(&mem);
+        reg.sbi(PORT::P7);
+        assert_eq!(reg.get(), 0b1100_0000);
+        reg.sbi(PORT::P1);
+        assert_eq!(reg.get(), 0b1100_0010);
+        reg.cbi(PORT::P7);
+        assert_eq!(reg.get(), 0b0100_0010);
+    }
+
+    #[test]
+    fn sbi_cbi_multi_bit_field() {
+        let mem = InMemoryRegister::<u8, PORT::Register>::new(0b1000_0000);
+        let reg = as_read_write(&mem);
+        reg.sbi(PORT::LOW);
+        assert_eq!(reg.get(), 0b1000_0011);
+        reg.cbi(PORT::LOW);
+        assert_eq!(reg.get(), 0b1000_0000);
+    }
+}
+
+mod wide_registers {
+    use crate::mmio::registers::{InMemoryRegister, ReadOnly16, ReadWrite16};
+
+    register_bitfields![u16,
+        CNT [
+            HIGH OFFSET(8) NUMBITS(8) [],
+            LOW OFFSET(0) NUMBITS(8) []
+        ]
+    ];
+
+    // The byte pair is backed by a host `u16`, which is little endian like the AVR
+    fn as_read_write(reg: &InMemoryRegister<u16, CNT::Register>) -> &ReadWrite16<CNT::Register> {
+        unsafe { &*(reg as *const _ as *const ReadWrite16<CNT::Register>) }
+    }
+
+    fn as_read_only(reg: &InMemoryRegister<u16, CNT::Register>) -> &ReadOnly16<CNT::Register> {
+        unsafe { &*(reg as *const _ as *const ReadOnly16<CNT::Register>) }
+    }
+
+    #[test]
+    fn layout() {
+        assert_eq!(core::mem::size_of::<ReadWrite16>(), 2);
+        assert_eq!(core::mem::align_of::<ReadWrite16>(), 1);
+        assert_eq!(core::mem::size_of::<ReadOnly16>(), 2);
+        assert_eq!(core::mem::align_of::<ReadOnly16>(), 1);
+    }
+
+    #[test]
+    fn get_set() {
+        let mem = InMemoryRegister::<u16, CNT::Register>::new(0x1234);
+        let reg = as_read_write(&mem);
+        assert_eq!(reg.get(), 0x1234);
+        reg.set(0xBEEF);
+        assert_eq!(mem.get(), 0xBEEF);
+        crate::interrupt::free(|cs| reg.set_cs(cs, 0x0102));
+        assert_eq!(crate::interrupt::free(|cs| reg.get_cs(cs)), 0x0102);
+        assert_eq!(as_read_only(&mem).get(), 0x0102);
+    }
+
+    #[test]
+    fn fields() {
+        let mem = InMemoryRegister::<u16, CNT::Register>::new(0x1234);
+        let reg = as_read_write(&mem);
+        assert_eq!(reg.read(CNT::HIGH), 0x12);
+        reg.modify(CNT::LOW.val(0xFF));
+        assert_eq!(mem.get(), 0x12FF);
+        reg.write(CNT::HIGH.val(0x80));
+        assert_eq!(mem.get(), 0x8000);
+        assert!(reg.is_set(CNT::HIGH));
+        assert!(reg.matches_all(CNT::HIGH.val(0x80) + CNT::LOW::CLEAR));
+        assert_eq!(as_read_only(&mem).read(CNT::HIGH), 0x80);
+        assert!(!as_read_only(&mem).matches_any(CNT::LOW::SET));
+    }
+}
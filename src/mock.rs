@@ -0,0 +1,23 @@
+//! Test doubles of `embedded-hal` pins shared by the driver tests.
+
+use core::cell::Cell;
+use core::convert::Infallible;
+
+use embedded_hal::digital::v2::OutputPin;
+
+/// Pin writing its level into a shared cell
+pub(crate) struct MockPin<'a>(pub &'a Cell<bool>);
+
+impl OutputPin for MockPin<'_> {
+    type Error = Infallible;
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.0.set(true);
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.0.set(false);
+        Ok(())
+    }
+}
@@ -0,0 +1,525 @@
+//! Brushed DC motors on H-bridges.
+//!
+//! A [`Motor`] takes a signed speed, `-MAX_SPEED` (full reverse) to
+//! `MAX_SPEED` (full forward), and drives a [`Bridge`] with it. Speed
+//! changes can be limited to an acceleration, which is applied in
+//! [`Motor::update`], and a direction reversal can be made to pause at zero
+//! for a dead time first. At zero speed the bridge either lets the motor
+//! coast or brakes it by shorting its terminals.
+//!
+//! Bridges are wired with `embedded_hal` pins, so the hal's PWM pins work
+//! right away:
+//!
+//! | Driver | Wiring |
+//! | --- | --- |
+//! | L298N, TB6612 | [`DualInput`]: PWM on `ENA`/`PWMA`, direction on `IN1` and `IN2` |
+//! | DRV8833 (in-in) | [`InIn`]: PWM on `xIN1`, direction on `xIN2` |
+//!
+//! ```no_run
+//! use m48_robo_rust::{
+//!     motor::{DualInput, Idle, Motor},
+//!     prelude::*,
+//!     pwm,
+//! };
+//!
+//! let dp = m48_robo_rust::Peripherals::take().unwrap();
+//! let mut portb = dp.PORTB.split();
+//! let mut portd = dp.PORTD.split();
+//!
+//! let mut timer = pwm::Timer1Pwm::new(dp.TC1, pwm::Prescaler::Prescale8);
+//! let bridge = DualInput::new(
+//!     portb.pb1.into_output(&mut portb.ddr).into_pwm(&mut timer),
+//!     portd.pd6.into_output(&mut portd.ddr),
+//!     portd.pd7.into_output(&mut portd.ddr),
+//! );
+//!
+//! let mut motor = Motor::new(bridge);
+//! motor.set_acceleration(500);
+//! motor.set_dead_time(50);
+//! motor.set_idle(Idle::Brake);
+//!
+//! motor.set_speed(-200);
+//! loop {
+//!     motor.update(10);
+//!     m48_robo_rust::delay_ms(10);
+//! }
+//! ```
+//!
+//! [`Motor`]: struct.Motor.html
+//! [`Motor::update`]: struct.Motor.html#method.update
+//! [`Bridge`]: trait.Bridge.html
+//! [`DualInput`]: struct.DualInput.html
+//! [`InIn`]: struct.InIn.html
+
+use embedded_hal::{digital::v2::OutputPin, PwmPin};
+
+/// Full speed, the duty cycle of the PWM pin
+pub const MAX_SPEED: i16 = 255;
+
+/// State of an H-bridge
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Drive {
+    /// Both terminals open, the motor runs down freely
+    Coast,
+    /// Both terminals shorted, the motor stops quickly
+    Brake,
+    /// Forward with a duty cycle
+    Forward(u8),
+    /// Reverse with a duty cycle
+    Reverse(u8),
+}
+
+/// H-bridge driving one motor
+pub trait Bridge {
+    fn drive(&mut self, drive: Drive);
+}
+
+/// Bridge with a PWM enable and two direction inputs (L298N, TB6612)
+///
+/// `IN1` high and `IN2` low drive forward, the opposite reverse. Both low
+/// coast and both high with full duty brake.
+pub struct DualInput<P, A, B> {
+    pwm: P,
+    in1: A,
+    in2: B,
+}
+
+impl<P, A, B> DualInput<P, A, B>
+where
+    P: PwmPin<Duty = u8>,
+    A: OutputPin,
+    B: OutputPin,
+{
+    /// Take over the pins, coasting
+    pub fn new(pwm: P, in1: A, in2: B) -> DualInput<P, A, B> {
+        let mut bridge = DualInput { pwm, in1, in2 };
+        bridge.drive(Drive::Coast);
+        bridge.pwm.enable();
+        bridge
+    }
+
+    /// Coast and give back the pins
+    pub fn release(mut self) -> (P, A, B) {
+        self.drive(Drive::Coast);
+        self.pwm.disable();
+        (self.pwm, self.in1, self.in2)
+    }
+
+    fn inputs(&mut self, in1: bool, in2: bool, duty: u8) {
+        if in1 {
+            self.in1.set_high().ok();
+        } else {
+            self.in1.set_low().ok();
+        }
+        if in2 {
+            self.in2.set_high().ok();
+        } else {
+            self.in2.set_low().ok();
+        }
+        self.pwm.set_duty(duty);
+    }
+}
+
+impl<P, A, B> Bridge for DualInput<P, A, B>
+where
+    P: PwmPin<Duty = u8>,
+    A: OutputPin,
+    B: OutputPin,
+{
+    fn drive(&mut self, drive: Drive) {
+        match drive {
+            Drive::Coast => self.inputs(false, false, 0),
+            Drive::Brake => self.inputs(true, true, u8::MAX),
+            Drive::Forward(duty) => self.inputs(true, false, duty),
+            Drive::Reverse(duty) => self.inputs(false, true, duty),
+        }
+    }
+}
+
+/// Bridge with PWM on one input and the direction on the other, the
+/// DRV8833 in in-in mode
+///
+/// Forward alternates driving and coasting (fast decay). In reverse the
+/// direction input stays high and the PWM input is inverted, alternating
+/// reverse and braking (slow decay), so the same duty cycle can turn the
+/// motor slightly faster in reverse.
+pub struct InIn<P, D> {
+    pwm: P,
+    direction: D,
+}
+
+impl<P, D> InIn<P, D>
+where
+    P: PwmPin<Duty = u8>,
+    D: OutputPin,
+{
+    /// Take over the pins, coasting
+    pub fn new(pwm: P, direction: D) -> InIn<P, D> {
+        let mut bridge = InIn { pwm, direction };
+        bridge.drive(Drive::Coast);
+        bridge.pwm.enable();
+        bridge
+    }
+
+    /// Coast and give back the pins
+    pub fn release(mut self) -> (P, D) {
+        self.drive(Drive::Coast);
+        self.pwm.disable();
+        (self.pwm, self.direction)
+    }
+}
+
+impl<P, D> Bridge for InIn<P, D>
+where
+    P: PwmPin<Duty = u8>,
+    D: OutputPin,
+{
+    fn drive(&mut self, drive: Drive) {
+        let (direction, duty) = match drive {
+            Drive::Coast => (false, 0),
+            Drive::Brake => (true, u8::MAX),
+            Drive::Forward(duty) => (false, duty),
+            Drive::Reverse(duty) => (true, u8::MAX - duty),
+        };
+        if direction {
+            self.direction.set_high().ok();
+        } else {
+            self.direction.set_low().ok();
+        }
+        self.pwm.set_duty(duty);
+    }
+}
+
+/// Bridge state at zero speed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Idle {
+    Coast,
+    Brake,
+}
+
+/// Motor with a signed speed on a `Bridge`
+pub struct Motor<B> {
+    bridge: B,
+    speed: i16,
+    target: i16,
+    /// Speed per second, 0 for no limit
+    acceleration: u16,
+    /// Speed change not applied yet in 1/1000
+    remainder: u16,
+    /// Pause at zero before reversing in ms
+    dead_time: u16,
+    /// Left of the current pause in ms
+    paused: u16,
+    idle: Idle,
+}
+
+impl<B: Bridge> Motor<B> {
+    /// Motor on `bridge`, coasting at zero speed
+    pub fn new(bridge: B) -> Motor<B> {
+        let mut motor = Motor {
+            bridge,
+            speed: 0,
+            target: 0,
+            acceleration: 0,
+            remainder: 0,
+            dead_time: 0,
+            paused: 0,
+            idle: Idle::Coast,
+        };
+        motor.output();
+        motor
+    }
+
+    /// Idle the bridge and give it back
+    pub fn release(mut self) -> B {
+        self.speed = 0;
+        self.output();
+        self.bridge
+    }
+
+    /// Current speed
+    pub fn speed(&self) -> i16 {
+        self.speed
+    }
+
+    /// Speed the motor accelerates to
+    pub fn target(&self) -> i16 {
+        self.target
+    }
+
+    /// Change speed to `speed`, limited to `MAX_SPEED` either way. Without
+    /// an acceleration limit or dead time this takes effect right away.
+    pub fn set_speed(&mut self, speed: i16) {
+        let speed = speed.max(-MAX_SPEED);
+        self.target = speed.min(MAX_SPEED);
+        self.advance(0);
+    }
+
+    /// Limit speed changes to `per_s` per second, 0 removes the limit
+    pub fn set_acceleration(&mut self, per_s: u16) {
+        self.acceleration = per_s;
+        self.remainder = 0;
+    }
+
+    /// Pause at zero speed for `ms` before driving the other way, and after
+    /// any stop from driving
+    pub fn set_dead_time(&mut self, ms: u16) {
+        self.dead_time = ms;
+    }
+
+    /// How the bridge holds zero speed
+    pub fn set_idle(&mut self, idle: Idle) {
+        self.idle = idle;
+        if self.speed == 0 {
+            self.output();
+        }
+    }
+
+    /// Stop right away, ignoring the acceleration limit, and coast from now on
+    pub fn coast(&mut self) {
+        self.stop(Idle::Coast);
+    }
+
+    /// Stop right away, ignoring the acceleration limit, and brake from now on
+    pub fn brake(&mut self) {
+        self.stop(Idle::Brake);
+    }
+
+    /// Advance acceleration and dead time by `elapsed_ms`
+    pub fn update(&mut self, elapsed_ms: u16) {
+        self.advance(elapsed_ms);
+    }
+
+    fn stop(&mut self, idle: Idle) {
+        self.idle = idle;
+        self.target = 0;
+        self.remainder = 0;
+        if self.speed != 0 {
+            self.speed = 0;
+            self.paused = self.dead_time;
+        }
+        self.output();
+    }
+
+    fn advance(&mut self, elapsed_ms: u16) {
+        let mut elapsed_ms = elapsed_ms;
+        if self.paused > 0 {
+            if elapsed_ms < self.paused {
+                self.paused -= elapsed_ms;
+                return;
+            }
+            elapsed_ms -= self.paused;
+            self.paused = 0;
+        }
+
+        let reversing = (self.speed > 0 && self.target < 0) || (self.speed < 0 && self.target > 0);
+        // Without a dead time the ramp goes right through zero
+        let goal = if reversing && self.dead_time > 0 {
+            0
+        } else {
+            self.target
+        };
+        let speed = self.ramp(goal, elapsed_ms);
+        if speed == 0 && self.speed != 0 {
+            self.paused = self.dead_time;
+        }
+        if speed != self.speed {
+            self.speed = speed;
+            self.output();
+        }
+    }
+
+    /// Speed moved towards `goal` for `elapsed_ms`
+    fn ramp(&mut self, goal: i16, elapsed_ms: u16) -> i16 {
+        if self.acceleration == 0 {
+            return goal;
+        }
+        let travel = self.remainder as u32 + self.acceleration as u32 * elapsed_ms as u32;
+        self.remainder = (travel % 1000) as u16;
+        let step = travel / 1000;
+        let distance = if goal > self.speed {
+            goal - self.speed
+        } else {
+            self.speed - goal
+        };
+        if step >= distance as u32 {
+            self.remainder = 0;
+            goal
+        } else if goal > self.speed {
+            self.speed + step as i16
+        } else {
+            self.speed - step as i16
+        }
+    }
+
+    fn output(&mut self) {
+        let drive = match self.speed {
+            0 if self.idle == Idle::Brake => Drive::Brake,
+            0 => Drive::Coast,
+            speed if speed > 0 => Drive::Forward(speed as u8),
+            speed => Drive::Reverse((-speed) as u8),
+        };
+        self.bridge.drive(drive);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+    use std::vec::Vec;
+
+    use crate::mock::MockPin;
+
+    /// PWM pin writing its duty cycle into a shared cell
+    struct MockPwm<'a>(&'a Cell<u8>);
+
+    impl PwmPin for MockPwm<'_> {
+        type Duty = u8;
+
+        fn disable(&mut self) {}
+
+        fn enable(&mut self) {}
+
+        fn get_duty(&self) -> u8 {
+            self.0.get()
+        }
+
+        fn get_max_duty(&self) -> u8 {
+            u8::MAX
+        }
+
+        fn set_duty(&mut self, duty: u8) {
+            self.0.set(duty);
+        }
+    }
+
+    /// Bridge recording each drive
+    struct Log(Vec<Drive>);
+
+    impl Bridge for Log {
+        fn drive(&mut self, drive: Drive) {
+            self.0.push(drive);
+        }
+    }
+
+    #[test]
+    fn dual_input() {
+        let (duty, in1, in2) = (Cell::new(7), Cell::new(true), Cell::new(true));
+        let mut bridge = DualInput::new(MockPwm(&duty), MockPin(&in1), MockPin(&in2));
+        let state = || (duty.get(), in1.get(), in2.get());
+        assert_eq!(state(), (0, false, false));
+        bridge.drive(Drive::Forward(100));
+        assert_eq!(state(), (100, true, false));
+        bridge.drive(Drive::Reverse(255));
+        assert_eq!(state(), (255, false, true));
+        bridge.drive(Drive::Brake);
+        assert_eq!(state(), (255, true, true));
+        bridge.release();
+        assert_eq!(state(), (0, false, false));
+    }
+
+    #[test]
+    fn in_in() {
+        let (duty, direction) = (Cell::new(7), Cell::new(true));
+        let mut bridge = InIn::new(MockPwm(&duty), MockPin(&direction));
+        let state = || (duty.get(), direction.get());
+        assert_eq!(state(), (0, false));
+        bridge.drive(Drive::Forward(100));
+        assert_eq!(state(), (100, false));
+        // Reverse drives while the PWM input is low
+        bridge.drive(Drive::Reverse(100));
+        assert_eq!(state(), (155, true));
+        bridge.drive(Drive::Reverse(255));
+        assert_eq!(state(), (0, true));
+        bridge.drive(Drive::Brake);
+        assert_eq!(state(), (255, true));
+    }
+
+    #[test]
+    fn speed_and_idle() {
+        let mut motor = Motor::new(Log(Vec::new()));
+        motor.set_speed(100);
+        motor.set_speed(-1000);
+        assert_eq!(motor.speed(), -MAX_SPEED);
+        motor.set_idle(Idle::Brake);
+        motor.set_speed(0);
+        motor.coast();
+        motor.set_speed(1);
+        motor.brake();
+        assert_eq!(
+            motor.release().0,
+            [
+                Drive::Coast,
+                Drive::Forward(100),
+                Drive::Reverse(255),
+                Drive::Brake,
+                Drive::Coast,
+                Drive::Forward(1),
+                Drive::Brake,
+                Drive::Brake,
+            ]
+        );
+    }
+
+    #[test]
+    fn acceleration() {
+        let mut motor = Motor::new(Log(Vec::new()));
+        // 250 per second is 2.5 per 10 ms
+        motor.set_acceleration(250);
+        motor.set_speed(10);
+        assert_eq!(motor.speed(), 0);
+        let speeds: Vec<i16> = (0..5)
+            .map(|_| {
+                motor.update(10);
+                motor.speed()
+            })
+            .collect();
+        assert_eq!(speeds, [2, 5, 7, 10, 10]);
+
+        // Straight through zero without a dead time
+        motor.set_speed(-10);
+        for _ in 0..4 {
+            motor.update(20);
+        }
+        assert_eq!(motor.speed(), -10);
+
+        // Stopping ignores the limit
+        motor.brake();
+        assert_eq!((motor.speed(), motor.target()), (0, 0));
+    }
+
+    #[test]
+    fn dead_time() {
+        let mut motor = Motor::new(Log(Vec::new()));
+        motor.set_dead_time(30);
+        motor.set_speed(50);
+        motor.set_speed(-50);
+        // Paused at zero
+        assert_eq!(motor.speed(), 0);
+        motor.update(20);
+        assert_eq!(motor.speed(), 0);
+        motor.update(20);
+        assert_eq!(motor.speed(), -50);
+
+        // With a ramp the pause starts once zero is reached
+        motor.set_acceleration(1000);
+        motor.set_speed(20);
+        let speeds: Vec<i16> = (0..8)
+            .map(|_| {
+                motor.update(20);
+                motor.speed()
+            })
+            .collect();
+        assert_eq!(speeds, [-30, -10, 0, 0, 10, 20, 20, 20]);
+
+        // Stops pause as well
+        motor.coast();
+        motor.set_speed(20);
+        assert_eq!(motor.speed(), 0);
+        motor.update(30);
+        assert_eq!(motor.speed(), 0);
+        motor.update(20);
+        assert_eq!(motor.speed(), 20);
+    }
+}
@@ -0,0 +1,42 @@
+//! Lookup tables in program memory.
+//!
+//! Read-only data is copied into the 512 bytes of RAM at startup unless it is
+//! placed in the `.progmem.data` section, which has to be read with `lpm`:
+//!
+//! ```ignore
+//! #[cfg_attr(target_arch = "avr", link_section = ".progmem.data")]
+//! static TABLE: [u16; 4] = [1, 2, 3, 4];
+//!
+//! let value = unsafe { progmem::read_u16(&TABLE[i]) };
+//! ```
+//!
+//! Host builds read the tables like any other static.
+
+/// Read a byte of a `.progmem.data` static
+///
+/// # Safety
+/// `p` has to point into a static placed in `.progmem.data`.
+#[inline(always)]
+pub(crate) unsafe fn read_u8(p: *const u8) -> u8 {
+    #[cfg(target_arch = "avr")]
+    {
+        let byte: u8;
+        llvm_asm!("lpm $0, Z" : "=r"(byte) : "z"(p));
+        byte
+    }
+
+    #[cfg(not(target_arch = "avr"))]
+    {
+        *p
+    }
+}
+
+/// Read a word of a `.progmem.data` static
+///
+/// # Safety
+/// `p` has to point into a static placed in `.progmem.data`.
+#[inline(always)]
+pub(crate) unsafe fn read_u16(p: *const u16) -> u16 {
+    let p = p as *const u8;
+    u16::from_le_bytes([read_u8(p), read_u8(p.add(1))])
+}
@@ -0,0 +1,371 @@
+//! Lock-free single-producer single-consumer queue.
+//!
+//! A [`Queue`] passes values from one interrupt handler to the main program
+//! or the other way round without masking interrupts. The queue is split
+//! once into a [`Producer`] and a [`Consumer`] handle; the producer only
+//! writes the tail index and the consumer only the head index, both of
+//! which are 8-bit atomics the AVR loads and stores in one instruction.
+//!
+//! The storage is an array `[T; N]` provided by the caller, one slot of
+//! which is kept free to tell a full queue from an empty one, so the queue
+//! holds up to `N - 1` values. `N` can be at most 256.
+//!
+//! ```
+//! use m48_robo_rust::queue::Queue;
+//!
+//! static SAMPLES: Queue<u16, [u16; 9]> = Queue::new([0; 9]);
+//!
+//! let (mut producer, mut consumer) = SAMPLES.split().unwrap();
+//! // Handles are only given out once
+//! assert!(SAMPLES.split().is_none());
+//!
+//! // in the ADC interrupt
+//! producer.try_push(512).ok();
+//!
+//! // in main
+//! assert_eq!(consumer.pop(), Some(512));
+//! assert_eq!(consumer.pop(), None);
+//! ```
+//!
+//! Values pushed while the queue is full are dropped and counted, see
+//! [`Consumer::take_overflows`].
+//!
+//! [`Queue`]: struct.Queue.html
+//! [`Producer`]: struct.Producer.html
+//! [`Consumer`]: struct.Consumer.html
+//! [`Consumer::take_overflows`]: struct.Consumer.html#method.take_overflows
+
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::mem;
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use crate::interrupt;
+
+/// Bounded FIFO of `Copy` values in the array `B`
+pub struct Queue<T, B> {
+    /// Only accessed through slot pointers, a reference to the whole array
+    /// would alias the slot written by the other side
+    storage: UnsafeCell<B>,
+    /// Length of `storage`
+    slots: usize,
+    /// Next slot to pop, written by the consumer only
+    head: AtomicU8,
+    /// Next slot to push, written by the producer only
+    tail: AtomicU8,
+    /// Failed pushes, wrapping, written by the producer only
+    overflows: AtomicU8,
+    producer: AtomicBool,
+    consumer: AtomicBool,
+    _marker: PhantomData<T>,
+}
+
+// The handles guarantee a single writer per index and slot
+unsafe impl<T: Send, B: Send> Sync for Queue<T, B> {}
+
+impl<T: Copy, B: AsRef<[T]> + AsMut<[T]>> Queue<T, B> {
+    /// Empty queue on top of `storage`, its contents are ignored
+    pub const fn new(storage: B) -> Queue<T, B> {
+        Queue {
+            storage: UnsafeCell::new(storage),
+            // `AsRef` can't be called here, an array holds exactly this many
+            slots: mem::size_of::<B>() / mem::size_of::<T>(),
+            head: AtomicU8::new(0),
+            tail: AtomicU8::new(0),
+            overflows: AtomicU8::new(0),
+            producer: AtomicBool::new(false),
+            consumer: AtomicBool::new(false),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Split into the producer and consumer handles, `None` while either of
+    /// them exists. Dropping a handle makes it available again.
+    pub fn split(&self) -> Option<(Producer<'_, T, B>, Consumer<'_, T, B>)> {
+        debug_assert!(self.slots() >= 2 && self.slots() <= 256);
+        interrupt::free(|_| {
+            if self.producer.load(Ordering::Relaxed) || self.consumer.load(Ordering::Relaxed) {
+                None
+            } else {
+                // Without handles nothing else accesses the storage. It has
+                // to be an array, whose first slot is at its own address.
+                debug_assert!({
+                    let storage = unsafe { (*self.storage.get()).as_ref() };
+                    storage.len() == self.slots
+                        && storage.as_ptr() == self.storage.get() as *const T
+                });
+                self.producer.store(true, Ordering::Relaxed);
+                self.consumer.store(true, Ordering::Relaxed);
+                let consumer = Consumer {
+                    queue: self,
+                    seen: self.overflows.load(Ordering::Relaxed),
+                };
+                Some((Producer { queue: self }, consumer))
+            }
+        })
+    }
+
+    /// Maximum number of queued values
+    pub fn capacity(&self) -> usize {
+        self.slots() - 1
+    }
+
+    /// Number of queued values
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire) as usize;
+        let tail = self.tail.load(Ordering::Acquire) as usize;
+        (tail + self.slots() - head) % self.slots()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+
+    /// Number of values dropped because the queue was full, wraps at 256
+    pub fn overflows(&self) -> u8 {
+        self.overflows.load(Ordering::Relaxed)
+    }
+
+    fn slots(&self) -> usize {
+        self.slots
+    }
+
+    fn next(&self, index: u8) -> u8 {
+        ((index as usize + 1) % self.slots()) as u8
+    }
+
+    /// Pointer to slot `index`. The producer only accesses the slot at the
+    /// tail and the consumer the one at the head, which are never the same
+    /// while either access is allowed.
+    unsafe fn slot(&self, index: u8) -> *mut T {
+        (self.storage.get() as *mut T).add(index as usize)
+    }
+}
+
+/// Pushing end of a `Queue`
+pub struct Producer<'a, T: Copy, B: AsRef<[T]> + AsMut<[T]>> {
+    queue: &'a Queue<T, B>,
+}
+
+impl<'a, T: Copy, B: AsRef<[T]> + AsMut<[T]>> Producer<'a, T, B> {
+    /// Append `value`, handing it back and counting an overflow if the queue
+    /// is full
+    pub fn try_push(&mut self, value: T) -> Result<(), T> {
+        let queue = self.queue;
+        let tail = queue.tail.load(Ordering::Relaxed);
+        let next = queue.next(tail);
+        if next == queue.head.load(Ordering::Acquire) {
+            let overflows = queue.overflows.load(Ordering::Relaxed);
+            queue
+                .overflows
+                .store(overflows.wrapping_add(1), Ordering::Relaxed);
+            return Err(value);
+        }
+        unsafe { queue.slot(tail).write(value) };
+        queue.tail.store(next, Ordering::Release);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.queue.is_full()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.queue.capacity()
+    }
+}
+
+impl<'a, T: Copy, B: AsRef<[T]> + AsMut<[T]>> Drop for Producer<'a, T, B> {
+    fn drop(&mut self) {
+        self.queue.producer.store(false, Ordering::Release);
+    }
+}
+
+/// Popping end of a `Queue`
+pub struct Consumer<'a, T: Copy, B: AsRef<[T]> + AsMut<[T]>> {
+    queue: &'a Queue<T, B>,
+    seen: u8,
+}
+
+impl<'a, T: Copy, B: AsRef<[T]> + AsMut<[T]>> Consumer<'a, T, B> {
+    /// Remove the oldest value
+    pub fn pop(&mut self) -> Option<T> {
+        let queue = self.queue;
+        let head = queue.head.load(Ordering::Relaxed);
+        if head == queue.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        let value = unsafe { queue.slot(head).read() };
+        queue.head.store(queue.next(head), Ordering::Release);
+        Some(value)
+    }
+
+    /// Oldest value without removing it
+    pub fn peek(&self) -> Option<T> {
+        let queue = self.queue;
+        let head = queue.head.load(Ordering::Relaxed);
+        if head == queue.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        Some(unsafe { queue.slot(head).read() })
+    }
+
+    /// Number of overflows since the last call (or the split), up to 255
+    pub fn take_overflows(&mut self) -> u8 {
+        let overflows = self.queue.overflows();
+        let new = overflows.wrapping_sub(self.seen);
+        self.seen = overflows;
+        new
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.queue.capacity()
+    }
+}
+
+impl<'a, T: Copy, B: AsRef<[T]> + AsMut<[T]>> Drop for Consumer<'a, T, B> {
+    fn drop(&mut self) {
+        self.queue.consumer.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn fifo_and_capacity() {
+        let queue = Queue::new([0u8; 4]);
+        let (mut producer, mut consumer) = queue.split().unwrap();
+        assert_eq!(producer.capacity(), 3);
+        assert!(consumer.is_empty());
+        assert_eq!(consumer.pop(), None);
+
+        for i in 1..=3 {
+            assert_eq!(producer.try_push(i), Ok(()));
+        }
+        assert!(producer.is_full());
+        assert_eq!(producer.try_push(4), Err(4));
+        assert_eq!(queue.len(), 3);
+        assert_eq!(consumer.peek(), Some(1));
+
+        assert_eq!(consumer.pop(), Some(1));
+        assert_eq!(producer.try_push(5), Ok(()));
+        assert_eq!(consumer.pop(), Some(2));
+        assert_eq!(consumer.pop(), Some(3));
+        assert_eq!(consumer.pop(), Some(5));
+        assert_eq!(consumer.pop(), None);
+        assert!(producer.is_empty());
+    }
+
+    #[test]
+    fn overflows_are_counted() {
+        let queue = Queue::new([0u16; 2]);
+        let (mut producer, mut consumer) = queue.split().unwrap();
+        assert_eq!(consumer.take_overflows(), 0);
+        producer.try_push(1).unwrap();
+        for i in 0..300 {
+            assert_eq!(producer.try_push(i), Err(i));
+        }
+        assert_eq!(queue.overflows(), (300 % 256) as u8);
+        // More than 255 since the last look can't be told apart
+        assert_eq!(consumer.take_overflows(), 44);
+        assert_eq!(consumer.take_overflows(), 0);
+        producer.try_push(2).ok();
+        assert_eq!(consumer.take_overflows(), 1);
+        assert_eq!(consumer.pop(), Some(1));
+    }
+
+    #[test]
+    fn split_once() {
+        let queue = Queue::new([0u8; 3]);
+        let (producer, consumer) = queue.split().unwrap();
+        assert!(queue.split().is_none());
+        drop(producer);
+        assert!(queue.split().is_none());
+        drop(consumer);
+
+        let (mut producer, _consumer) = queue.split().unwrap();
+        producer.try_push(7).unwrap();
+        drop(producer);
+        drop(_consumer);
+        // Values survive re-splitting
+        let (_producer, mut consumer) = queue.split().unwrap();
+        assert_eq!(consumer.pop(), Some(7));
+    }
+
+    #[test]
+    fn largest_queue() {
+        let queue = Queue::new([0u8; 256]);
+        let (mut producer, mut consumer) = queue.split().unwrap();
+        for round in 0..3 {
+            for i in 0..255 {
+                producer.try_push(i as u8 ^ round).unwrap();
+            }
+            assert!(producer.try_push(0).is_err());
+            for i in 0..255 {
+                assert_eq!(consumer.pop(), Some(i as u8 ^ round));
+            }
+            assert_eq!(consumer.pop(), None);
+        }
+    }
+
+    #[test]
+    fn threads_stress() {
+        const COUNT: u32 = 1_000_000;
+        static QUEUE: Queue<u32, [u32; 16]> = Queue::new([0; 16]);
+        static DONE: AtomicBool = AtomicBool::new(false);
+        let (mut producer, mut consumer) = QUEUE.split().unwrap();
+
+        // Values which don't fit are dropped
+        let sender = thread::spawn(move || {
+            let dropped = (0..COUNT)
+                .filter(|&i| producer.try_push(i).is_err())
+                .count() as u32;
+            DONE.store(true, Ordering::Release);
+            dropped
+        });
+
+        let mut received = 0;
+        let mut next = 0;
+        loop {
+            // Everything was pushed before `DONE`, so an empty queue after
+            // seeing it is final
+            let done = DONE.load(Ordering::Acquire);
+            match consumer.pop() {
+                Some(value) => {
+                    // In order, with gaps for the dropped values
+                    assert!(value >= next);
+                    next = value + 1;
+                    received += 1;
+                }
+                None if done => break,
+                None => thread::yield_now(),
+            }
+        }
+        let dropped = sender.join().unwrap();
+        assert_eq!(received + dropped, COUNT);
+        assert_eq!(QUEUE.overflows(), dropped as u8);
+    }
+}
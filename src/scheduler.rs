@@ -0,0 +1,409 @@
+//! Cooperative, statically allocated task scheduler.
+//!
+//! Tasks are plain `fn()`s which are released by [`Scheduler::advance`] after
+//! a number of ticks and run to completion in [`Scheduler::dispatch`], highest
+//! priority first. The scheduler does not allocate, the task table is a slice
+//! of [`Slot`]s provided by the caller, each slot taking 9 bytes of RAM on the
+//! MCU.
+//!
+//! A task released again before it got to run has overrun, this is counted
+//! per task and can be read with [`Scheduler::overruns`].
+//!
+//! # Example
+//! Ticking with the millisecond clock from the `time` module:
+//! ```no_run
+//! use m48_robo_rust::scheduler::{Scheduler, Slot, Task};
+//! use m48_robo_rust::time;
+//!
+//! fn read_sensors() {}
+//! fn control() {}
+//! fn blink() {}
+//!
+//! let mut slots: [Slot; 3] = [None; 3];
+//! let mut scheduler = Scheduler::new(&mut slots);
+//! scheduler.add(Task::periodic(control, 10).priority(2));
+//! scheduler.add(Task::periodic(read_sensors, 10).priority(3).offset(5));
+//! scheduler.add(Task::periodic(blink, 500));
+//!
+//! let mut last = time::millis();
+//! loop {
+//!     let now = time::millis();
+//!     scheduler.advance(now.wrapping_sub(last) as u16);
+//!     last = now;
+//!     scheduler.dispatch();
+//! }
+//! ```
+//!
+//! [`Scheduler::advance`]: struct.Scheduler.html#method.advance
+//! [`Scheduler::dispatch`]: struct.Scheduler.html#method.dispatch
+//! [`Scheduler::overruns`]: struct.Scheduler.html#method.overruns
+//! [`Slot`]: type.Slot.html
+
+/// Entry of the task table, `None` for a free slot
+pub type Slot = Option<Task>;
+
+/// Handle of a task added to a `Scheduler`, the index of its slot.
+///
+/// The handle is reused once the task was removed or a one-shot task has run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TaskId(u8);
+
+/// Periodic or one-shot task
+#[derive(Clone, Copy)]
+pub struct Task {
+    run: fn(),
+    /// Ticks between releases, zero for one-shot tasks
+    period: u16,
+    /// Ticks until the next release
+    delay: u16,
+    priority: u8,
+    pending: bool,
+    overruns: u8,
+}
+
+impl Task {
+    /// Task released every `period` ticks, the first time after one period.
+    ///
+    /// A period of zero would mark the task as one-shot without ever releasing
+    /// it, it is raised to one tick instead.
+    pub const fn periodic(run: fn(), period: u16) -> Task {
+        // `max` is not usable in a const fn, this maps zero to one
+        let period = period | (period == 0) as u16;
+        Task {
+            run,
+            period,
+            delay: period,
+            priority: 0,
+            pending: false,
+            overruns: 0,
+        }
+    }
+
+    /// Task released once after `delay` ticks
+    pub const fn once(run: fn(), delay: u16) -> Task {
+        Task {
+            run,
+            period: 0,
+            delay,
+            priority: 0,
+            pending: delay == 0,
+            overruns: 0,
+        }
+    }
+
+    /// Priority of the task, higher values are dispatched first. Tasks of the
+    /// same priority run in the order of their slots.
+    pub const fn priority(mut self, priority: u8) -> Task {
+        self.priority = priority;
+        self
+    }
+
+    /// Ticks until the first release of a periodic task, used to spread
+    /// tasks with the same period over different ticks. An offset of zero
+    /// releases the task right away.
+    pub fn offset(mut self, ticks: u16) -> Task {
+        if ticks == 0 {
+            self.delay = self.period;
+            self.pending = true;
+        } else {
+            self.delay = ticks;
+        }
+        self
+    }
+
+    fn is_once(&self) -> bool {
+        self.period == 0
+    }
+
+    fn advance(&mut self, ticks: u16) {
+        if self.is_once() && (self.pending || self.delay == 0) {
+            return;
+        }
+        if ticks < self.delay {
+            self.delay -= ticks;
+            return;
+        }
+
+        let releases = if self.is_once() {
+            self.delay = 0;
+            1
+        } else {
+            let over = ticks - self.delay;
+            self.delay = self.period - over % self.period;
+            1 + over / self.period
+        };
+        let missed = if self.pending { releases } else { releases - 1 };
+        self.overruns = self.overruns.saturating_add(missed.min(255) as u8);
+        self.pending = true;
+    }
+}
+
+/// Cooperative scheduler over a caller provided task table
+pub struct Scheduler<'a> {
+    slots: &'a mut [Slot],
+}
+
+impl<'a> Scheduler<'a> {
+    /// Create a scheduler keeping its tasks in `slots`, which should be all
+    /// `None`. At most 256 slots are used.
+    pub fn new(slots: &'a mut [Slot]) -> Scheduler<'a> {
+        let len = slots.len().min(256);
+        Scheduler {
+            slots: &mut slots[..len],
+        }
+    }
+
+    /// Add `task` to the first free slot, `None` if the table is full
+    pub fn add(&mut self, task: Task) -> Option<TaskId> {
+        let index = self.slots.iter().position(|slot| slot.is_none())?;
+        self.slots[index] = Some(task);
+        Some(TaskId(index as u8))
+    }
+
+    /// Remove a task, returns it if it was still scheduled
+    pub fn remove(&mut self, id: TaskId) -> Option<Task> {
+        self.slots.get_mut(id.0 as usize)?.take()
+    }
+
+    /// Number of times the task was released before its previous release
+    /// had run, saturating at 255
+    pub fn overruns(&self, id: TaskId) -> Option<u8> {
+        self.task(id).map(|task| task.overruns)
+    }
+
+    /// Reset the overrun count of a task
+    pub fn clear_overruns(&mut self, id: TaskId) {
+        if let Some(Some(task)) = self.slots.get_mut(id.0 as usize) {
+            task.overruns = 0;
+        }
+    }
+
+    /// Whether a task has been released and waits to be dispatched
+    pub fn is_pending(&self, id: TaskId) -> bool {
+        matches!(self.task(id), Some(task) if task.pending)
+    }
+
+    /// Let `ticks` ticks pass, releasing the tasks that became due
+    pub fn advance(&mut self, ticks: u16) {
+        if ticks == 0 {
+            return;
+        }
+        for task in self.slots.iter_mut().flatten() {
+            task.advance(ticks);
+        }
+    }
+
+    /// Run all released tasks, highest priority first, and return how many
+    /// were run. One-shot tasks are removed after they ran.
+    pub fn dispatch(&mut self) -> usize {
+        let mut count = 0;
+        while let Some(index) = self.next_pending() {
+            let task = self.slots[index].as_mut().unwrap();
+            task.pending = false;
+            let run = task.run;
+            if task.is_once() {
+                self.slots[index] = None;
+            }
+            run();
+            count += 1;
+        }
+        count
+    }
+
+    fn task(&self, id: TaskId) -> Option<&Task> {
+        self.slots.get(id.0 as usize)?.as_ref()
+    }
+
+    fn next_pending(&self) -> Option<usize> {
+        let mut next: Option<(usize, u8)> = None;
+        for (index, slot) in self.slots.iter().enumerate() {
+            if let Some(task) = slot {
+                let higher = match next {
+                    Some((_, priority)) => task.priority > priority,
+                    None => true,
+                };
+                if task.pending && higher {
+                    next = Some((index, task.priority));
+                }
+            }
+        }
+        next.map(|(index, _)| index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tasks `a`, `b` and `c` appending 1, 2 and 3 as decimal digits to a
+    /// log local to the test
+    macro_rules! tasks {
+        () => {
+            use std::sync::atomic::{AtomicU32, Ordering};
+
+            static LOG: AtomicU32 = AtomicU32::new(0);
+
+            fn push(id: u32) {
+                LOG.store(LOG.load(Ordering::Relaxed) * 10 + id, Ordering::Relaxed);
+            }
+
+            fn take_log() -> u32 {
+                LOG.swap(0, Ordering::Relaxed)
+            }
+
+            #[allow(dead_code)]
+            fn a() {
+                push(1);
+            }
+
+            #[allow(dead_code)]
+            fn b() {
+                push(2);
+            }
+
+            #[allow(dead_code)]
+            fn c() {
+                push(3);
+            }
+        };
+    }
+
+    #[test]
+    fn periodic_release() {
+        tasks!();
+        let mut slots: [Slot; 2] = [None; 2];
+        let mut sched = Scheduler::new(&mut slots);
+        sched.add(Task::periodic(a, 3)).unwrap();
+        sched.add(Task::periodic(b, 2)).unwrap();
+
+        let mut runs = Vec::new();
+        for _ in 0..6 {
+            sched.advance(1);
+            sched.dispatch();
+            runs.push(take_log());
+        }
+        assert_eq!(runs, vec![0, 2, 1, 2, 0, 12]);
+    }
+
+    #[test]
+    fn zero_period_runs_every_tick() {
+        tasks!();
+        let mut slots: [Slot; 1] = [None; 1];
+        let mut sched = Scheduler::new(&mut slots);
+        sched.add(Task::periodic(a, 0)).unwrap();
+
+        for _ in 0..3 {
+            sched.advance(1);
+            assert_eq!(sched.dispatch(), 1);
+        }
+        assert_eq!(take_log(), 111);
+    }
+
+    #[test]
+    fn priority_order() {
+        tasks!();
+        let mut slots: [Slot; 3] = [None; 3];
+        let mut sched = Scheduler::new(&mut slots);
+        sched.add(Task::periodic(a, 1)).unwrap();
+        sched.add(Task::periodic(b, 1).priority(5)).unwrap();
+        sched.add(Task::periodic(c, 1).priority(5)).unwrap();
+
+        sched.advance(1);
+        assert_eq!(sched.dispatch(), 3);
+        assert_eq!(take_log(), 231);
+    }
+
+    #[test]
+    fn offset_spreads_releases() {
+        tasks!();
+        let mut slots: [Slot; 2] = [None; 2];
+        let mut sched = Scheduler::new(&mut slots);
+        sched.add(Task::periodic(a, 4)).unwrap();
+        sched.add(Task::periodic(b, 4).offset(2)).unwrap();
+
+        for _ in 0..8 {
+            sched.advance(1);
+            sched.dispatch();
+        }
+        assert_eq!(take_log(), 2121);
+    }
+
+    #[test]
+    fn zero_offset_releases_immediately() {
+        tasks!();
+        let mut slots: [Slot; 1] = [None; 1];
+        let mut sched = Scheduler::new(&mut slots);
+        let id = sched.add(Task::periodic(a, 3).offset(0)).unwrap();
+        assert_eq!(sched.dispatch(), 1);
+        sched.advance(2);
+        assert_eq!(sched.dispatch(), 0);
+        sched.advance(1);
+        assert_eq!(sched.dispatch(), 1);
+        assert_eq!(sched.overruns(id), Some(0));
+        assert_eq!(take_log(), 11);
+    }
+
+    #[test]
+    fn one_shot_runs_once_and_frees_slot() {
+        tasks!();
+        let mut slots: [Slot; 1] = [None; 1];
+        let mut sched = Scheduler::new(&mut slots);
+        let id = sched.add(Task::once(a, 2)).unwrap();
+        assert_eq!(sched.add(Task::once(b, 1)), None);
+
+        sched.advance(1);
+        assert_eq!(sched.dispatch(), 0);
+        sched.advance(1);
+        assert!(sched.is_pending(id));
+        assert_eq!(sched.dispatch(), 1);
+        sched.advance(10);
+        assert_eq!(sched.dispatch(), 0);
+        assert_eq!(take_log(), 1);
+
+        assert_eq!(sched.add(Task::once(b, 0)), Some(id));
+        assert_eq!(sched.dispatch(), 1);
+        assert_eq!(take_log(), 2);
+    }
+
+    #[test]
+    fn overruns_are_counted() {
+        tasks!();
+        let mut slots: [Slot; 1] = [None; 1];
+        let mut sched = Scheduler::new(&mut slots);
+        let id = sched.add(Task::periodic(a, 2)).unwrap();
+
+        // Released at 2, 4 and 6 without being dispatched
+        sched.advance(2);
+        sched.advance(2);
+        sched.advance(2);
+        assert_eq!(sched.overruns(id), Some(2));
+
+        // A long stall releasing the task several times at once
+        sched.advance(7);
+        assert_eq!(sched.overruns(id), Some(5));
+
+        assert_eq!(sched.dispatch(), 1);
+        assert_eq!(take_log(), 1);
+        sched.clear_overruns(id);
+        assert_eq!(sched.overruns(id), Some(0));
+
+        // Phase is kept over the stall, next release at 14
+        sched.advance(1);
+        assert!(sched.is_pending(id));
+        assert_eq!(sched.overruns(id), Some(0));
+    }
+
+    #[test]
+    fn remove_task() {
+        tasks!();
+        let mut slots: [Slot; 2] = [None; 2];
+        let mut sched = Scheduler::new(&mut slots);
+        let id = sched.add(Task::periodic(a, 1)).unwrap();
+        assert!(sched.remove(id).is_some());
+        assert!(sched.remove(id).is_none());
+        assert_eq!(sched.overruns(id), None);
+        sched.advance(1);
+        assert_eq!(sched.dispatch(), 0);
+        assert_eq!(take_log(), 0);
+    }
+}
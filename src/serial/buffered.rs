@@ -0,0 +1,215 @@
+//! `BufferedSerial` on USART0.
+
+use core::cell::Cell;
+use core::convert::Infallible;
+
+use embedded_hal::serial;
+
+use super::{Error, Errors, Received};
+use crate::dev::usart::{UCSR0A, UCSR0B, USART0};
+use crate::hal::port::mode::InputMode;
+use crate::interrupt::{self, Mutex, Shared};
+use crate::queue::{Consumer, Producer, Queue};
+use crate::Serial;
+
+/// Bytes received but not read yet before `Error::Overrun`, queued errors
+/// take up a byte each
+pub const RX_CAPACITY: usize = 32;
+/// Bytes written but not sent yet before writing blocks
+pub const TX_CAPACITY: usize = 32;
+
+// Entries of `Received`
+type RxBuffer = [u16; RX_CAPACITY + 1];
+type TxBuffer = [u8; TX_CAPACITY + 1];
+
+static RX: Queue<u16, RxBuffer> = Queue::new([0; RX_CAPACITY + 1]);
+static TX: Queue<u8, TxBuffer> = Queue::new([0; TX_CAPACITY + 1]);
+
+// Queue ends of the interrupt handlers, the other ends are kept in the
+// `BufferedSerial`
+static RX_PRODUCER: Shared<Producer<'static, u16, RxBuffer>> = Shared::new();
+static TX_CONSUMER: Shared<Consumer<'static, u8, TxBuffer>> = Shared::new();
+
+// Errors the receive handler couldn't queue as the queue was full, they go
+// with the next entry
+static ERRORS: Mutex<Cell<Errors>> = Mutex::new(Cell::new(Errors::NONE));
+
+/// `Serial` with interrupt driven receive and transmit queues
+///
+/// There is only one USART, so the queues are static and there can be only
+/// one `BufferedSerial` at a time.
+pub struct BufferedSerial<IMODE: InputMode> {
+    serial: Serial<IMODE>,
+    rx: Consumer<'static, u16, RxBuffer>,
+    tx: Producer<'static, u8, TxBuffer>,
+    /// Rest of the last entry taken from `rx`, errors first
+    errors: Errors,
+    byte: Option<u8>,
+}
+
+impl<IMODE: InputMode> BufferedSerial<IMODE> {
+    /// Take over `serial` with empty queues. Reception starts right away,
+    /// but nothing happens until interrupts are enabled globally.
+    pub fn new(serial: Serial<IMODE>) -> BufferedSerial<IMODE> {
+        // Can't fail, the handles of an earlier `BufferedSerial` were
+        // dropped by `release` as it had to give up the `Serial`
+        let (rx_producer, mut rx) = RX.split().unwrap();
+        let (tx, mut tx_consumer) = TX.split().unwrap();
+
+        // Drop anything left from before
+        while rx.pop().is_some() {}
+        while tx_consumer.pop().is_some() {}
+
+        RX_PRODUCER.init(rx_producer).ok();
+        TX_CONSUMER.init(tx_consumer).ok();
+        interrupt::free(|cs| {
+            ERRORS.borrow(cs).set(Errors::NONE);
+            USART0
+                .ucsr0b
+                .modify_cs(cs, UCSR0B::RXCIE0::SET + UCSR0B::UDRIE0::CLEAR);
+        });
+        BufferedSerial {
+            serial,
+            rx,
+            tx,
+            errors: Errors::NONE,
+            byte: None,
+        }
+    }
+
+    /// Stop the interrupts and return the `Serial`, queued bytes which were
+    /// not sent or read are dropped
+    pub fn release(self) -> Serial<IMODE> {
+        USART0
+            .ucsr0b
+            .modify_atomic(UCSR0B::RXCIE0::CLEAR + UCSR0B::UDRIE0::CLEAR);
+        RX_PRODUCER.take();
+        TX_CONSUMER.take();
+        self.serial
+    }
+
+    /// Number of received bytes waiting to be read, errors between them
+    /// count as a byte each
+    pub fn available(&self) -> usize {
+        self.rx.len() + self.byte.is_some() as usize
+    }
+
+    /// Number of written bytes waiting to be sent
+    pub fn queued(&self) -> usize {
+        self.tx.len()
+    }
+}
+
+impl<IMODE: InputMode> serial::Read<u8> for BufferedSerial<IMODE> {
+    type Error = Error;
+
+    /// Next received byte. Errors are reported in order with the bytes, each
+    /// error once right before the first byte received after it.
+    fn read(&mut self) -> nb::Result<u8, Error> {
+        loop {
+            if let Some(error) = self.errors.take() {
+                return Err(nb::Error::Other(error));
+            }
+            if let Some(byte) = self.byte.take() {
+                return Ok(byte);
+            }
+            match self.rx.pop() {
+                Some(entry) => {
+                    let (errors, byte) = Received(entry).split();
+                    self.errors = errors;
+                    self.byte = byte;
+                }
+                None => {
+                    // Only left while the queue was full, so these errors
+                    // followed all bytes read so far
+                    self.errors = interrupt::free(|cs| ERRORS.borrow(cs).replace(Errors::NONE));
+                    if self.errors == Errors::NONE {
+                        return Err(nb::Error::WouldBlock);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<IMODE: InputMode> serial::Write<u8> for BufferedSerial<IMODE> {
+    type Error = Infallible;
+
+    /// Queue a byte, `WouldBlock` while the transmit queue is full
+    fn write(&mut self, byte: u8) -> nb::Result<(), Infallible> {
+        if self.tx.is_full() {
+            return Err(nb::Error::WouldBlock);
+        }
+        self.tx.try_push(byte).ok();
+        USART0.ucsr0b.modify_atomic(UCSR0B::UDRIE0::SET);
+        Ok(())
+    }
+
+    /// `WouldBlock` until all queued bytes have been handed to the USART
+    fn flush(&mut self) -> nb::Result<(), Infallible> {
+        if self.tx.is_empty() && USART0.ucsr0a.is_set(UCSR0A::UDRE0) {
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
+
+impl<IMODE: InputMode> ufmt::uWrite for BufferedSerial<IMODE> {
+    type Error = Infallible;
+
+    /// Queue the string, waiting only while the transmit queue is full
+    fn write_str(&mut self, s: &str) -> Result<(), Infallible> {
+        for &byte in s.as_bytes() {
+            nb::block!(serial::Write::write(self, byte))?;
+        }
+        Ok(())
+    }
+}
+
+#[avr_device::interrupt(atmega48p)]
+fn USART_RX() {
+    // The error flags belong to the byte in UDR0 and have to be read before
+    // it
+    let status = USART0.ucsr0a.extract();
+    let byte = USART0.udr0.get();
+
+    let mut errors = Errors::NONE;
+    if status.is_set(UCSR0A::DOR0) {
+        errors.insert(Error::Overrun);
+    }
+    let garbled = status.is_set(UCSR0A::FE0) || status.is_set(UCSR0A::UPE0);
+    if status.is_set(UCSR0A::FE0) {
+        errors.insert(Error::Framing);
+    } else if status.is_set(UCSR0A::UPE0) {
+        errors.insert(Error::Parity);
+    }
+
+    interrupt::free(|cs| {
+        let pending = ERRORS.borrow(cs);
+        errors.merge(pending.get());
+        let entry = if garbled {
+            Received::errors(errors)
+        } else {
+            Received::byte(errors, byte)
+        };
+        match RX_PRODUCER.lock_cs(cs, |_, rx| rx.try_push(entry.0).is_ok()) {
+            Some(true) => pending.set(Errors::NONE),
+            _ => {
+                if !garbled {
+                    errors.insert(Error::Overrun);
+                }
+                pending.set(errors);
+            }
+        }
+    });
+}
+
+#[avr_device::interrupt(atmega48p)]
+fn USART_UDRE() {
+    match TX_CONSUMER.lock(|_, tx| tx.pop()).and_then(|byte| byte) {
+        Some(byte) => USART0.udr0.set(byte),
+        // Nothing left, the interrupt fires as long as UDR0 is empty
+        None => USART0.ucsr0b.modify_atomic(UCSR0B::UDRIE0::CLEAR),
+    }
+}
@@ -0,0 +1,151 @@
+//! Interrupt driven serial port.
+//!
+//! [`BufferedSerial`] takes over a `Serial` and moves bytes between the
+//! USART and two lock-free [`queue`]s in the `USART_RX` and `USART_UDRE`
+//! interrupts, so writing only waits when the transmit queue is full and
+//! received bytes are kept until they are read. It is enabled with the `buffered-serial`
+//! feature, which claims both interrupts.
+//!
+//! ```no_run
+//! use m48_robo_rust::{prelude::*, serial::BufferedSerial};
+//!
+//! let dp = m48_robo_rust::Peripherals::take().unwrap();
+//! let mut pins = dp.PORTD.split();
+//! let serial = m48_robo_rust::Serial::new(
+//!     dp.USART0,
+//!     pins.pd0,
+//!     pins.pd1.into_output(&mut pins.ddr),
+//!     9600,
+//! );
+//!
+//! let mut serial = BufferedSerial::new(serial);
+//! unsafe { avr_device::interrupt::enable() };
+//!
+//! ufmt::uwriteln!(&mut serial, "Hello!\r").ok();
+//! match serial.read() {
+//!     Ok(byte) => {}
+//!     Err(nb::Error::WouldBlock) => {}
+//!     // A byte was lost or garbled before this point
+//!     Err(nb::Error::Other(error)) => {}
+//! }
+//! ```
+//!
+//! [`BufferedSerial`]: struct.BufferedSerial.html
+//! [`queue`]: ../queue/index.html
+
+#[cfg(all(target_arch = "avr", feature = "buffered-serial"))]
+mod buffered;
+#[cfg(all(target_arch = "avr", feature = "buffered-serial"))]
+pub use self::buffered::{BufferedSerial, RX_CAPACITY, TX_CAPACITY};
+
+/// Receive error
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Bytes were lost, because the USART or the receive queue was full
+    Overrun,
+    /// A byte had no valid stop bit, usually a baud rate mismatch
+    Framing,
+    /// A byte failed the parity check
+    Parity,
+}
+
+/// Errors waiting to be reported, each one at most once
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Errors(u8);
+
+// Only the interrupt driven serial port collects errors
+#[cfg_attr(
+    not(all(target_arch = "avr", feature = "buffered-serial")),
+    allow(dead_code)
+)]
+impl Errors {
+    const NONE: Errors = Errors(0);
+
+    fn mask(error: Error) -> u8 {
+        match error {
+            Error::Overrun => 1 << 0,
+            Error::Framing => 1 << 1,
+            Error::Parity => 1 << 2,
+        }
+    }
+
+    fn insert(&mut self, error: Error) {
+        self.0 |= Errors::mask(error);
+    }
+
+    fn merge(&mut self, other: Errors) {
+        self.0 |= other.0;
+    }
+
+    /// Remove and return the most severe error
+    fn take(&mut self) -> Option<Error> {
+        for &error in &[Error::Overrun, Error::Framing, Error::Parity] {
+            if self.0 & Errors::mask(error) != 0 {
+                self.0 &= !Errors::mask(error);
+                return Some(error);
+            }
+        }
+        None
+    }
+}
+
+/// Entry of the receive queue, the errors which occurred since the previous
+/// entry followed by a byte, or only errors if the byte was garbled. Keeping
+/// the errors in line with the bytes reports them exactly where they
+/// happened.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Received(u16);
+
+#[cfg_attr(
+    not(all(target_arch = "avr", feature = "buffered-serial")),
+    allow(dead_code)
+)]
+impl Received {
+    /// Set if the entry holds no byte
+    const NO_BYTE: u16 = 1 << 15;
+
+    fn byte(errors: Errors, byte: u8) -> Received {
+        Received((errors.0 as u16) << 8 | byte as u16)
+    }
+
+    fn errors(errors: Errors) -> Received {
+        Received(Received::NO_BYTE | (errors.0 as u16) << 8)
+    }
+
+    /// Errors to report before the byte, if any
+    fn split(self) -> (Errors, Option<u8>) {
+        let errors = Errors((self.0 >> 8) as u8 & 0x7F);
+        if self.0 & Received::NO_BYTE != 0 {
+            (errors, None)
+        } else {
+            (errors, Some(self.0 as u8))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_are_reported_once() {
+        let mut errors = Errors::NONE;
+        assert_eq!(errors.take(), None);
+        errors.insert(Error::Parity);
+        errors.insert(Error::Overrun);
+        errors.insert(Error::Overrun);
+        assert_eq!(errors.take(), Some(Error::Overrun));
+        assert_eq!(errors.take(), Some(Error::Parity));
+        assert_eq!(errors.take(), None);
+    }
+
+    #[test]
+    fn received_entries() {
+        let mut errors = Errors::NONE;
+        assert_eq!(Received::byte(errors, 0xFF).split(), (errors, Some(0xFF)));
+        errors.insert(Error::Framing);
+        errors.insert(Error::Overrun);
+        assert_eq!(Received::byte(errors, 0).split(), (errors, Some(0)));
+        assert_eq!(Received::errors(errors).split(), (errors, None));
+    }
+}
@@ -0,0 +1,552 @@
+//! Hobby servos on Timer1.
+//!
+//! A servo expects a pulse every 20 ms whose width, usually 1000 to 2000 µs,
+//! sets its position. [`Timer1`] runs Timer1 in fast PWM mode with `ICR1`
+//! as top for the 20 ms period and drives up to two servos from `OC1A`
+//! (`PB1`) and `OC1B` (`PB2`). The prescaler and top are derived from the
+//! CPU clock (see [`Timing`]), so pulses are right for every `clock-*`
+//! feature.
+//!
+//! Each [`Servo`] has a [`Calibration`] of its pulse widths and an optional
+//! speed limit, which ramps the pulse towards the target in [`Servo::update`]:
+//!
+//! ```no_run
+//! use m48_robo_rust::prelude::*;
+//! use m48_robo_rust::servo::{Calibration, Timer1};
+//!
+//! let dp = m48_robo_rust::Peripherals::take().unwrap();
+//! let mut portb = dp.PORTB.split();
+//!
+//! let mut timer = Timer1::new(dp.TC1);
+//! let pin = portb.pb1.into_output(&mut portb.ddr);
+//! let mut servo = timer.servo_a(pin, Calibration::new(600, 1450, 2400));
+//!
+//! servo.set_speed(60);
+//! servo.set_angle(45);
+//! while servo.is_moving() {
+//!     servo.update(20);
+//!     m48_robo_rust::delay_ms(20);
+//! }
+//! ```
+//!
+//! More servos than Timer1 has outputs are driven in software by [`multi`].
+//!
+//! [`Timer1`]: struct.Timer1.html
+//! [`Timing`]: struct.Timing.html
+//! [`Servo`]: struct.Servo.html
+//! [`Calibration`]: struct.Calibration.html
+//! [`Servo::update`]: struct.Servo.html#method.update
+//! [`multi`]: multi/index.html
+
+pub mod multi;
+
+/// Servo pulse period in µs
+pub const PERIOD_US: u32 = 20_000;
+
+/// Prescaler shifts of Timer1: 1, 8, 64, 256 and 1024
+const PRESCALER_SHIFTS: [u8; 5] = [0, 3, 6, 8, 10];
+
+/// Fraction bits of `Timing::scale`
+const SCALE_BITS: u32 = 14;
+
+/// Timer1 setup for the servo period
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timing {
+    /// Prescaler shift, the timer counts at `cpu_hz >> shift`
+    pub shift: u8,
+    /// Counts per period, `ICR1` is one less
+    pub top: u16,
+    /// Counts per µs with `SCALE_BITS` fraction bits
+    scale: u32,
+}
+
+impl Timing {
+    /// Timing with the finest resolution for `period_us` at `cpu_hz`, `None`
+    /// if the period doesn't fit even with the largest prescaler
+    pub fn new(cpu_hz: u32, period_us: u32) -> Option<Timing> {
+        PRESCALER_SHIFTS.iter().find_map(|&shift| {
+            let tick_hz = (cpu_hz >> shift) as u64;
+            let top = (tick_hz * period_us as u64 + 500_000) / 1_000_000;
+            if top > u16::MAX as u64 || top < 2 {
+                return None;
+            }
+            let scale = ((tick_hz << SCALE_BITS) + 500_000) / 1_000_000;
+            Some(Timing {
+                shift,
+                top: top as u16,
+                scale: scale as u32,
+            })
+        })
+    }
+
+    /// Timer counts for `us`, at most `top - 1` as the counter never reaches
+    /// `top`
+    pub fn ticks(&self, us: u16) -> u16 {
+        let ticks = (us as u32 * self.scale + (1 << (SCALE_BITS - 1))) >> SCALE_BITS;
+        if ticks >= self.top as u32 {
+            self.top - 1
+        } else {
+            ticks as u16
+        }
+    }
+}
+
+/// Pulse widths of a servo in µs
+///
+/// Angles from -90° to 90° map linearly from `min` through `center` to
+/// `max`, so a servo can be trimmed with `center` alone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Calibration {
+    pub min: u16,
+    pub center: u16,
+    pub max: u16,
+}
+
+impl Calibration {
+    /// The common 1000 to 2000 µs range
+    pub const DEFAULT: Calibration = Calibration::new(1000, 1500, 2000);
+
+    pub const fn new(min: u16, center: u16, max: u16) -> Calibration {
+        Calibration { min, center, max }
+    }
+
+    /// `us` limited to `min..=max`
+    pub fn clamp(&self, us: u16) -> u16 {
+        if us < self.min {
+            self.min
+        } else if us > self.max {
+            self.max
+        } else {
+            us
+        }
+    }
+
+    /// Pulse width for `degrees`, limited to -90° to 90°
+    pub fn pulse(&self, degrees: i16) -> u16 {
+        let (span, degrees) = if degrees < 0 {
+            (
+                self.center as i32 - self.min as i32,
+                degrees.max(-90) as i32,
+            )
+        } else {
+            (self.max as i32 - self.center as i32, degrees.min(90) as i32)
+        };
+        self.clamp((self.center as i32 + span * degrees / 90) as u16)
+    }
+}
+
+impl Default for Calibration {
+    fn default() -> Calibration {
+        Calibration::DEFAULT
+    }
+}
+
+/// Pulse width moving towards a target at a limited speed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ramp {
+    current: u16,
+    target: u16,
+    /// µs per second, 0 for no limit
+    speed: u16,
+    /// Movement not applied yet in µs/1000
+    remainder: u32,
+}
+
+impl Ramp {
+    /// Ramp standing at `us`
+    pub const fn new(us: u16) -> Ramp {
+        Ramp {
+            current: us,
+            target: us,
+            speed: 0,
+            remainder: 0,
+        }
+    }
+
+    pub fn current(&self) -> u16 {
+        self.current
+    }
+
+    pub fn target(&self) -> u16 {
+        self.target
+    }
+
+    /// Limit the speed to `us_per_s`, 0 removes the limit
+    pub fn set_speed(&mut self, us_per_s: u16) {
+        self.speed = us_per_s;
+    }
+
+    /// Move towards `us`, right away without a speed limit
+    pub fn set_target(&mut self, us: u16) {
+        self.target = us;
+        if self.speed == 0 {
+            self.current = us;
+        }
+    }
+
+    /// Advance by `elapsed_ms` and return the current pulse width
+    pub fn update(&mut self, elapsed_ms: u16) -> u16 {
+        if self.current == self.target {
+            self.remainder = 0;
+            return self.current;
+        }
+        if self.speed == 0 {
+            self.current = self.target;
+            return self.current;
+        }
+        let travel = self.remainder + self.speed as u32 * elapsed_ms as u32;
+        let step = travel / 1000;
+        self.remainder = travel % 1000;
+        let (low, high) = if self.target > self.current {
+            (self.current, self.target)
+        } else {
+            (self.target, self.current)
+        };
+        if step >= (high - low) as u32 {
+            self.current = self.target;
+            self.remainder = 0;
+        } else if self.target > self.current {
+            self.current += step as u16;
+        } else {
+            self.current -= step as u16;
+        }
+        self.current
+    }
+}
+
+/// Compare output a servo pulse is generated on
+pub trait Channel {
+    /// Set the pulse width in timer counts
+    fn set_ticks(&mut self, ticks: u16);
+}
+
+/// Servo on a `Channel`
+pub struct Servo<C> {
+    channel: C,
+    timing: Timing,
+    calibration: Calibration,
+    ramp: Ramp,
+}
+
+impl<C: Channel> Servo<C> {
+    /// Servo on `channel`, starting at the center
+    pub fn new(channel: C, timing: Timing, calibration: Calibration) -> Servo<C> {
+        let mut servo = Servo {
+            channel,
+            timing,
+            calibration,
+            ramp: Ramp::new(calibration.center),
+        };
+        servo.output();
+        servo
+    }
+
+    /// Give back the channel
+    pub fn release(self) -> C {
+        self.channel
+    }
+
+    pub fn calibration(&self) -> Calibration {
+        self.calibration
+    }
+
+    /// Change the calibration, the target stays within the new limits
+    pub fn set_calibration(&mut self, calibration: Calibration) {
+        self.calibration = calibration;
+        let target = calibration.clamp(self.ramp.target());
+        self.set_pulse(target);
+    }
+
+    /// Move to `degrees` from the center, -90° to 90°
+    pub fn set_angle(&mut self, degrees: i16) {
+        let us = self.calibration.pulse(degrees);
+        self.set_pulse(us);
+    }
+
+    /// Move to the pulse width `us`, limited by the calibration
+    pub fn set_pulse(&mut self, us: u16) {
+        self.ramp.set_target(self.calibration.clamp(us));
+        self.output();
+    }
+
+    /// Limit the speed to about `degrees_per_s`, 0 removes the limit. A
+    /// calibration with `max` below `min` has no range, any limit then is the
+    /// slowest speed.
+    pub fn set_speed(&mut self, degrees_per_s: u16) {
+        let range = self.calibration.max.saturating_sub(self.calibration.min) as u32;
+        let us_per_s = degrees_per_s as u32 * range / 180;
+        self.ramp.set_speed(if us_per_s > u16::MAX as u32 {
+            u16::MAX
+        } else if us_per_s == 0 && degrees_per_s > 0 {
+            1
+        } else {
+            us_per_s as u16
+        });
+    }
+
+    /// Advance a speed limited movement by `elapsed_ms`
+    pub fn update(&mut self, elapsed_ms: u16) {
+        self.ramp.update(elapsed_ms);
+        self.output();
+    }
+
+    /// Current pulse width in µs
+    pub fn pulse(&self) -> u16 {
+        self.ramp.current()
+    }
+
+    /// Pulse width the servo moves to in µs
+    pub fn target(&self) -> u16 {
+        self.ramp.target()
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.ramp.current() != self.ramp.target()
+    }
+
+    fn output(&mut self) {
+        let ticks = self.timing.ticks(self.ramp.current());
+        self.channel.set_ticks(ticks);
+    }
+}
+
+#[cfg(target_arch = "avr")]
+pub use self::timer1::{Oc1a, Oc1b, Timer1};
+
+#[cfg(target_arch = "avr")]
+mod timer1 {
+    use super::{Calibration, Channel, Servo, Timing, PERIOD_US};
+    use crate::atmega48p;
+    use crate::clock;
+    use crate::dev::tc1::{TC1, TCCR1A, TCCR1B};
+    use crate::hal::port::{
+        mode::Output,
+        portb::{PB1, PB2},
+    };
+    use crate::mmio::bitopts::FieldValue;
+
+    /// Clock select of the prescaler of `timing`
+    pub(super) fn clock_select(timing: &Timing) -> FieldValue<u8, TCCR1B::Register> {
+        match timing.shift {
+            0 => TCCR1B::CS1::Direct,
+            3 => TCCR1B::CS1::Prescale8,
+            6 => TCCR1B::CS1::Prescale64,
+            8 => TCCR1B::CS1::Prescale256,
+            _ => TCCR1B::CS1::Prescale1024,
+        }
+    }
+
+    /// Timer1 generating the servo period
+    pub struct Timer1 {
+        tc1: atmega48p::TC1,
+        timing: Timing,
+    }
+
+    impl Timer1 {
+        /// Start Timer1 with a 20 ms period for the current CPU clock,
+        /// both outputs stay disconnected until a servo is attached
+        pub fn new(tc1: atmega48p::TC1) -> Timer1 {
+            // Even 16 MHz gives 40000 counts with prescaler 8
+            let timing = Timing::new(clock::frequency(), PERIOD_US).unwrap();
+            // Fast PWM with ICR1 as top (mode 14)
+            TC1.tccr1b.write(TCCR1B::CS1::Stopped);
+            TC1.icr1.set(timing.top - 1);
+            TC1.ocr1a.set(0);
+            TC1.ocr1b.set(0);
+            TC1.tcnt1.set(0);
+            TC1.tccr1a.write(TCCR1A::WGM1.val(0b10));
+            TC1.tccr1b
+                .write(TCCR1B::WGM1.val(0b11) + clock_select(&timing));
+            Timer1 { tc1, timing }
+        }
+
+        pub fn timing(&self) -> Timing {
+            self.timing
+        }
+
+        /// Servo on `OC1A` (`PB1`)
+        pub fn servo_a(&mut self, pin: PB1<Output>, calibration: Calibration) -> Servo<Oc1a> {
+            TC1.tccr1a.modify(TCCR1A::COM1A::Clear);
+            Servo::new(Oc1a { pin }, self.timing, calibration)
+        }
+
+        /// Servo on `OC1B` (`PB2`)
+        pub fn servo_b(&mut self, pin: PB2<Output>, calibration: Calibration) -> Servo<Oc1b> {
+            TC1.tccr1a.modify(TCCR1A::COM1B::Clear);
+            Servo::new(Oc1b { pin }, self.timing, calibration)
+        }
+
+        /// Stop the timer, attached servos stop getting pulses
+        pub fn release(self) -> atmega48p::TC1 {
+            TC1.tccr1b.write(TCCR1B::CS1::Stopped);
+            TC1.tccr1a
+                .write(TCCR1A::COM1A::Disconnected + TCCR1A::COM1B::Disconnected);
+            self.tc1
+        }
+    }
+
+    /// `OC1A` output
+    pub struct Oc1a {
+        pin: PB1<Output>,
+    }
+
+    impl Oc1a {
+        /// Disconnect the output and give back the pin
+        pub fn release(self) -> PB1<Output> {
+            TC1.tccr1a.modify(TCCR1A::COM1A::Disconnected);
+            self.pin
+        }
+    }
+
+    impl Channel for Oc1a {
+        fn set_ticks(&mut self, ticks: u16) {
+            // OCR1A is double buffered in fast PWM, so the pulse never glitches
+            TC1.ocr1a.set(ticks);
+        }
+    }
+
+    /// `OC1B` output
+    pub struct Oc1b {
+        pin: PB2<Output>,
+    }
+
+    impl Oc1b {
+        /// Disconnect the output and give back the pin
+        pub fn release(self) -> PB2<Output> {
+            TC1.tccr1a.modify(TCCR1A::COM1B::Disconnected);
+            self.pin
+        }
+    }
+
+    impl Channel for Oc1b {
+        fn set_ticks(&mut self, ticks: u16) {
+            TC1.ocr1b.set(ticks);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    struct Mock(Vec<u16>);
+
+    impl Channel for Mock {
+        fn set_ticks(&mut self, ticks: u16) {
+            self.0.push(ticks);
+        }
+    }
+
+    #[test]
+    fn timing_for_each_clock() {
+        // 1 MHz: 1 µs resolution without prescaler
+        let timing = Timing::new(1_000_000, PERIOD_US).unwrap();
+        assert_eq!((timing.shift, timing.top), (0, 20_000));
+        assert_eq!(timing.ticks(1500), 1500);
+
+        // 8 MHz needs prescaler 8
+        let timing = Timing::new(8_000_000, PERIOD_US).unwrap();
+        assert_eq!((timing.shift, timing.top), (3, 20_000));
+        assert_eq!(timing.ticks(1000), 1000);
+
+        // 16 MHz: 0.5 µs resolution
+        let timing = Timing::new(16_000_000, PERIOD_US).unwrap();
+        assert_eq!((timing.shift, timing.top), (3, 40_000));
+        assert_eq!(timing.ticks(1500), 3000);
+        assert_eq!(timing.ticks(u16::MAX), 39_999);
+
+        // The example's old setup: 1 MHz / 8 with 2500 counts
+        let timing = Timing::new(1_000_000 >> 3, PERIOD_US).unwrap();
+        assert_eq!((timing.shift, timing.top), (0, 2500));
+        assert_eq!(timing.ticks(640), 80);
+
+        assert_eq!(Timing::new(100_000_000, 1_000_000), None);
+    }
+
+    #[test]
+    fn calibration() {
+        let calibration = Calibration::new(600, 1400, 2400);
+        assert_eq!(calibration.pulse(0), 1400);
+        assert_eq!(calibration.pulse(-90), 600);
+        assert_eq!(calibration.pulse(90), 2400);
+        assert_eq!(calibration.pulse(-45), 1000);
+        assert_eq!(calibration.pulse(45), 1900);
+        assert_eq!(calibration.pulse(i16::MIN), 600);
+        assert_eq!(calibration.pulse(i16::MAX), 2400);
+        assert_eq!(calibration.clamp(100), 600);
+        assert_eq!(Calibration::default().pulse(18), 1600);
+    }
+
+    #[test]
+    fn ramp() {
+        let mut ramp = Ramp::new(1500);
+        ramp.set_target(2000);
+        assert_eq!(ramp.current(), 2000);
+
+        // 100 µs/s in 20 ms steps is 2 µs per step
+        ramp.set_speed(100);
+        ramp.set_target(1990);
+        assert_eq!(ramp.update(20), 1998);
+        assert_eq!(ramp.update(20), 1996);
+        for _ in 0..2 {
+            ramp.update(20);
+        }
+        assert_eq!(ramp.update(20), 1990);
+        assert_eq!(ramp.update(20), 1990);
+
+        // Slow speeds carry the remainder over
+        ramp.set_speed(30);
+        ramp.set_target(2000);
+        let steps: Vec<u16> = (0..5).map(|_| ramp.update(10)).collect();
+        assert_eq!(steps, [1990, 1990, 1990, 1991, 1991]);
+        let mut updates = 5;
+        while ramp.current() != ramp.target() {
+            ramp.update(10);
+            updates += 1;
+        }
+        // 10 µs at 30 µs/s take a third of a second
+        assert_eq!(updates, 34);
+    }
+
+    #[test]
+    fn servo() {
+        let timing = Timing::new(16_000_000, PERIOD_US).unwrap();
+        let mut servo = Servo::new(Mock(Vec::new()), timing, Calibration::DEFAULT);
+        assert_eq!(servo.pulse(), 1500);
+
+        servo.set_angle(90);
+        assert_eq!(servo.pulse(), 2000);
+        servo.set_pulse(500);
+        assert_eq!(servo.pulse(), 1000);
+
+        // 90°/s over the 1000 µs range is 500 µs/s
+        servo.set_speed(90);
+        servo.set_angle(0);
+        assert!(servo.is_moving());
+        servo.update(100);
+        assert_eq!((servo.pulse(), servo.target()), (1050, 1500));
+        for _ in 0..9 {
+            servo.update(100);
+        }
+        assert!(!servo.is_moving());
+
+        servo.set_calibration(Calibration::new(1600, 1800, 2000));
+        assert_eq!(servo.target(), 1600);
+        // Timer counts at 16 MHz are half µs
+        let ticks = servo.release().0;
+        assert_eq!(ticks[..5], [3000, 4000, 2000, 2000, 2100]);
+        assert_eq!(ticks.last(), Some(&3000));
+    }
+
+    #[test]
+    fn speed_of_inverted_calibration() {
+        let timing = Timing::new(16_000_000, PERIOD_US).unwrap();
+        let mut servo = Servo::new(Mock(Vec::new()), timing, Calibration::new(2000, 1500, 1000));
+        servo.set_speed(90);
+        servo.set_pulse(1000);
+        servo.update(1000);
+        // Every target is clamped to `min`, approached at 1 µs/s
+        assert_eq!((servo.pulse(), servo.target()), (1501, 2000));
+    }
+}
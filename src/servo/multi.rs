@@ -0,0 +1,656 @@
+//! Software generated servo pulses on any GPIO pins.
+//!
+//! With the `servo-multi` feature, [`Timer1`] runs Timer1 in CTC mode with
+//! `ICR1` as top for the 20 ms frame and claims the `TIMER1_CAPT` and
+//! `TIMER1_COMPA` interrupts. Every frame starts by raising all servo pins
+//! at once, then the pins are dropped in the order of their pulse widths,
+//! one `OCR1A` compare match per [`Step`]. Pulses ending too close after
+//! each other for another interrupt are chained into the same one, which
+//! waits for the timer count instead.
+//!
+//! The servos themselves live in a [`Bank`] over a caller provided table of
+//! [`Slot`]s, each taking about 25 bytes of RAM on the MCU. Changes reach
+//! the pins once the bank is handed over with [`Timer1::flush`]:
+//!
+//! ```no_run
+//! use m48_robo_rust::servo::{multi::{Slot, Timer1}, Calibration};
+//! use m48_robo_rust::gpio::{mode::Output, Pin, Pins};
+//!
+//! let dp = m48_robo_rust::Peripherals::take().unwrap();
+//! let mut pins = Pins::take().unwrap();
+//!
+//! let timer = Timer1::new(dp.TC1);
+//! let mut slots: [Slot<Pin<Output>>; 2] = Default::default();
+//! let mut servos = timer.bank(&mut slots);
+//! let hip = servos
+//!     .attach(pins.portc.pc0.into_output().downgrade(), Calibration::DEFAULT)
+//!     .ok()
+//!     .unwrap();
+//! unsafe { avr_device::interrupt::enable() };
+//!
+//! servos[hip].set_angle(30);
+//! nb::block!(timer.flush(&servos)).ok();
+//! ```
+//!
+//! [`Timer1`]: struct.Timer1.html
+//! [`Timer1::flush`]: struct.Timer1.html#method.flush
+//! [`Step`]: struct.Step.html
+//! [`Bank`]: struct.Bank.html
+//! [`Slot`]: type.Slot.html
+
+use core::ops::{Index, IndexMut};
+
+use super::{Calibration, Channel, Servo, Timing};
+
+/// Servos a `Frame` can hold
+pub const MAX_SERVOS: usize = 12;
+
+/// GPIO ports servo pins can be on
+pub const PORTS: usize = 3;
+
+/// Pin of a servo as a port index and the bit mask inside that port
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Line {
+    /// Port index, below `PORTS`
+    pub port: u8,
+    pub mask: u8,
+}
+
+/// Pin a `Bank` can generate pulses on
+pub trait ServoPin {
+    fn line(&self) -> Line;
+}
+
+/// Falling edge of one or more pulses
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Step {
+    /// Timer count the pins are dropped at
+    pub at: u16,
+    /// Pins to drop per port
+    pub clear: [u8; PORTS],
+    /// Too close to the previous step (or the frame start) for its own
+    /// interrupt, handled right after it instead
+    pub chained: bool,
+}
+
+const NO_STEP: Step = Step {
+    at: 0,
+    clear: [0; PORTS],
+    chained: false,
+};
+
+/// Pulses of one frame, sorted by their widths
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Frame {
+    /// Pins to raise per port at the frame start
+    pub set: [u8; PORTS],
+    steps: [Step; MAX_SERVOS],
+    len: u8,
+}
+
+impl Frame {
+    /// Frame without pulses
+    pub const EMPTY: Frame = Frame {
+        set: [0; PORTS],
+        steps: [NO_STEP; MAX_SERVOS],
+        len: 0,
+    };
+
+    /// Frame for `pulses` of a line and its width in timer counts. Pulses of
+    /// the same width share a step and steps less than `gap` counts apart
+    /// are chained.
+    ///
+    /// Pulses of zero counts are left out, as are pulses of more than
+    /// `MAX_SERVOS` different widths.
+    pub fn new<I>(pulses: I, gap: u16) -> Frame
+    where
+        I: IntoIterator<Item = (Line, u16)>,
+    {
+        let mut frame = Frame::EMPTY;
+        for (line, ticks) in pulses {
+            if ticks == 0 {
+                continue;
+            }
+            let len = frame.len as usize;
+            let i = frame.steps[..len]
+                .iter()
+                .position(|step| step.at >= ticks)
+                .unwrap_or(len);
+            if i == len || frame.steps[i].at != ticks {
+                if len == MAX_SERVOS {
+                    continue;
+                }
+                frame.steps.copy_within(i..len, i + 1);
+                frame.steps[i] = Step {
+                    at: ticks,
+                    ..NO_STEP
+                };
+                frame.len += 1;
+            }
+            let port = line.port as usize;
+            frame.steps[i].clear[port] |= line.mask;
+            frame.set[port] |= line.mask;
+        }
+
+        let mut previous = 0;
+        for step in &mut frame.steps[..frame.len as usize] {
+            step.chained = step.at - previous < gap;
+            previous = step.at;
+        }
+        frame
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps[..self.len as usize]
+    }
+}
+
+/// Timer count up to which a `Sequencer` for `timing` takes new frames.
+///
+/// Loading a frame may take a few hundred cycles, it must be done well before
+/// the next frame start.
+pub fn window(timing: &Timing) -> u16 {
+    timing.top - timing.top / 8
+}
+
+/// Position of the interrupts within the current frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sequencer {
+    frame: Frame,
+    next: u8,
+    /// Timer count up to which a new frame can be loaded
+    window: u16,
+}
+
+impl Sequencer {
+    /// Sequencer without pulses, taking new frames while the timer count is
+    /// below `window`
+    pub fn new(window: u16) -> Sequencer {
+        Sequencer {
+            frame: Frame::EMPTY,
+            next: 0,
+            window,
+        }
+    }
+
+    pub fn frame(&self) -> &Frame {
+        &self.frame
+    }
+
+    /// Start a frame, returns the pins to raise per port
+    pub fn start(&mut self) -> [u8; PORTS] {
+        self.next = 0;
+        self.frame.set
+    }
+
+    /// Next step of the frame, without moving on
+    pub fn peek(&self) -> Option<Step> {
+        self.frame.steps().get(self.next as usize).copied()
+    }
+
+    /// Move on to the next step of the frame
+    pub fn step(&mut self) -> Option<Step> {
+        let step = self.peek()?;
+        self.next += 1;
+        Some(step)
+    }
+
+    /// Whether all pulses of the frame have ended
+    pub fn is_idle(&self) -> bool {
+        self.next == self.frame.len
+    }
+
+    /// Replace the frame from the next frame start on.
+    ///
+    /// Only done between the end of the last pulse and `window` at the
+    /// timer count `now`, so copying the frame with interrupts masked never
+    /// delays an edge. Returns whether `frame` was taken.
+    pub fn load(&mut self, frame: &Frame, now: u16) -> bool {
+        if !self.is_idle() || now >= self.window {
+            return false;
+        }
+        self.frame = *frame;
+        self.next = frame.len;
+        true
+    }
+}
+
+/// Handle of a servo attached to a `Bank`, the index of its slot
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ServoId(u8);
+
+/// Channel of the servos in a `Bank`, their pulses are generated from the
+/// bank's `Frame`
+#[derive(Debug)]
+pub struct SoftChannel;
+
+impl Channel for SoftChannel {
+    fn set_ticks(&mut self, _ticks: u16) {}
+}
+
+/// Entry of the servo table, `None` for a free slot
+pub type Slot<P> = Option<(P, Servo<SoftChannel>)>;
+
+/// Servos on a caller provided table of slots
+pub struct Bank<'a, P> {
+    slots: &'a mut [Slot<P>],
+    timing: Timing,
+    gap: u16,
+}
+
+impl<'a, P: ServoPin> Bank<'a, P> {
+    /// Bank for pulses of `timing`, steps less than `gap` counts apart are
+    /// chained. Only the first `MAX_SERVOS` slots are used.
+    pub fn new(slots: &'a mut [Slot<P>], timing: Timing, gap: u16) -> Bank<'a, P> {
+        let len = if slots.len() > MAX_SERVOS {
+            MAX_SERVOS
+        } else {
+            slots.len()
+        };
+        Bank {
+            slots: &mut slots[..len],
+            timing,
+            gap,
+        }
+    }
+
+    /// Attach a servo on `pin` to the first free slot, starting at the
+    /// center. Gives the pin back if all slots are taken.
+    pub fn attach(&mut self, pin: P, calibration: Calibration) -> Result<ServoId, P> {
+        match self.slots.iter().position(Option::is_none) {
+            Some(i) => {
+                let servo = Servo::new(SoftChannel, self.timing, calibration);
+                self.slots[i] = Some((pin, servo));
+                Ok(ServoId(i as u8))
+            }
+            None => Err(pin),
+        }
+    }
+
+    /// Remove a servo and give back its pin, which keeps getting pulses
+    /// until the bank is flushed
+    pub fn detach(&mut self, id: ServoId) -> Option<P> {
+        let slot = self.slots.get_mut(id.0 as usize)?;
+        slot.take().map(|(pin, _)| pin)
+    }
+
+    /// Advance the speed limited movements by `elapsed_ms`
+    pub fn update(&mut self, elapsed_ms: u16) {
+        for (_, servo) in self.slots.iter_mut().flatten() {
+            servo.update(elapsed_ms);
+        }
+    }
+
+    /// Whether any servo is still moving towards its target
+    pub fn is_moving(&self) -> bool {
+        self.slots
+            .iter()
+            .flatten()
+            .any(|(_, servo)| servo.is_moving())
+    }
+
+    /// Frame of the current pulses, which end at the latest `gap` counts
+    /// before the `window` closes
+    pub fn frame(&self) -> Frame {
+        // Leaves the interrupts time to finish the last step, so the next
+        // frame can still be loaded after it
+        let last = window(&self.timing) - self.gap;
+        let timing = &self.timing;
+        let pulses = self.slots.iter().flatten().map(|(pin, servo)| {
+            let ticks = timing.ticks(servo.pulse());
+            (pin.line(), if ticks > last { last } else { ticks })
+        });
+        Frame::new(pulses, self.gap)
+    }
+}
+
+impl<'a, P> Index<ServoId> for Bank<'a, P> {
+    type Output = Servo<SoftChannel>;
+
+    /// Panics if the servo was detached
+    fn index(&self, id: ServoId) -> &Servo<SoftChannel> {
+        match &self.slots[id.0 as usize] {
+            Some((_, servo)) => servo,
+            None => panic!("servo was detached"),
+        }
+    }
+}
+
+impl<'a, P> IndexMut<ServoId> for Bank<'a, P> {
+    fn index_mut(&mut self, id: ServoId) -> &mut Servo<SoftChannel> {
+        match &mut self.slots[id.0 as usize] {
+            Some((_, servo)) => servo,
+            None => panic!("servo was detached"),
+        }
+    }
+}
+
+#[cfg(all(target_arch = "avr", feature = "servo-multi"))]
+pub use self::timer1::Timer1;
+
+#[cfg(all(target_arch = "avr", feature = "servo-multi"))]
+mod timer1 {
+    use core::convert::Infallible;
+
+    use super::super::{timer1::clock_select, Timing, PERIOD_US};
+    use super::{window, Bank, Line, Sequencer, ServoPin, Slot, PORTS};
+    use crate::atmega48p;
+    use crate::clock;
+    use crate::dev::tc1::{TC1, TCCR1A, TCCR1B, TIFR1, TIMSK1};
+    use crate::gpio::{mode::Output, Pin, Port};
+    use crate::interrupt::{self, Shared};
+
+    /// Cycles from a compare match until the next one can be set up, a
+    /// generous guess at the interrupt entry, locking and exit
+    const ISR_CYCLES: u32 = 120;
+
+    /// Ports in the order of `Line::port`
+    const GPIO_PORTS: [Port; PORTS] = [Port::B, Port::C, Port::D];
+
+    static SEQUENCER: Shared<Sequencer> = Shared::new();
+
+    impl ServoPin for Pin<Output> {
+        fn line(&self) -> Line {
+            let port = match self.port() {
+                Port::B => 0,
+                Port::C => 1,
+                Port::D => 2,
+            };
+            Line {
+                port,
+                mask: self.mask(),
+            }
+        }
+    }
+
+    /// Timer1 sequencing the pulses of a `Bank`
+    pub struct Timer1 {
+        tc1: atmega48p::TC1,
+        timing: Timing,
+    }
+
+    impl Timer1 {
+        /// Start Timer1 with a 20 ms frame for the current CPU clock. Pulses
+        /// start once a bank was flushed and interrupts are enabled globally.
+        pub fn new(tc1: atmega48p::TC1) -> Timer1 {
+            let timing = Timing::new(clock::frequency(), PERIOD_US).unwrap();
+            SEQUENCER.take();
+            SEQUENCER.init(Sequencer::new(window(&timing))).ok();
+
+            // CTC with ICR1 as top (mode 12), which raises ICF1 at the top
+            TC1.tccr1b.write(TCCR1B::CS1::Stopped);
+            TC1.tccr1a.write(TCCR1A::WGM1.val(0b00));
+            TC1.icr1.set(timing.top - 1);
+            TC1.tcnt1.set(0);
+            TIFR1.write(TIFR1::ICF1::SET + TIFR1::OCF1A::SET);
+            TIMSK1.modify(TIMSK1::ICIE1::SET + TIMSK1::OCIE1A::SET);
+            TC1.tccr1b
+                .write(TCCR1B::WGM1.val(0b11) + clock_select(&timing));
+            Timer1 { tc1, timing }
+        }
+
+        pub fn timing(&self) -> Timing {
+            self.timing
+        }
+
+        /// Empty bank on `slots` with the timing of this timer
+        pub fn bank<'a>(&self, slots: &'a mut [Slot<Pin<Output>>]) -> Bank<'a, Pin<Output>> {
+            let gap = (ISR_CYCLES >> self.timing.shift) as u16 + 1;
+            Bank::new(slots, self.timing, gap)
+        }
+
+        /// Hand the current pulses of `bank` to the interrupts, they take
+        /// effect with the next frame.
+        ///
+        /// Returns `WouldBlock` while pulses are running or the next frame
+        /// is about to start, for at most one frame.
+        pub fn flush(&self, bank: &Bank<Pin<Output>>) -> nb::Result<(), Infallible> {
+            let frame = bank.frame();
+            let loaded = SEQUENCER.lock(|_, sequencer| {
+                let now = TC1.tcnt1.get();
+                // A pending frame start would be delayed by the copy
+                !TIFR1.is_set(TIFR1::ICF1) && sequencer.load(&frame, now)
+            });
+            match loaded {
+                Some(true) => Ok(()),
+                _ => Err(nb::Error::WouldBlock),
+            }
+        }
+
+        /// Stop the timer and its interrupts, all servo pins are left low
+        pub fn release(self) -> atmega48p::TC1 {
+            TC1.tccr1b.write(TCCR1B::CS1::Stopped);
+            TIMSK1.modify(TIMSK1::ICIE1::CLEAR + TIMSK1::OCIE1A::CLEAR);
+            if let Some(sequencer) = SEQUENCER.take() {
+                interrupt::free(|_| {
+                    for (port, &mask) in GPIO_PORTS.iter().zip(&sequencer.frame().set) {
+                        port.set_low(mask);
+                    }
+                });
+            }
+            self.tc1
+        }
+    }
+
+    /// Set up the compare match of a step at `at`. Returns `false` if the
+    /// count has already reached it, the step then is due right away: ending
+    /// the pulse late beats ending it a frame late.
+    #[inline(always)]
+    fn schedule(at: u16) -> bool {
+        TC1.ocr1a.set(at);
+        if TC1.tcnt1.get() < at {
+            return true;
+        }
+        // A match right after the write would run the following step early
+        TIFR1.write(TIFR1::OCF1A::SET);
+        false
+    }
+
+    /// Drop the pins of the due step and of the steps chained to it, then
+    /// set up the compare match of the next one
+    #[inline(always)]
+    fn edges(sequencer: &mut Sequencer) {
+        while let Some(step) = sequencer.step() {
+            while TC1.tcnt1.get() < step.at {}
+            for (port, &mask) in GPIO_PORTS.iter().zip(&step.clear) {
+                if mask != 0 {
+                    port.set_low(mask);
+                }
+            }
+            match sequencer.peek() {
+                Some(next) if next.chained => {}
+                Some(next) => {
+                    if schedule(next.at) {
+                        return;
+                    }
+                }
+                None => return,
+            }
+        }
+    }
+
+    #[avr_device::interrupt(atmega48p)]
+    fn TIMER1_CAPT() {
+        SEQUENCER.lock(|_, sequencer| {
+            let set = sequencer.start();
+            for (port, &mask) in GPIO_PORTS.iter().zip(&set) {
+                if mask != 0 {
+                    port.set_high(mask);
+                }
+            }
+            match sequencer.peek() {
+                Some(step) if step.chained => edges(sequencer),
+                Some(step) => {
+                    // The handler may have started late
+                    if !schedule(step.at) {
+                        edges(sequencer);
+                    }
+                }
+                None => {}
+            }
+        });
+    }
+
+    #[avr_device::interrupt(atmega48p)]
+    fn TIMER1_COMPA() {
+        SEQUENCER.lock(|_, sequencer| edges(sequencer));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::servo::PERIOD_US;
+    use std::vec::Vec;
+
+    struct Mock(Line);
+
+    impl ServoPin for Mock {
+        fn line(&self) -> Line {
+            self.0
+        }
+    }
+
+    fn line(port: u8, bit: u8) -> Line {
+        Line {
+            port,
+            mask: 1 << bit,
+        }
+    }
+
+    #[test]
+    fn sorted_steps() {
+        let pulses = [
+            (line(0, 1), 3000),
+            (line(1, 0), 2000),
+            (line(2, 7), 4000),
+            (line(1, 3), 3000),
+            // Off
+            (line(2, 0), 0),
+            (line(1, 5), 2010),
+        ];
+        let frame = Frame::new(pulses.iter().copied(), 16);
+        assert_eq!(frame.set, [0b10, 0b10_1001, 0x80]);
+        let steps: Vec<_> = frame
+            .steps()
+            .iter()
+            .map(|step| (step.at, step.clear, step.chained))
+            .collect();
+        assert_eq!(
+            steps,
+            [
+                (2000, [0, 0b1, 0], false),
+                (2010, [0, 0b10_0000, 0], true),
+                (3000, [0b10, 0b1000, 0], false),
+                (4000, [0, 0, 0x80], false),
+            ]
+        );
+
+        // Steps right after the frame start are chained as well
+        assert!(Frame::new(Some((line(0, 0), 10)), 16).steps()[0].chained);
+        assert_eq!(Frame::new(None, 16), Frame::EMPTY);
+    }
+
+    #[test]
+    fn frame_capacity() {
+        let pulses = (0..20).map(|i| (line(i % 3, i / 3), 1000 + i as u16));
+        let frame = Frame::new(pulses, 1);
+        assert_eq!(frame.steps().len(), MAX_SERVOS);
+        assert_eq!(frame.steps()[MAX_SERVOS - 1].at, 1011);
+        // Left out pulses don't get raised either
+        assert_eq!(frame.set, [0b1111, 0b1111, 0b1111]);
+
+        // Same widths share their steps
+        let pulses = (0..20).map(|i| (line(i % 3, i / 3), 1000 + (i % 2) as u16));
+        assert_eq!(Frame::new(pulses, 1).steps().len(), 2);
+    }
+
+    #[test]
+    fn sequencer() {
+        let frame = Frame::new([(line(0, 0), 100), (line(0, 1), 200)].iter().copied(), 16);
+        let mut sequencer = Sequencer::new(900);
+        assert!(sequencer.is_idle());
+        assert!(!sequencer.load(&frame, 950));
+        assert!(sequencer.load(&frame, 500));
+        // Takes effect with the next frame
+        assert_eq!(sequencer.step(), None);
+
+        assert_eq!(sequencer.start(), [0b11, 0, 0]);
+        assert_eq!(sequencer.peek().map(|step| step.at), Some(100));
+        assert!(!sequencer.load(&Frame::EMPTY, 150));
+        assert_eq!(sequencer.step().map(|step| step.at), Some(100));
+        assert_eq!(sequencer.step().map(|step| step.at), Some(200));
+        assert_eq!(sequencer.step(), None);
+        assert!(sequencer.is_idle());
+
+        assert!(sequencer.load(&Frame::EMPTY, 250));
+        assert_eq!(sequencer.start(), [0; PORTS]);
+        assert!(sequencer.is_idle());
+    }
+
+    #[test]
+    fn bank() {
+        let timing = Timing::new(8_000_000, PERIOD_US).unwrap();
+        let mut slots: [Slot<Mock>; 3] = Default::default();
+        let mut bank = Bank::new(&mut slots, timing, 16);
+        let a = bank
+            .attach(Mock(line(1, 0)), Calibration::DEFAULT)
+            .ok()
+            .unwrap();
+        let b = bank
+            .attach(Mock(line(1, 1)), Calibration::DEFAULT)
+            .ok()
+            .unwrap();
+        let c = bank
+            .attach(Mock(line(2, 4)), Calibration::new(600, 1500, 30_000))
+            .ok()
+            .unwrap();
+        assert!(bank.attach(Mock(line(2, 5)), Calibration::DEFAULT).is_err());
+
+        bank[a].set_angle(-90);
+        bank[b].set_speed(90);
+        bank[b].set_angle(90);
+        bank[c].set_pulse(25_000);
+        assert!(bank.is_moving());
+        let steps: Vec<_> = bank.frame().steps().iter().map(|step| step.at).collect();
+        // Pulses are cut before the window closes
+        assert_eq!(steps, [1000, 1500, 17_484]);
+
+        bank.update(100);
+        assert_eq!(bank[b].pulse(), 1550);
+        for _ in 0..9 {
+            bank.update(100);
+        }
+        assert!(!bank.is_moving());
+
+        assert_eq!(bank.detach(c).map(|pin| pin.0), Some(line(2, 4)));
+        assert!(bank.detach(c).is_none());
+        let frame = bank.frame();
+        assert_eq!(frame.set, [0, 0b11, 0]);
+        assert_eq!(frame.steps()[1].at, 2000);
+        // The freed slot is reused
+        assert!(bank.attach(Mock(line(0, 2)), Calibration::DEFAULT).is_ok());
+    }
+
+    #[test]
+    fn longest_pulse_leaves_time_to_load() {
+        let timing = Timing::new(16_000_000, PERIOD_US).unwrap();
+        let mut slots: [Slot<Mock>; 1] = Default::default();
+        let mut bank = Bank::new(&mut slots, timing, 2);
+        let a = bank
+            .attach(Mock(line(0, 0)), Calibration::new(600, 1500, 30_000))
+            .ok()
+            .unwrap();
+        bank[a].set_pulse(30_000);
+        let frame = bank.frame();
+        let last = frame.steps()[0];
+        assert_eq!(last.at, window(&timing) - 2);
+
+        let mut sequencer = Sequencer::new(window(&timing));
+        assert!(sequencer.load(&frame, 0));
+        sequencer.start();
+        assert_eq!(sequencer.step(), Some(last));
+        // Right after the interrupt of the longest pulse
+        assert!(sequencer.load(&frame, last.at + 1));
+    }
+}
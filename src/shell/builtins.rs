@@ -0,0 +1,305 @@
+//! Commands for poking at the hardware.
+//!
+//! Each function here returns a [`Command`] for the table, running on a
+//! context which implements [`Hardware`]:
+//!
+//! | Command | |
+//! | --- | --- |
+//! | `peek <addr>` | Read the I/O register at data space address `addr` |
+//! | `poke <addr> <value>` | Write an I/O register and read it back |
+//! | `adc <channel>` | One conversion of ADC channel 0-7 or 8 for the temperature sensor |
+//! | `pwm <duty>` | Set the PWM duty cycle, 0-255 |
+//! | `eeprom read <addr>` | Read an EEPROM byte |
+//! | `eeprom write <addr> <value>` | Write an EEPROM byte |
+//!
+//! [`Mcu`] is the `Hardware` of the ATmega48P, everything else only needs
+//! the trait and can be tried on the host.
+//!
+//! [`Command`]: ../struct.Command.html
+//! [`Hardware`]: trait.Hardware.html
+//! [`Mcu`]: struct.Mcu.html
+
+use ufmt::uWrite;
+
+use super::{Args, Command, Error};
+use crate::fmt::Hex;
+
+/// Lowest I/O register address, below are the CPU registers
+const IO_START: u8 = 0x20;
+
+/// Hardware access of the built-in commands
+pub trait Hardware {
+    /// Read the I/O register at `address`, at least `0x20`
+    fn peek(&mut self, address: u8) -> u8;
+    /// Write the I/O register at `address`, at least `0x20`
+    fn poke(&mut self, address: u8, value: u8);
+    /// Convert `channel`, `None` if there is no such channel
+    fn adc(&mut self, channel: u8) -> Option<u16>;
+    fn pwm(&mut self, duty: u8);
+    fn eeprom_read(&mut self, address: u8) -> u8;
+    fn eeprom_write(&mut self, address: u8, value: u8);
+}
+
+pub const fn peek<C: Hardware, W: uWrite>() -> Command<C, W> {
+    Command::new("peek", "<addr>", run_peek)
+}
+
+pub const fn poke<C: Hardware, W: uWrite>() -> Command<C, W> {
+    Command::new("poke", "<addr> <value>", run_poke)
+}
+
+pub const fn adc<C: Hardware, W: uWrite>() -> Command<C, W> {
+    Command::new("adc", "<channel>", run_adc)
+}
+
+pub const fn pwm<C: Hardware, W: uWrite>() -> Command<C, W> {
+    Command::new("pwm", "<duty>", run_pwm)
+}
+
+pub const fn eeprom<C: Hardware, W: uWrite>() -> Command<C, W> {
+    Command::new("eeprom", "read <addr> | write <addr> <value>", run_eeprom)
+}
+
+fn io_address(args: &mut Args) -> Result<u8, Error> {
+    match args.number()? {
+        address if address >= IO_START => Ok(address),
+        _ => Err(Error::InvalidArgument),
+    }
+}
+
+fn show<W: uWrite>(out: &mut W, address: u8, value: u8) {
+    ufmt::uwrite!(out, "0x{}: 0x{}\r\n", Hex(address), Hex(value)).ok();
+}
+
+fn run_peek<C: Hardware, W: uWrite>(hw: &mut C, args: &mut Args, out: &mut W) -> Result<(), Error> {
+    let address = io_address(args)?;
+    args.end()?;
+    show(out, address, hw.peek(address));
+    Ok(())
+}
+
+fn run_poke<C: Hardware, W: uWrite>(hw: &mut C, args: &mut Args, out: &mut W) -> Result<(), Error> {
+    let address = io_address(args)?;
+    let value = args.number()?;
+    args.end()?;
+    hw.poke(address, value);
+    show(out, address, hw.peek(address));
+    Ok(())
+}
+
+fn run_adc<C: Hardware, W: uWrite>(hw: &mut C, args: &mut Args, out: &mut W) -> Result<(), Error> {
+    let channel = args.number()?;
+    args.end()?;
+    let value = hw.adc(channel).ok_or(Error::InvalidArgument)?;
+    ufmt::uwrite!(out, "{}\r\n", value).ok();
+    Ok(())
+}
+
+fn run_pwm<C: Hardware, W: uWrite>(hw: &mut C, args: &mut Args, out: &mut W) -> Result<(), Error> {
+    let duty = args.number()?;
+    args.end()?;
+    hw.pwm(duty);
+    ufmt::uwrite!(out, "duty {}\r\n", duty).ok();
+    Ok(())
+}
+
+fn run_eeprom<C: Hardware, W: uWrite>(
+    hw: &mut C,
+    args: &mut Args,
+    out: &mut W,
+) -> Result<(), Error> {
+    match args.word()? {
+        "read" => {
+            let address = args.number()?;
+            args.end()?;
+            show(out, address, hw.eeprom_read(address));
+        }
+        "write" => {
+            let address = args.number()?;
+            let value = args.number()?;
+            args.end()?;
+            hw.eeprom_write(address, value);
+            show(out, address, hw.eeprom_read(address));
+        }
+        _ => return Err(Error::InvalidArgument),
+    }
+    Ok(())
+}
+
+#[cfg(target_arch = "avr")]
+pub use self::mcu::Mcu;
+
+#[cfg(target_arch = "avr")]
+mod mcu {
+    use embedded_hal::PwmPin;
+
+    use super::Hardware;
+    use crate::dev::adc::{ADC, ADCSRA, ADMUX};
+    use crate::eeprom;
+
+    /// `Hardware` of the ATmega48P with the PWM pin `P`
+    ///
+    /// The ADC is switched on for each conversion and uses AVcc as reference,
+    /// the temperature sensor the internal 1.1 V reference. It must not be in
+    /// use elsewhere.
+    pub struct Mcu<P> {
+        pub pwm: P,
+    }
+
+    impl<P: PwmPin<Duty = u8>> Mcu<P> {
+        pub fn new(pwm: P) -> Mcu<P> {
+            Mcu { pwm }
+        }
+    }
+
+    impl<P: PwmPin<Duty = u8>> Hardware for Mcu<P> {
+        fn peek(&mut self, address: u8) -> u8 {
+            unsafe { core::ptr::read_volatile(address as *const u8) }
+        }
+
+        fn poke(&mut self, address: u8, value: u8) {
+            unsafe { core::ptr::write_volatile(address as *mut u8, value) }
+        }
+
+        fn adc(&mut self, channel: u8) -> Option<u16> {
+            // ADC0-7 and the temperature sensor
+            if channel > 8 {
+                return None;
+            }
+            // The temperature sensor is only specified against the internal
+            // reference
+            let reference = if channel == 8 {
+                ADMUX::REFS::Internal
+            } else {
+                ADMUX::REFS::Avcc
+            };
+            ADC.admux.write(reference + ADMUX::MUX.val(channel));
+            ADC.adcsra
+                .write(ADCSRA::ADEN::SET + ADCSRA::ADSC::SET + ADCSRA::ADPS::Prescale128);
+            while ADC.adcsra.is_set(ADCSRA::ADSC) {}
+            let value = ADC.adc.get();
+            ADC.adcsra.write(ADCSRA::ADEN::CLEAR);
+            Some(value)
+        }
+
+        fn pwm(&mut self, duty: u8) {
+            self.pwm.set_duty(duty);
+            self.pwm.enable();
+        }
+
+        fn eeprom_read(&mut self, address: u8) -> u8 {
+            eeprom::read(address)
+        }
+
+        fn eeprom_write(&mut self, address: u8, value: u8) {
+            eeprom::program(address, value, eeprom::Mode::EraseWrite);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::tests::Terminal;
+    use crate::shell::Shell;
+    use std::string::String;
+
+    struct Mock {
+        io: [u8; 256],
+        eeprom: [u8; 256],
+        duty: u8,
+    }
+
+    impl Hardware for Mock {
+        fn peek(&mut self, address: u8) -> u8 {
+            assert!(address >= IO_START);
+            self.io[address as usize]
+        }
+
+        fn poke(&mut self, address: u8, value: u8) {
+            assert!(address >= IO_START);
+            // Pretend the top bit is read-only
+            self.io[address as usize] = value & 0x7F;
+        }
+
+        fn adc(&mut self, channel: u8) -> Option<u16> {
+            if channel < 8 {
+                Some(100 * channel as u16)
+            } else {
+                None
+            }
+        }
+
+        fn pwm(&mut self, duty: u8) {
+            self.duty = duty;
+        }
+
+        fn eeprom_read(&mut self, address: u8) -> u8 {
+            self.eeprom[address as usize]
+        }
+
+        fn eeprom_write(&mut self, address: u8, value: u8) {
+            self.eeprom[address as usize] = value;
+        }
+    }
+
+    impl Mock {
+        fn new() -> Mock {
+            Mock {
+                io: [0; 256],
+                eeprom: [0; 256],
+                duty: 0,
+            }
+        }
+    }
+
+    static COMMANDS: [Command<Mock, Terminal>; 5] = [peek(), poke(), adc(), pwm(), eeprom()];
+
+    fn run(mock: &mut Mock, input: &str) -> String {
+        let mut shell = Shell::new(&COMMANDS).no_echo();
+        let mut terminal = Terminal::new(input);
+        shell.poll(&mut terminal, mock);
+        terminal.output
+    }
+
+    #[test]
+    fn registers() {
+        let mut mock = Mock::new();
+        mock.io[0x25] = 0x0F;
+        assert_eq!(run(&mut mock, "peek 0x25\r"), "0x25: 0x0F\r\n> ");
+        assert_eq!(run(&mut mock, "poke 0x2B 0xFF\r"), "0x2B: 0x7F\r\n> ");
+        assert_eq!(mock.io[0x2B], 0x7F);
+        // CPU registers and the rest of the data space are off limits
+        assert_eq!(
+            run(&mut mock, "peek 0x1F\rpeek 0x100\rpoke 0x30\r"),
+            "error: invalid argument\r\n> \
+             error: invalid argument\r\n> \
+             error: missing argument\r\n> "
+        );
+    }
+
+    #[test]
+    fn adc_and_pwm() {
+        let mut mock = Mock::new();
+        assert_eq!(run(&mut mock, "adc 3\r"), "300\r\n> ");
+        assert_eq!(run(&mut mock, "adc 9\r"), "error: invalid argument\r\n> ");
+        assert_eq!(run(&mut mock, "pwm 200\r"), "duty 200\r\n> ");
+        assert_eq!(mock.duty, 200);
+        assert_eq!(run(&mut mock, "pwm 256\r"), "error: invalid argument\r\n> ");
+        assert_eq!(mock.duty, 200);
+    }
+
+    #[test]
+    fn eeprom_bytes() {
+        let mut mock = Mock::new();
+        assert_eq!(run(&mut mock, "eeprom write 10 0xA5\r"), "0x0A: 0xA5\r\n> ");
+        assert_eq!(mock.eeprom[10], 0xA5);
+        assert_eq!(run(&mut mock, "eeprom read 10\r"), "0x0A: 0xA5\r\n> ");
+        assert_eq!(
+            run(&mut mock, "eeprom\reeprom erase\reeprom read 10 11\r"),
+            "error: missing argument\r\n> \
+             error: invalid argument\r\n> \
+             error: too many arguments\r\n> "
+        );
+    }
+}
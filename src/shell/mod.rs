@@ -0,0 +1,467 @@
+//! Line oriented command shell.
+//!
+//! [`Shell`] collects received bytes into a line, with echo and backspace
+//! editing, splits it into whitespace separated words and runs the
+//! [`Command`] named by the first word. Commands are plain functions taking
+//! a context of the application's choice, the remaining words as [`Args`]
+//! and the output:
+//!
+//! ```
+//! use core::convert::Infallible;
+//! use m48_robo_rust::shell::{Args, Command, Error, Shell};
+//! # struct Out;
+//! # impl ufmt::uWrite for Out {
+//! #     type Error = Infallible;
+//! #     fn write_str(&mut self, _: &str) -> Result<(), Infallible> { Ok(()) }
+//! # }
+//!
+//! struct Robot {
+//!     speed: u8,
+//! }
+//!
+//! fn speed(robot: &mut Robot, args: &mut Args, out: &mut Out) -> Result<(), Error> {
+//!     robot.speed = args.number()?;
+//!     args.end()?;
+//!     ufmt::uwrite!(out, "speed {}\r\n", robot.speed).ok();
+//!     Ok(())
+//! }
+//!
+//! static COMMANDS: [Command<Robot, Out>; 1] = [Command::new("speed", "<0-255>", speed)];
+//!
+//! let mut robot = Robot { speed: 0 };
+//! let mut shell = Shell::new(&COMMANDS);
+//! for &byte in b"speed 0x40\r" {
+//!     shell.feed(byte, &mut robot, &mut Out);
+//! }
+//! assert_eq!(robot.speed, 64);
+//! ```
+//!
+//! `help` is always available and lists the commands with their help text.
+//! The [`builtins`] cover register, ADC, PWM and EEPROM access.
+//!
+//! [`Shell`]: struct.Shell.html
+//! [`Command`]: struct.Command.html
+//! [`Args`]: struct.Args.html
+//! [`builtins`]: builtins/index.html
+
+use core::convert::TryFrom;
+
+use embedded_hal::serial;
+use ufmt::{uDisplay, uWrite, Formatter};
+
+pub mod builtins;
+
+/// Longest line in bytes, longer lines are rejected
+pub const LINE_LEN: usize = 32;
+
+/// Why a line could not be run
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    UnknownCommand,
+    MissingArgument,
+    InvalidArgument,
+    TooManyArguments,
+    LineTooLong,
+    /// Non-ASCII bytes or a receive error
+    BadInput,
+}
+
+impl uDisplay for Error {
+    fn fmt<W>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error>
+    where
+        W: uWrite + ?Sized,
+    {
+        f.write_str(match self {
+            Error::UnknownCommand => "unknown command, try help",
+            Error::MissingArgument => "missing argument",
+            Error::InvalidArgument => "invalid argument",
+            Error::TooManyArguments => "too many arguments",
+            Error::LineTooLong => "line too long",
+            Error::BadInput => "bad input",
+        })
+    }
+}
+
+/// Entry of the command table
+pub struct Command<C, W> {
+    pub name: &'static str,
+    /// Usage shown by `help`, e.g. `"<channel>"`
+    pub help: &'static str,
+    pub run: fn(&mut C, &mut Args<'_>, &mut W) -> Result<(), Error>,
+}
+
+impl<C, W> Command<C, W> {
+    pub const fn new(
+        name: &'static str,
+        help: &'static str,
+        run: fn(&mut C, &mut Args<'_>, &mut W) -> Result<(), Error>,
+    ) -> Command<C, W> {
+        Command { name, help, run }
+    }
+}
+
+/// Arguments of a command, iterating over the words after its name
+#[derive(Clone, Debug)]
+pub struct Args<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Args<'a> {
+    pub fn new(line: &'a str) -> Args<'a> {
+        Args { rest: line }
+    }
+
+    /// Next word, `MissingArgument` if there is none
+    pub fn word(&mut self) -> Result<&'a str, Error> {
+        self.next().ok_or(Error::MissingArgument)
+    }
+
+    /// Next word as a number, see [`parse_number`](fn.parse_number.html)
+    pub fn number<T: TryFrom<u32>>(&mut self) -> Result<T, Error> {
+        let number = parse_number(self.word()?).ok_or(Error::InvalidArgument)?;
+        T::try_from(number).map_err(|_| Error::InvalidArgument)
+    }
+
+    /// `TooManyArguments` unless all words were used
+    pub fn end(&mut self) -> Result<(), Error> {
+        match self.next() {
+            Some(_) => Err(Error::TooManyArguments),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a> Iterator for Args<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let rest = self.rest.trim_start_matches(' ');
+        if rest.is_empty() {
+            self.rest = rest;
+            return None;
+        }
+        let end = rest.find(' ').unwrap_or(rest.len());
+        let (word, rest) = rest.split_at(end);
+        self.rest = rest;
+        Some(word)
+    }
+}
+
+/// Parse a decimal, `0x` hexadecimal or `0b` binary number
+pub fn parse_number(word: &str) -> Option<u32> {
+    let (digits, radix) = match word.get(..2) {
+        Some("0x") => (&word[2..], 16),
+        Some("0b") => (&word[2..], 2),
+        _ => (word, 10),
+    };
+    // `from_str_radix` also takes a sign
+    if digits.is_empty() || digits.starts_with('+') {
+        return None;
+    }
+    u32::from_str_radix(digits, radix).ok()
+}
+
+/// Line editor and command dispatcher
+pub struct Shell<'a, C, W> {
+    commands: &'a [Command<C, W>],
+    line: [u8; LINE_LEN],
+    len: usize,
+    error: Option<Error>,
+    echo: bool,
+    last_cr: bool,
+}
+
+impl<'a, C, W: uWrite> Shell<'a, C, W> {
+    /// Shell running `commands`, echoing the input
+    pub fn new(commands: &'a [Command<C, W>]) -> Shell<'a, C, W> {
+        Shell {
+            commands,
+            line: [0; LINE_LEN],
+            len: 0,
+            error: None,
+            echo: true,
+            last_cr: false,
+        }
+    }
+
+    /// Turn off echoing the input, for terminals with local echo
+    pub fn no_echo(mut self) -> Shell<'a, C, W> {
+        self.echo = false;
+        self
+    }
+
+    /// Write the prompt, the shell writes it again after every line
+    pub fn prompt(&self, out: &mut W) {
+        out.write_str("> ").ok();
+    }
+
+    /// Handle a received byte, running the command at the end of a line
+    pub fn feed(&mut self, byte: u8, context: &mut C, out: &mut W) {
+        // A CR LF pair ends only one line
+        let after_cr = self.last_cr;
+        self.last_cr = byte == b'\r';
+        match byte {
+            b'\n' if after_cr => {}
+            b'\r' | b'\n' => {
+                if self.echo {
+                    out.write_str("\r\n").ok();
+                }
+                let result = self.run(context, out);
+                if let Err(error) = result {
+                    ufmt::uwrite!(out, "error: {}\r\n", error).ok();
+                }
+                self.len = 0;
+                self.error = None;
+                self.prompt(out);
+            }
+            // Backspace and delete
+            0x08 | 0x7F if self.len > 0 => {
+                self.len -= 1;
+                if self.echo {
+                    out.write_str("\x08 \x08").ok();
+                }
+            }
+            0x20..=0x7E => {
+                if self.len == LINE_LEN {
+                    self.fail(Error::LineTooLong);
+                    return;
+                }
+                self.line[self.len] = byte;
+                self.len += 1;
+                if self.echo {
+                    out.write_char(byte as char).ok();
+                }
+            }
+            0x80..=0xFF => self.fail(Error::BadInput),
+            // Other control characters, backspace at the start of the line
+            _ => {}
+        }
+    }
+
+    /// Write the list of commands
+    pub fn help(&self, out: &mut W) {
+        let width = self
+            .commands
+            .iter()
+            .map(|command| command.name.len())
+            .max()
+            .unwrap_or(0);
+        out.write_str("help\r\n").ok();
+        for command in self.commands {
+            out.write_str(command.name).ok();
+            for _ in command.name.len()..width + 2 {
+                out.write_char(' ').ok();
+            }
+            ufmt::uwrite!(out, "{}\r\n", command.help).ok();
+        }
+    }
+
+    fn fail(&mut self, error: Error) {
+        if self.error.is_none() {
+            self.error = Some(error);
+        }
+    }
+
+    fn run(&mut self, context: &mut C, out: &mut W) -> Result<(), Error> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        // Only printable ASCII is stored
+        let line = core::str::from_utf8(&self.line[..self.len]).map_err(|_| Error::BadInput)?;
+        let mut args = Args::new(line);
+        let name = match args.next() {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+        if name == "help" {
+            args.end()?;
+            self.help(out);
+            return Ok(());
+        }
+        let command = self
+            .commands
+            .iter()
+            .find(|command| command.name == name)
+            .ok_or(Error::UnknownCommand)?;
+        (command.run)(context, &mut args, out)
+    }
+}
+
+impl<'a, C, S> Shell<'a, C, S>
+where
+    S: serial::Read<u8> + uWrite,
+{
+    /// Handle all bytes `serial` has received so far, answering on it
+    pub fn poll(&mut self, serial: &mut S, context: &mut C) {
+        loop {
+            match serial.read() {
+                Ok(byte) => self.feed(byte, context, serial),
+                Err(nb::Error::WouldBlock) => break,
+                Err(nb::Error::Other(_)) => self.fail(Error::BadInput),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::Infallible;
+    use std::string::String;
+    use std::vec::Vec;
+
+    /// Serial port receiving the bytes of a string, `!` is a receive error
+    pub(crate) struct Terminal {
+        pub input: Vec<u8>,
+        pub output: String,
+    }
+
+    impl Terminal {
+        pub fn new(input: &str) -> Terminal {
+            Terminal {
+                input: input.bytes().collect(),
+                output: String::new(),
+            }
+        }
+    }
+
+    impl serial::Read<u8> for Terminal {
+        type Error = ();
+
+        fn read(&mut self) -> nb::Result<u8, ()> {
+            if self.input.is_empty() {
+                return Err(nb::Error::WouldBlock);
+            }
+            match self.input.remove(0) {
+                b'!' => Err(nb::Error::Other(())),
+                byte => Ok(byte),
+            }
+        }
+    }
+
+    impl uWrite for Terminal {
+        type Error = Infallible;
+
+        fn write_str(&mut self, s: &str) -> Result<(), Infallible> {
+            self.output.push_str(s);
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct Context {
+        calls: Vec<String>,
+    }
+
+    fn set(context: &mut Context, args: &mut Args, out: &mut Terminal) -> Result<(), Error> {
+        let key = args.word()?;
+        let value: u8 = args.number()?;
+        args.end()?;
+        context.calls.push(std::format!("set {} {}", key, value));
+        out.output.push_str("ok\r\n");
+        Ok(())
+    }
+
+    fn list(context: &mut Context, args: &mut Args, _: &mut Terminal) -> Result<(), Error> {
+        let words: Vec<_> = args.collect();
+        context.calls.push(words.join(","));
+        Ok(())
+    }
+
+    static COMMANDS: [Command<Context, Terminal>; 2] = [
+        Command::new("set", "<key> <value>", set),
+        Command::new("list", "<words...>", list),
+    ];
+
+    fn run(shell: &mut Shell<Context, Terminal>, input: &str) -> (Context, String) {
+        let mut context = Context::default();
+        let mut terminal = Terminal::new(input);
+        shell.poll(&mut terminal, &mut context);
+        (context, terminal.output)
+    }
+
+    #[test]
+    fn words() {
+        let words: Vec<_> = Args::new("  peek   0x2A 7 ").collect();
+        assert_eq!(words, ["peek", "0x2A", "7"]);
+        assert_eq!(Args::new("").next(), None);
+        assert_eq!(Args::new("   ").next(), None);
+
+        let mut args = Args::new("12 300 x");
+        assert_eq!(args.number::<u8>(), Ok(12));
+        assert_eq!(args.number::<u8>(), Err(Error::InvalidArgument));
+        assert_eq!(args.end(), Err(Error::TooManyArguments));
+        assert_eq!(args.word(), Err(Error::MissingArgument));
+        assert_eq!(args.end(), Ok(()));
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(parse_number("0"), Some(0));
+        assert_eq!(parse_number("1234"), Some(1234));
+        assert_eq!(parse_number("0x2a"), Some(42));
+        assert_eq!(parse_number("0xFF"), Some(255));
+        assert_eq!(parse_number("0b101"), Some(5));
+        assert_eq!(parse_number("0x"), None);
+        assert_eq!(parse_number("+5"), None);
+        assert_eq!(parse_number("-5"), None);
+        assert_eq!(parse_number("12a"), None);
+        assert_eq!(parse_number("4294967296"), None);
+    }
+
+    #[test]
+    fn dispatch() {
+        let mut shell = Shell::new(&COMMANDS).no_echo();
+        let (context, out) = run(&mut shell, "set speed 0x10\rlist a  b c\r\n\r\n");
+        assert_eq!(context.calls, ["set speed 16", "a,b,c"]);
+        assert_eq!(out, "ok\r\n> > > ");
+    }
+
+    #[test]
+    fn errors() {
+        let mut shell = Shell::new(&COMMANDS).no_echo();
+        let (context, out) = run(&mut shell, "go\rset x\rset x 1 2\rset x 256\r");
+        assert!(context.calls.is_empty());
+        assert_eq!(
+            out,
+            "error: unknown command, try help\r\n> \
+             error: missing argument\r\n> \
+             error: too many arguments\r\n> \
+             error: invalid argument\r\n> "
+        );
+    }
+
+    #[test]
+    fn line_editing() {
+        let mut shell = Shell::new(&COMMANDS);
+        let (context, out) = run(&mut shell, "lisd\x08t xy\x7f\r");
+        assert_eq!(context.calls, ["x"]);
+        assert_eq!(out, "lisd\x08 \x08t xy\x08 \x08\r\n> ");
+
+        // Backspace on an empty line does nothing
+        let (_, out) = run(&mut shell, "\x08\r");
+        assert_eq!(out, "\r\n> ");
+    }
+
+    #[test]
+    fn bad_lines_are_dropped() {
+        let mut shell = Shell::new(&COMMANDS).no_echo();
+        let long: String = "a".repeat(LINE_LEN + 1);
+        let input = std::format!("list {}\rlist \u{e9}\rlist !x\rlist y\r", long);
+        let (context, out) = run(&mut shell, &input);
+        assert_eq!(context.calls, ["y"]);
+        assert_eq!(
+            out,
+            "error: line too long\r\n> error: bad input\r\n> error: bad input\r\n> > "
+        );
+    }
+
+    #[test]
+    fn help() {
+        let mut shell = Shell::new(&COMMANDS).no_echo();
+        let (_, out) = run(&mut shell, "help\r");
+        assert_eq!(out, "help\r\nset   <key> <value>\r\nlist  <words...>\r\n> ");
+        let (_, out) = run(&mut shell, "help me\r");
+        assert_eq!(out, "error: too many arguments\r\n> ");
+    }
+}
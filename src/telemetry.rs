@@ -0,0 +1,491 @@
+//! Framed binary telemetry.
+//!
+//! A frame carries a message ID, a sequence number, up to [`MAX_PAYLOAD`]
+//! bytes of payload and a CRC-16 over all of them:
+//!
+//! | `id` | `seq` | payload | CRC-16, little endian |
+//! | --- | --- | --- | --- |
+//!
+//! It is COBS encoded, which removes all zero bytes, and terminated by a
+//! zero, so a receiver which joins in the middle of a frame resynchronises at
+//! the next one. The CRC is CRC-16/CCITT-FALSE (polynomial `0x1021`, initial
+//! value `0xFFFF`). The sequence number counts frames per [`Encoder`] and
+//! wraps, gaps in it tell the receiver how many frames were lost.
+//!
+//! Payload values are little endian, [`Message`] builds them:
+//!
+//! ```
+//! use m48_robo_rust::telemetry::{Decoder, Encoder, Message, MAX_FRAME};
+//!
+//! const ADC: u8 = 1;
+//!
+//! let mut encoder = Encoder::new();
+//! let mut frame = [0; MAX_FRAME];
+//! let message = Message::new(ADC).u16(512).i16(-3);
+//! let bytes = encoder.encode(&message, &mut frame);
+//! assert_eq!(bytes.last(), Some(&0));
+//!
+//! let mut decoder = Decoder::new([0; MAX_FRAME]);
+//! let (last, rest) = bytes.split_last().unwrap();
+//! for &byte in rest {
+//!     assert!(decoder.feed(byte).is_none());
+//! }
+//! let frame = decoder.feed(*last).unwrap().unwrap();
+//! assert_eq!((frame.id, frame.seq), (ADC, 0));
+//! assert_eq!(frame.payload, [0x00, 0x02, 0xFD, 0xFF]);
+//! ```
+//!
+//! On the MCU [`Encoder::send`] writes the frame to the serial port; the
+//! `telemetry` tool in the workspace decodes frames on the host into CSV.
+//!
+//! [`MAX_PAYLOAD`]: constant.MAX_PAYLOAD.html
+//! [`Encoder`]: struct.Encoder.html
+//! [`Encoder::send`]: struct.Encoder.html#method.send
+//! [`Message`]: struct.Message.html
+
+use embedded_hal::serial;
+
+/// Largest payload of a frame in bytes
+pub const MAX_PAYLOAD: usize = 32;
+
+/// ID, sequence number and CRC
+const OVERHEAD: usize = 4;
+
+/// Largest encoded frame in bytes, including the COBS code byte and the
+/// terminating zero
+pub const MAX_FRAME: usize = MAX_PAYLOAD + OVERHEAD + 2;
+
+/// Why a received frame was dropped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The COBS encoding was cut short, usually by lost bytes
+    Encoding,
+    /// The frame didn't fit into the decoder buffer
+    TooLong,
+    /// The frame is shorter than ID, sequence number and CRC
+    TooShort,
+    /// The CRC doesn't match
+    Crc,
+}
+
+/// Add `byte` to the CRC-16/CCITT-FALSE `crc`
+pub fn crc16_update(crc: u16, byte: u8) -> u16 {
+    let mut crc = crc ^ (byte as u16) << 8;
+    for _ in 0..8 {
+        crc = if crc & 0x8000 != 0 {
+            crc << 1 ^ 0x1021
+        } else {
+            crc << 1
+        };
+    }
+    crc
+}
+
+/// CRC-16/CCITT-FALSE of `data`
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter()
+        .fold(0xFFFF, |crc, &byte| crc16_update(crc, byte))
+}
+
+/// Message ID and payload of a frame
+///
+/// Values which don't fit into `MAX_PAYLOAD` are dropped.
+#[derive(Clone, Debug)]
+pub struct Message {
+    id: u8,
+    len: usize,
+    payload: [u8; MAX_PAYLOAD],
+}
+
+impl Message {
+    /// Empty message
+    pub const fn new(id: u8) -> Message {
+        Message {
+            id,
+            len: 0,
+            payload: [0; MAX_PAYLOAD],
+        }
+    }
+
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.payload[..self.len]
+    }
+
+    /// Append raw bytes
+    pub fn bytes(mut self, bytes: &[u8]) -> Message {
+        debug_assert!(self.len + bytes.len() <= MAX_PAYLOAD);
+        if self.len + bytes.len() <= MAX_PAYLOAD {
+            self.payload[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+            self.len += bytes.len();
+        }
+        self
+    }
+
+    pub fn u8(self, value: u8) -> Message {
+        self.bytes(&[value])
+    }
+
+    pub fn i8(self, value: i8) -> Message {
+        self.bytes(&[value as u8])
+    }
+
+    pub fn u16(self, value: u16) -> Message {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn i16(self, value: i16) -> Message {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn u32(self, value: u32) -> Message {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    pub fn i32(self, value: i32) -> Message {
+        self.bytes(&value.to_le_bytes())
+    }
+}
+
+/// Frame encoder, counting the sequence number
+#[derive(Clone, Debug, Default)]
+pub struct Encoder {
+    seq: u8,
+}
+
+impl Encoder {
+    pub const fn new() -> Encoder {
+        Encoder { seq: 0 }
+    }
+
+    /// Sequence number of the next frame
+    pub fn seq(&self) -> u8 {
+        self.seq
+    }
+
+    /// Encode `message` into `buffer`, returning the frame with its
+    /// terminating zero
+    pub fn encode<'b>(&mut self, message: &Message, buffer: &'b mut [u8; MAX_FRAME]) -> &'b [u8] {
+        let mut raw = [0; MAX_PAYLOAD + OVERHEAD];
+        let payload = message.payload();
+        let end = payload.len() + 2;
+        raw[0] = message.id;
+        raw[1] = self.seq;
+        raw[2..end].copy_from_slice(payload);
+        let crc = crc16(&raw[..end]);
+        raw[end..end + 2].copy_from_slice(&crc.to_le_bytes());
+        self.seq = self.seq.wrapping_add(1);
+
+        let len = cobs_encode(&raw[..end + 2], buffer);
+        &buffer[..len]
+    }
+
+    /// Encode `message` and write it to `serial`, blocking until all bytes
+    /// were accepted
+    pub fn send<S>(&mut self, serial: &mut S, message: &Message) -> Result<(), S::Error>
+    where
+        S: serial::Write<u8>,
+    {
+        let mut buffer = [0; MAX_FRAME];
+        for &byte in self.encode(message, &mut buffer) {
+            nb::block!(serial.write(byte))?;
+        }
+        Ok(())
+    }
+}
+
+/// COBS encode `raw` into `out` followed by a zero, returns the length.
+/// `out` has to have room for one code byte per 254 bytes and the zero.
+fn cobs_encode(raw: &[u8], out: &mut [u8]) -> usize {
+    let mut code_at = 0;
+    let mut code = 1;
+    let mut len = 1;
+    for &byte in raw {
+        if byte != 0 {
+            out[len] = byte;
+            len += 1;
+            code += 1;
+        }
+        if byte == 0 || code == 0xFF {
+            out[code_at] = code;
+            code_at = len;
+            len += 1;
+            code = 1;
+        }
+    }
+    out[code_at] = code;
+    out[len] = 0;
+    len + 1
+}
+
+/// Received frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Frame<'a> {
+    pub id: u8,
+    pub seq: u8,
+    pub payload: &'a [u8],
+}
+
+/// Frame decoder, reassembling frames in the buffer `B`
+///
+/// `[u8; MAX_FRAME]` holds every frame an `Encoder` produces.
+#[derive(Clone, Debug)]
+pub struct Decoder<B> {
+    buffer: B,
+    len: usize,
+    /// Code of the current COBS block, 0 before the first one
+    code: u8,
+    /// Data bytes left in the current COBS block
+    remaining: u8,
+    overflow: bool,
+    ended: bool,
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> Decoder<B> {
+    /// Decoder waiting for the start of a frame, the contents of `buffer`
+    /// are ignored
+    pub const fn new(buffer: B) -> Decoder<B> {
+        Decoder {
+            buffer,
+            len: 0,
+            code: 0,
+            remaining: 0,
+            overflow: false,
+            ended: false,
+        }
+    }
+
+    /// Handle a received byte, returning the frame or error at the end of
+    /// a frame. Empty frames are skipped.
+    pub fn feed(&mut self, byte: u8) -> Option<Result<Frame<'_>, Error>> {
+        // The last frame is kept until the next byte arrives
+        if self.ended {
+            self.len = 0;
+            self.code = 0;
+            self.remaining = 0;
+            self.overflow = false;
+            self.ended = false;
+        }
+        if byte == 0 {
+            self.ended = true;
+            return self.finish();
+        }
+        if self.remaining > 0 {
+            self.push(byte);
+            self.remaining -= 1;
+        } else {
+            // A block shorter than the maximum ends with an implicit zero
+            if self.code != 0 && self.code != 0xFF {
+                self.push(0);
+            }
+            self.code = byte;
+            self.remaining = byte - 1;
+        }
+        None
+    }
+
+    fn push(&mut self, byte: u8) {
+        let buffer = self.buffer.as_mut();
+        if self.len < buffer.len() {
+            buffer[self.len] = byte;
+            self.len += 1;
+        } else {
+            self.overflow = true;
+        }
+    }
+
+    fn finish(&self) -> Option<Result<Frame<'_>, Error>> {
+        if self.code == 0 {
+            return None;
+        }
+        if self.remaining > 0 {
+            return Some(Err(Error::Encoding));
+        }
+        if self.overflow {
+            return Some(Err(Error::TooLong));
+        }
+        if self.len < OVERHEAD {
+            return Some(Err(Error::TooShort));
+        }
+        let (data, crc) = self.buffer.as_ref()[..self.len].split_at(self.len - 2);
+        if crc16(data).to_le_bytes() != crc {
+            return Some(Err(Error::Crc));
+        }
+        Some(Ok(Frame {
+            id: data[0],
+            seq: data[1],
+            payload: &data[2..],
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    fn cobs(raw: &[u8]) -> Vec<u8> {
+        let mut out = [0xAA; 300];
+        let len = cobs_encode(raw, &mut out);
+        out[..len].to_vec()
+    }
+
+    /// `(id, seq, payload)` of a decoded frame
+    type Decoded = Result<(u8, u8, Vec<u8>), Error>;
+
+    /// Decode `bytes`, collecting the frames and errors
+    fn decode(bytes: &[u8]) -> Vec<Decoded> {
+        let mut decoder = Decoder::new([0; MAX_FRAME]);
+        bytes
+            .iter()
+            .filter_map(|&byte| {
+                decoder
+                    .feed(byte)
+                    .map(|result| result.map(|frame| (frame.id, frame.seq, frame.payload.to_vec())))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+        assert_eq!(crc16(&[]), 0xFFFF);
+    }
+
+    #[test]
+    fn cobs_vectors() {
+        assert_eq!(cobs(&[]), [0x01, 0x00]);
+        assert_eq!(cobs(&[0x00]), [0x01, 0x01, 0x00]);
+        assert_eq!(cobs(&[0x00, 0x00]), [0x01, 0x01, 0x01, 0x00]);
+        assert_eq!(
+            cobs(&[0x11, 0x22, 0x00, 0x33]),
+            [0x03, 0x11, 0x22, 0x02, 0x33, 0x00]
+        );
+        assert_eq!(cobs(&[0x11, 0x00]), [0x02, 0x11, 0x01, 0x00]);
+
+        let long: Vec<u8> = (1..=255).collect();
+        let encoded = cobs(&long);
+        assert_eq!(encoded[0], 0xFF);
+        assert_eq!(encoded[255], 0x02);
+        assert_eq!(encoded.len(), 258);
+        assert!(!encoded[..257].contains(&0));
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut encoder = Encoder::new();
+        let mut bytes = Vec::new();
+        let messages = [
+            Message::new(1),
+            Message::new(2).u16(0).u16(512).i32(-1),
+            Message::new(0).bytes(&[0; MAX_PAYLOAD]),
+            Message::new(0xFF).bytes(&[0xFF; MAX_PAYLOAD]),
+        ];
+        for message in &messages {
+            let mut buffer = [0; MAX_FRAME];
+            let frame = encoder.encode(message, &mut buffer);
+            assert_eq!(
+                frame.iter().position(|&byte| byte == 0),
+                Some(frame.len() - 1)
+            );
+            bytes.extend_from_slice(frame);
+        }
+        assert_eq!(encoder.seq(), 4);
+
+        let frames = decode(&bytes);
+        assert_eq!(frames.len(), messages.len());
+        for (seq, (frame, message)) in frames.iter().zip(&messages).enumerate() {
+            let payload = message.payload().to_vec();
+            assert_eq!(frame, &Ok((message.id(), seq as u8, payload)));
+        }
+    }
+
+    #[test]
+    fn message_values() {
+        let message = Message::new(7)
+            .u8(1)
+            .i8(-2)
+            .u16(0x1234)
+            .i16(-2)
+            .u32(0xDEADBEEF);
+        assert_eq!(
+            message.payload(),
+            [1, 0xFE, 0x34, 0x12, 0xFE, 0xFF, 0xEF, 0xBE, 0xAD, 0xDE]
+        );
+    }
+
+    #[test]
+    fn errors_and_resync() {
+        let mut encoder = Encoder::new();
+        let mut buffer = [0; MAX_FRAME];
+        let good = encoder
+            .encode(&Message::new(3).u16(1000), &mut buffer)
+            .to_vec();
+
+        // Joining in the middle of a frame, the first byte looks like a
+        // COBS code for a longer block
+        let mut bytes = good[3..].to_vec();
+        // Lost bytes cut a COBS block short
+        bytes.extend_from_slice(&good[..4]);
+        bytes.push(0);
+        // A flipped bit
+        let mut corrupt = good.clone();
+        corrupt[3] ^= 0x10;
+        bytes.extend_from_slice(&corrupt);
+        // Idle zeros are ignored
+        bytes.extend_from_slice(&[0, 0]);
+        bytes.extend_from_slice(&cobs(&[1, 2, 3]));
+        bytes.extend_from_slice(&good);
+
+        assert_eq!(
+            decode(&bytes),
+            [
+                Err(Error::Encoding),
+                Err(Error::Encoding),
+                Err(Error::Crc),
+                Err(Error::TooShort),
+                Ok((3, 0, std::vec![0xE8, 0x03])),
+            ]
+        );
+    }
+
+    #[test]
+    fn too_long() {
+        let mut decoder = Decoder::new([0; 4]);
+        let frames: Vec<_> = cobs(&[1, 2, 3, 4, 5])
+            .into_iter()
+            .filter_map(|byte| decoder.feed(byte).map(|result| result.map(|_| ())))
+            .collect();
+        assert_eq!(frames, [Err(Error::TooLong)]);
+    }
+
+    #[test]
+    fn send() {
+        struct Port(Vec<u8>);
+
+        impl serial::Write<u8> for Port {
+            type Error = ();
+
+            fn write(&mut self, byte: u8) -> nb::Result<(), ()> {
+                self.0.push(byte);
+                Ok(())
+            }
+
+            fn flush(&mut self) -> nb::Result<(), ()> {
+                Ok(())
+            }
+        }
+
+        let mut encoder = Encoder::new();
+        let mut port = Port(Vec::new());
+        encoder.send(&mut port, &Message::new(9).u8(5)).unwrap();
+        encoder.send(&mut port, &Message::new(9).u8(6)).unwrap();
+        assert_eq!(
+            decode(&port.0),
+            [Ok((9, 0, std::vec![5])), Ok((9, 1, std::vec![6]))]
+        );
+    }
+}
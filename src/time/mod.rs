@@ -0,0 +1,284 @@
+//! Monotonic timebase.
+//!
+//! [`Instant`] and [`Duration`] count microseconds in 32 bits. An `Instant`
+//! wraps around after about 71.6 minutes, so instants are compared by their
+//! wrapping distance: any two instants less than half the range (about 35.8
+//! minutes) apart order correctly, even across the wrap.
+//!
+//! With one of the `systick-tc0` or `systick-tc2` features enabled, the
+//! selected timer is claimed for a 1 ms tick which drives [`millis()`],
+//! [`micros()`] and `Instant::now()`:
+//!
+//! ```no_run
+//! use m48_robo_rust::time::{self, Duration, Instant};
+//!
+//! time::init();
+//! unsafe { avr_device::interrupt::enable() };
+//!
+//! let mut next = Instant::now() + Duration::from_millis(500);
+//! loop {
+//!     if Instant::now() >= next {
+//!         next += Duration::from_millis(500);
+//!         // periodic work, without blocking the rest of the loop
+//!     }
+//! }
+//! ```
+//!
+//! [`Instant`]: struct.Instant.html
+//! [`Duration`]: struct.Duration.html
+//! [`millis()`]: fn.millis.html
+//! [`micros()`]: fn.micros.html
+
+use core::cmp::Ordering;
+use core::ops::{Add, AddAssign, Div, Mul, Sub, SubAssign};
+
+#[cfg(all(feature = "systick-tc0", feature = "systick-tc2"))]
+compile_error!("only one of the `systick-*` features can be enabled");
+
+#[cfg(all(
+    target_arch = "avr",
+    any(feature = "systick-tc0", feature = "systick-tc2")
+))]
+mod systick;
+#[cfg(all(
+    target_arch = "avr",
+    any(feature = "systick-tc0", feature = "systick-tc2")
+))]
+pub use self::systick::{init, micros, millis};
+
+/// Span of time with microsecond resolution
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Duration(u32);
+
+impl Duration {
+    pub const ZERO: Duration = Duration(0);
+    pub const MAX: Duration = Duration(u32::MAX);
+
+    pub const fn from_micros(us: u32) -> Duration {
+        Duration(us)
+    }
+
+    pub const fn from_millis(ms: u32) -> Duration {
+        Duration(ms * 1000)
+    }
+
+    pub const fn from_secs(s: u32) -> Duration {
+        Duration(s * 1_000_000)
+    }
+
+    pub const fn as_micros(self) -> u32 {
+        self.0
+    }
+
+    pub const fn as_millis(self) -> u32 {
+        self.0 / 1000
+    }
+
+    pub const fn as_secs(self) -> u32 {
+        self.0 / 1_000_000
+    }
+
+    pub fn checked_add(self, rhs: Duration) -> Option<Duration> {
+        self.0.checked_add(rhs.0).map(Duration)
+    }
+
+    pub fn checked_sub(self, rhs: Duration) -> Option<Duration> {
+        self.0.checked_sub(rhs.0).map(Duration)
+    }
+
+    pub fn saturating_sub(self, rhs: Duration) -> Duration {
+        Duration(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl Add for Duration {
+    type Output = Duration;
+
+    fn add(self, rhs: Duration) -> Duration {
+        Duration(self.0 + rhs.0)
+    }
+}
+
+impl AddAssign for Duration {
+    fn add_assign(&mut self, rhs: Duration) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Duration {
+    type Output = Duration;
+
+    fn sub(self, rhs: Duration) -> Duration {
+        Duration(self.0 - rhs.0)
+    }
+}
+
+impl SubAssign for Duration {
+    fn sub_assign(&mut self, rhs: Duration) {
+        *self = *self - rhs;
+    }
+}
+
+impl Mul<u32> for Duration {
+    type Output = Duration;
+
+    fn mul(self, rhs: u32) -> Duration {
+        Duration(self.0 * rhs)
+    }
+}
+
+impl Div<u32> for Duration {
+    type Output = Duration;
+
+    fn div(self, rhs: u32) -> Duration {
+        Duration(self.0 / rhs)
+    }
+}
+
+/// Point in time of the monotonic clock, see the [module docs](index.html)
+/// for how instants compare
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Instant(u32);
+
+impl Instant {
+    /// Instant at `us` microseconds of the (wrapping) clock
+    pub const fn from_micros(us: u32) -> Instant {
+        Instant(us)
+    }
+
+    /// Raw microsecond value of the (wrapping) clock
+    pub const fn as_micros(self) -> u32 {
+        self.0
+    }
+
+    /// Time passed from `earlier` to `self`, `earlier` has to be before `self`
+    pub fn duration_since(self, earlier: Instant) -> Duration {
+        Duration(self.0.wrapping_sub(earlier.0))
+    }
+
+    /// Time passed from `earlier` to `self`, `None` if `earlier` is after `self`
+    pub fn checked_duration_since(self, earlier: Instant) -> Option<Duration> {
+        if self >= earlier {
+            Some(self.duration_since(earlier))
+        } else {
+            None
+        }
+    }
+
+    /// Time passed since `self`
+    #[cfg(all(
+        target_arch = "avr",
+        any(feature = "systick-tc0", feature = "systick-tc2")
+    ))]
+    pub fn elapsed(self) -> Duration {
+        Instant::now().duration_since(self)
+    }
+}
+
+impl PartialOrd for Instant {
+    fn partial_cmp(&self, other: &Instant) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Instant {
+    fn cmp(&self, other: &Instant) -> Ordering {
+        (self.0.wrapping_sub(other.0) as i32).cmp(&0)
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, rhs: Duration) -> Instant {
+        Instant(self.0.wrapping_add(rhs.0))
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, rhs: Duration) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+
+    fn sub(self, rhs: Duration) -> Instant {
+        Instant(self.0.wrapping_sub(rhs.0))
+    }
+}
+
+impl SubAssign<Duration> for Instant {
+    fn sub_assign(&mut self, rhs: Duration) {
+        *self = *self - rhs;
+    }
+}
+
+impl Sub for Instant {
+    type Output = Duration;
+
+    fn sub(self, rhs: Instant) -> Duration {
+        self.duration_since(rhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duration_units() {
+        assert_eq!(Duration::from_millis(3).as_micros(), 3000);
+        assert_eq!(Duration::from_secs(2).as_millis(), 2000);
+        assert_eq!(Duration::from_micros(1_999_999).as_secs(), 1);
+        assert_eq!(Duration::from_millis(5) * 2, Duration::from_millis(10));
+        assert_eq!(Duration::from_millis(5) / 5, Duration::from_millis(1));
+    }
+
+    #[test]
+    fn duration_checked() {
+        let a = Duration::from_micros(10);
+        let b = Duration::from_micros(20);
+        assert_eq!(a.checked_sub(b), None);
+        assert_eq!(b.checked_sub(a), Some(a));
+        assert_eq!(a.saturating_sub(b), Duration::ZERO);
+        assert_eq!(Duration::MAX.checked_add(a), None);
+    }
+
+    #[test]
+    fn instant_orders_across_wrap() {
+        let before = Instant::from_micros(u32::MAX - 10);
+        let after = before + Duration::from_micros(20);
+        assert_eq!(after.as_micros(), 9);
+        assert!(after > before);
+        assert!(before < after);
+        assert_eq!(after - before, Duration::from_micros(20));
+        assert_eq!(after - Duration::from_micros(20), before);
+    }
+
+    #[test]
+    fn instant_checked_duration() {
+        let a = Instant::from_micros(u32::MAX);
+        let b = a + Duration::from_millis(1);
+        assert_eq!(b.checked_duration_since(a), Some(Duration::from_millis(1)));
+        assert_eq!(a.checked_duration_since(b), None);
+        assert_eq!(a.checked_duration_since(a), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn deadline_loop() {
+        let mut next = Instant::from_micros(u32::MAX - 1500);
+        let period = Duration::from_millis(1);
+        let mut now = next;
+        let mut fired = 0;
+        for _ in 0..5000 {
+            if now >= next {
+                next += period;
+                fired += 1;
+            }
+            now += Duration::from_micros(1);
+        }
+        assert_eq!(fired, 5);
+    }
+}
@@ -0,0 +1,147 @@
+//! 1 ms tick on Timer/Counter0 (`systick-tc0`) or Timer/Counter2
+//! (`systick-tc2`) in CTC mode.
+//!
+//! The timer is set up for the reset frequency `clock::FREQ_HZ`, so the tick
+//! does not follow `clock::set_prescaler()`.
+
+use core::cell::Cell;
+
+use super::Instant;
+use crate::clock;
+use crate::interrupt::{self, Mutex};
+
+#[cfg(feature = "clock-1mhz")]
+const PRESCALE: u32 = 8;
+#[cfg(any(feature = "clock-8mhz", feature = "clock-16mhz"))]
+const PRESCALE: u32 = 64;
+
+/// Timer counts per millisecond, the compare match value is one less
+const COUNTS_PER_MS: u32 = clock::FREQ_HZ / PRESCALE / 1000;
+const US_PER_COUNT: u32 = 1000 / COUNTS_PER_MS;
+
+// The compare value has to fit into 8 bits and a count has to be a whole
+// number of microseconds
+const _: [(); 0 - !(COUNTS_PER_MS <= 256 && 1000 % COUNTS_PER_MS == 0) as usize] = [];
+
+static MILLIS: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+#[cfg(feature = "systick-tc0")]
+mod timer {
+    use crate::dev::tc0::{TC0, TCCR0A, TCCR0B, TIFR0, TIMSK0};
+    use crate::mmio::bitopts::FieldValue;
+
+    #[cfg(feature = "clock-1mhz")]
+    const CS: FieldValue<u8, TCCR0B::Register> = TCCR0B::CS0::Prescale8;
+    #[cfg(any(feature = "clock-8mhz", feature = "clock-16mhz"))]
+    const CS: FieldValue<u8, TCCR0B::Register> = TCCR0B::CS0::Prescale64;
+
+    pub fn start() {
+        TC0.tccr0b.write(TCCR0B::CS0::Stopped);
+        TC0.tccr0a.write(TCCR0A::WGM0::Ctc);
+        TC0.ocr0a.set((super::COUNTS_PER_MS - 1) as u8);
+        TC0.tcnt0.set(0);
+        TIFR0.write(TIFR0::OCF0A::SET);
+        TIMSK0.modify(TIMSK0::OCIE0A::SET);
+        TC0.tccr0b.write(CS);
+    }
+
+    #[inline(always)]
+    pub fn count() -> u8 {
+        TC0.tcnt0.get()
+    }
+
+    #[inline(always)]
+    pub fn pending() -> bool {
+        TIFR0.is_set(TIFR0::OCF0A)
+    }
+
+    #[avr_device::interrupt(atmega48p)]
+    fn TIMER0_COMPA() {
+        super::tick();
+    }
+}
+
+#[cfg(feature = "systick-tc2")]
+mod timer {
+    use crate::dev::tc2::{TC2, TCCR2A, TCCR2B, TIFR2, TIMSK2};
+    use crate::mmio::bitopts::FieldValue;
+
+    #[cfg(feature = "clock-1mhz")]
+    const CS: FieldValue<u8, TCCR2B::Register> = TCCR2B::CS2::Prescale8;
+    #[cfg(any(feature = "clock-8mhz", feature = "clock-16mhz"))]
+    const CS: FieldValue<u8, TCCR2B::Register> = TCCR2B::CS2::Prescale64;
+
+    pub fn start() {
+        TC2.tccr2b.write(TCCR2B::CS2::Stopped);
+        TC2.tccr2a.write(TCCR2A::WGM2::Ctc);
+        TC2.ocr2a.set((super::COUNTS_PER_MS - 1) as u8);
+        TC2.tcnt2.set(0);
+        TIFR2.write(TIFR2::OCF2A::SET);
+        TIMSK2.modify(TIMSK2::OCIE2A::SET);
+        TC2.tccr2b.write(CS);
+    }
+
+    #[inline(always)]
+    pub fn count() -> u8 {
+        TC2.tcnt2.get()
+    }
+
+    #[inline(always)]
+    pub fn pending() -> bool {
+        TIFR2.is_set(TIFR2::OCF2A)
+    }
+
+    #[avr_device::interrupt(atmega48p)]
+    fn TIMER2_COMPA() {
+        super::tick();
+    }
+}
+
+#[inline(always)]
+fn tick() {
+    interrupt::free(|cs| {
+        let millis = MILLIS.borrow(cs);
+        millis.set(millis.get().wrapping_add(1));
+    });
+}
+
+/// Claim the timer and start the tick from zero.
+///
+/// The counters only advance once interrupts are enabled globally.
+pub fn init() {
+    interrupt::free(|cs| {
+        MILLIS.borrow(cs).set(0);
+        timer::start();
+    });
+}
+
+/// Milliseconds since `init()`, wraps after about 49.7 days
+pub fn millis() -> u32 {
+    interrupt::free(|cs| MILLIS.borrow(cs).get())
+}
+
+/// Microseconds since `init()`, wraps after about 71.6 minutes
+///
+/// The resolution is one timer count, 8 µs at 1 and 8 MHz and 4 µs at 16 MHz.
+pub fn micros() -> u32 {
+    interrupt::free(|cs| {
+        let mut millis = MILLIS.borrow(cs).get();
+        let mut count = timer::count();
+        // A compare match since the last tick has not been counted yet as
+        // interrupts are masked, the counter has already restarted then.
+        if timer::pending() {
+            millis = millis.wrapping_add(1);
+            count = timer::count();
+        }
+        millis
+            .wrapping_mul(1000)
+            .wrapping_add(count as u32 * US_PER_COUNT)
+    })
+}
+
+impl Instant {
+    /// Current instant of the system tick
+    pub fn now() -> Instant {
+        Instant::from_micros(micros())
+    }
+}
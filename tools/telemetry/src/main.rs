@@ -0,0 +1,282 @@
+//! Decodes telemetry frames (see `m48_robo_rust::telemetry`) from a serial
+//! device, a file or stdin into CSV lines of `seq,id,values...`.
+//!
+//! The payload layout of a message ID is given as `ID=TYPES`, e.g.
+//! `-m 1=u16,u16,i16`; payloads of other messages are written as bytes.
+//! Dropped and lost frames are reported on stderr.
+//!
+//! The serial device has to be set up beforehand, e.g.
+//! `stty -F /dev/ttyUSB0 2400 raw`.
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process;
+
+use m48_robo_rust::telemetry::{Decoder, Frame};
+
+const USAGE: &str = "usage: telemetry [-m ID=TYPES]... [INPUT]
+
+Decodes telemetry frames from INPUT (default stdin) into CSV lines of
+`seq,id,values...` on stdout.
+
+  -m ID=TYPES  payload layout of message ID, TYPES is a comma separated
+               list of u8, i8, u16, i16, u32 and i32";
+
+/// Little endian payload value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Type {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+}
+
+impl Type {
+    fn parse(name: &str) -> Option<Type> {
+        Some(match name {
+            "u8" => Type::U8,
+            "i8" => Type::I8,
+            "u16" => Type::U16,
+            "i16" => Type::I16,
+            "u32" => Type::U32,
+            "i32" => Type::I32,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Type::U8 | Type::I8 => 1,
+            Type::U16 | Type::I16 => 2,
+            Type::U32 | Type::I32 => 4,
+        }
+    }
+
+    /// Value at the start of `bytes`, which holds at least `size` bytes
+    fn read(self, bytes: &[u8]) -> i64 {
+        match self {
+            Type::U8 => bytes[0] as i64,
+            Type::I8 => bytes[0] as i8 as i64,
+            Type::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as i64,
+            Type::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as i64,
+            Type::U32 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as i64,
+            Type::I32 => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as i64,
+        }
+    }
+}
+
+/// Payload layout of a message
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Layout {
+    id: u8,
+    fields: Vec<Type>,
+}
+
+impl Layout {
+    /// Parse `ID=TYPES`
+    fn parse(arg: &str) -> Result<Layout, String> {
+        let mut parts = arg.splitn(2, '=');
+        let id = parts.next().unwrap_or("");
+        let types = parts
+            .next()
+            .ok_or_else(|| format!("missing `=` in layout `{}`", arg))?;
+        let id = id
+            .parse()
+            .map_err(|_| format!("invalid message ID `{}`", id))?;
+        let fields = types
+            .split(',')
+            .map(|name| Type::parse(name).ok_or_else(|| format!("unknown type `{}`", name)))
+            .collect::<Result<_, _>>()?;
+        Ok(Layout { id, fields })
+    }
+
+    /// Values of `payload`, `None` if its length doesn't match
+    fn values(&self, payload: &[u8]) -> Option<Vec<i64>> {
+        let size: usize = self.fields.iter().map(|field| field.size()).sum();
+        if payload.len() != size {
+            return None;
+        }
+        let mut offset = 0;
+        let values = self
+            .fields
+            .iter()
+            .map(|field| {
+                let value = field.read(&payload[offset..]);
+                offset += field.size();
+                value
+            })
+            .collect();
+        Some(values)
+    }
+}
+
+/// Writes frames as CSV and keeps track of the sequence numbers
+struct Log<W> {
+    out: W,
+    layouts: Vec<Layout>,
+    next_seq: Option<u8>,
+    frames: u64,
+    lost: u64,
+    dropped: u64,
+}
+
+impl<W: Write> Log<W> {
+    fn new(out: W, layouts: Vec<Layout>) -> Log<W> {
+        Log {
+            out,
+            layouts,
+            next_seq: None,
+            frames: 0,
+            lost: 0,
+            dropped: 0,
+        }
+    }
+
+    fn frame(&mut self, frame: &Frame) -> io::Result<()> {
+        if let Some(next) = self.next_seq {
+            let lost = frame.seq.wrapping_sub(next);
+            if lost != 0 {
+                eprintln!("lost {} frame(s) before seq {}", lost, frame.seq);
+                self.lost += lost as u64;
+            }
+        }
+        self.next_seq = Some(frame.seq.wrapping_add(1));
+        self.frames += 1;
+
+        let layout = self.layouts.iter().find(|layout| layout.id == frame.id);
+        let values = match layout.map(|layout| layout.values(frame.payload)) {
+            Some(Some(values)) => values,
+            Some(None) => {
+                eprintln!(
+                    "payload of message {} at seq {} doesn't match its layout",
+                    frame.id, frame.seq
+                );
+                frame.payload.iter().map(|&byte| byte as i64).collect()
+            }
+            None => frame.payload.iter().map(|&byte| byte as i64).collect(),
+        };
+        write!(self.out, "{},{}", frame.seq, frame.id)?;
+        for value in values {
+            write!(self.out, ",{}", value)?;
+        }
+        writeln!(self.out)
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let mut layouts = Vec::new();
+    let mut input = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            "-m" => {
+                let layout = args.next().ok_or("missing layout after -m")?;
+                layouts.push(Layout::parse(layout)?);
+            }
+            _ if input.is_none() => input = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument `{}`", arg)),
+        }
+    }
+
+    let mut reader: Box<dyn Read> = match input.as_deref() {
+        None | Some("-") => Box::new(io::stdin()),
+        Some(path) => Box::new(File::open(path).map_err(|e| format!("{}: {}", path, e))?),
+    };
+    let stdout = io::stdout();
+    let mut log = Log::new(stdout.lock(), layouts);
+    let mut decoder = Decoder::new(vec![0; 256]);
+    let mut buffer = [0; 256];
+    loop {
+        let len = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.to_string()),
+        };
+        for &byte in &buffer[..len] {
+            match decoder.feed(byte) {
+                Some(Ok(frame)) => log.frame(&frame).map_err(|e| e.to_string())?,
+                Some(Err(error)) => {
+                    eprintln!("dropped frame: {:?}", error);
+                    log.dropped += 1;
+                }
+                None => {}
+            }
+        }
+    }
+    eprintln!(
+        "{} frames, {} lost, {} dropped",
+        log.frames, log.lost, log.dropped
+    );
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(error) = run(&args) {
+        eprintln!("telemetry: {}\n\n{}", error, USAGE);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use m48_robo_rust::telemetry::{Encoder, Message, MAX_FRAME};
+
+    #[test]
+    fn layouts() {
+        assert_eq!(
+            Layout::parse("2=u16,i8,i32"),
+            Ok(Layout {
+                id: 2,
+                fields: vec![Type::U16, Type::I8, Type::I32],
+            })
+        );
+        assert!(Layout::parse("2").is_err());
+        assert!(Layout::parse("256=u8").is_err());
+        assert!(Layout::parse("2=u16,f32").is_err());
+
+        let layout = Layout::parse("1=i16,u8,u32").unwrap();
+        assert_eq!(
+            layout.values(&[0xFE, 0xFF, 7, 0, 0, 0, 0x80]),
+            Some(vec![-2, 7, 0x8000_0000])
+        );
+        assert_eq!(layout.values(&[0xFE, 0xFF]), None);
+    }
+
+    #[test]
+    fn csv() {
+        let mut encoder = Encoder::new();
+        let mut log = Log::new(Vec::new(), vec![Layout::parse("1=u16,i16").unwrap()]);
+        let mut decoder = Decoder::new([0; MAX_FRAME]);
+        let messages = [
+            Message::new(1).u16(512).i16(-3),
+            Message::new(5).u8(9).u8(0),
+            // Doesn't match the layout
+            Message::new(1).u8(1),
+        ];
+        for (i, message) in messages.iter().enumerate() {
+            let mut buffer = [0; MAX_FRAME];
+            let bytes = encoder.encode(message, &mut buffer);
+            // The second frame is lost
+            if i == 1 {
+                continue;
+            }
+            for &byte in bytes {
+                if let Some(frame) = decoder.feed(byte) {
+                    log.frame(&frame.unwrap()).unwrap();
+                }
+            }
+        }
+        assert_eq!(String::from_utf8(log.out).unwrap(), "0,1,512,-3\n2,1,1\n");
+        assert_eq!((log.frames, log.lost), (2, 1));
+    }
+}